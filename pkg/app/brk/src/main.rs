@@ -37,17 +37,17 @@ fn main() -> isize {
                 };
 
                 match sys_brk(Some(addr)) {
-                    Some(new_brk) => {
+                    Ok(new_brk) => {
                         println!("Brk to {:#x} success, new brk addr: {:#x}", addr, new_brk)
                     }
-                    None => println!("Brk to {:#x} failed", addr),
+                    Err(e) => println!("Brk to {:#x} failed: {}", addr, e),
                 }
 
-                sys_stat();
+                print_processes();
             }
             "cur" => match sys_brk(None) {
-                Some(brk) => println!("Current brk addr: {:#x}", brk),
-                None => println!("Failed to get current brk addr"),
+                Ok(brk) => println!("Current brk addr: {:#x}", brk),
+                Err(e) => println!("Failed to get current brk addr: {}", e),
             },
             "exit" => {
                 // sys_exit(0);
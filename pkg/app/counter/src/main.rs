@@ -14,7 +14,7 @@ fn main() -> isize {
     let mut pids = [0u16; THREAD_COUNT];
 
     for i in 0..THREAD_COUNT {
-        let pid = sys_fork();
+        let pid = sys_vfork().expect("Failed to fork");
         if pid == 0 {
             do_counter_inc();
             sys_exit(0);
@@ -25,11 +25,11 @@ fn main() -> isize {
 
     let cpid = sys_get_pid();
     println!("process #{} holds threads: {:?}", cpid, &pids);
-    sys_stat();
+    print_processes();
 
     for i in 0..THREAD_COUNT {
         println!("#{} waiting for #{}...", cpid, pids[i]);
-        sys_wait_pid(pids[i]);
+        let _ = sys_wait_pid(pids[i]);
     }
 
     println!("COUNTER result: {}", unsafe { COUNTER });
@@ -3,7 +3,6 @@
 
 extern crate lib;
 use lib::*;
-use core::hint::spin_loop;
 
 // DEMO_MODE 取值：0 - 正常，1 - 死锁示例，2 - 饥饿示例
 
@@ -37,13 +36,9 @@ fn rand_range(min: u64, max: u64) -> u64 {
     min + (r % (max - min))
 }
 
-// 模拟延迟，time 为循环次数
-#[inline(never)]
-#[unsafe(no_mangle)]
+// 模拟延迟，time 为毫秒数，期间让出 CPU
 fn delay(time: u64) {
-    for _ in 0..time {
-        spin_loop();
-    }
+    sys_sleep(Duration::milliseconds(time as i64));
 }
 
 //
@@ -56,16 +51,16 @@ fn philosopher(id: usize, demo: u64) -> ! {
         // --- 思考阶段 ---
         // 在死锁模式下使用固定、较短的延迟，让所有哲学家近乎同时饥饿
         if demo == 1 {
-            delay(0x01000);
+            delay(10);
         } else {
             if demo == 2 {
                 if id != 1 {
-                    delay(0x100);
+                    delay(1);
                 } else if id == 1{
-                    delay(0x10000);
+                    delay(100);
                 }
             } else {
-                delay(rand_range(0x30000, 0x60000));
+                delay(rand_range(300, 600));
             }
         }
         // --- 就餐阶段 ---
@@ -74,7 +69,7 @@ fn philosopher(id: usize, demo: u64) -> ! {
             // 所有哲学家都先拿左筷子，再拿右筷子
             CHOPSTICKS[id].wait();
             println!("Philosopher {} (PID {}) picked up left chopstick {}.", id, pid, id);
-            delay(0x10000);  // 模拟延迟
+            delay(100);  // 模拟延迟
             CHOPSTICKS[(id + 1) % PHILOSOPHER_COUNT].wait();
             println!("Philosopher {} (PID {}) picked up right chopstick {}.", id, pid, (id + 1) % PHILOSOPHER_COUNT);
         } else {
@@ -99,7 +94,7 @@ fn philosopher(id: usize, demo: u64) -> ! {
         }
 
         println!("Philosopher {} (PID {}) is eating (turn {}).", id, pid, turn + 1);
-        delay(rand_range(0x10000, 0x20000));  // 吃饭延迟
+        delay(rand_range(100, 200));  // 吃饭延迟
 
         // 放下筷子
         CHOPSTICKS[id].signal();
@@ -134,7 +129,7 @@ fn main() -> isize {
     }
     let mut pids = [0u16; PHILOSOPHER_COUNT];
     for id in 0..PHILOSOPHER_COUNT {
-        let pid = sys_fork();
+        let pid = sys_fork().expect("Failed to fork");
         if pid == 0 {
             philosopher(id, demo);
         } else {
@@ -143,10 +138,10 @@ fn main() -> isize {
     }
     let parent_pid = sys_get_pid();
     println!("#{}: Created philosophers: {:?}", parent_pid, &pids);
-    sys_stat();
+    print_processes();
     for &child in pids.iter() {
         println!("#{} waiting for philosopher #{}...", parent_pid, child);
-        sys_wait_pid(child);
+        let _ = sys_wait_pid(child);
     }
     println!("#{}: All philosophers have finished dinner.", parent_pid);
     0
@@ -163,23 +163,23 @@ fn main() -> isize {
     SEM_LT.signal();
     
     // 创建 3 个子进程分别用于输出 ">", "<" 和 "_"
-    let pid1 = sys_fork();
+    let pid1 = sys_vfork().expect("Failed to fork");
     if pid1 == 0 {
         fish_gt();
     }
-    let pid2 = sys_fork();
+    let pid2 = sys_vfork().expect("Failed to fork");
     if pid2 == 0 {
         fish_lt();
     }
-    let pid3 = sys_fork();
+    let pid3 = sys_vfork().expect("Failed to fork");
     if pid3 == 0 {
         fish_us();
     }
     
     // 父进程等待所有子进程结束
-    sys_wait_pid(pid1);
-    sys_wait_pid(pid2);
-    sys_wait_pid(pid3);
+    let _ = sys_wait_pid(pid1);
+    let _ = sys_wait_pid(pid2);
+    let _ = sys_wait_pid(pid3);
     println!("\nAll child processes have exited.");
     sys_exit(0);
 }
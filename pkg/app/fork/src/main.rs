@@ -4,6 +4,7 @@
 extern crate alloc;
 extern crate lib;
 
+use alloc::vec::Vec;
 use lib::*;
 
 static mut M: u64 = 0xdeadbeef;
@@ -12,9 +13,9 @@ fn main() -> isize {
     let mut c = 32;
     let m_ptr = &raw mut M;
 
-    // do not alloc heap before `fork`
-    // which may cause unexpected behavior since we won't copy the heap in `fork`
-    let pid = sys_fork();
+    // the whole address space, heap included, is copied on write
+    let mut v: Vec<u64> = (0..16).collect();
+    let pid = sys_fork().expect("Failed to fork");
 
     if pid == 0 {
         println!("I am the child process, pid: {}", pid);
@@ -27,27 +28,35 @@ fn main() -> isize {
             println!("child changed the value of M: {:#x}", *m_ptr);
         }
 
+        v.push(16);
+        v[0] = 0x2333;
+        assert_eq!(v.len(), 17);
+
         c += 32;
     } else {
         println!("I am the parent process");
 
-        sys_stat();
+        print_processes();
 
         assert_eq!(c, 32);
 
         println!("Waiting for child to exit...");
 
-        let ret = sys_wait_pid(pid);
+        let ret = sys_wait_pid(pid).expect("Failed to wait for child");
 
-        println!("Child exited with status {}", ret);
+        println!("Child {}", ret);
 
-        assert_eq!(ret, 64);
+        assert_eq!(ret, ExitStatus::Exited(64));
 
         unsafe {
             println!("parent read value of M: {:#x}", *m_ptr);
-            assert_eq!(*m_ptr, 0x2333);
+            // the write of the child is not visible to the parent
+            assert_eq!(*m_ptr, 0xdeadbeef);
         }
 
+        // neither is the heap
+        assert_eq!(v, (0..16).collect::<Vec<u64>>());
+
         c += 1024;
 
         assert_eq!(c, 1056);
@@ -5,9 +5,17 @@ use lib::*;
 
 extern crate lib;
 
-fn main() -> isize {
+fn main(args: &[&str]) -> isize {
     println!("Hello, world!!!");
 
+    for (i, arg) in args.iter().enumerate() {
+        println!("argv[{}] = {}", i, arg);
+    }
+
+    for (key, val) in envs() {
+        println!("{}={}", key, val);
+    }
+
     233
 }
 
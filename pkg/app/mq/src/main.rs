@@ -35,7 +35,7 @@ fn main() -> isize {
     let total_children = 1 + TOTAL_CUSTOMERS;
     let mut pids = [0u16; 1 + TOTAL_CUSTOMERS];
     for (i, pid_ref) in pids.iter_mut().enumerate() {
-        let pid = sys_fork();
+        let pid = sys_vfork().expect("Failed to fork");
         if pid == 0 {
             if i == 0 {
                 // 第一个子进程作为咖啡师
@@ -51,12 +51,12 @@ fn main() -> isize {
 
     let parent_pid = sys_get_pid();
     println!("#{}: Created children: {:?}", parent_pid, &pids);
-    sys_stat();
+    print_processes();
 
     // 父进程等待所有子进程退出
     for &child in pids.iter() {
         println!("#{} waiting for child #{}...", parent_pid, child);
-        sys_wait_pid(child);
+        let _ = sys_wait_pid(child);
     }
 
     // 释放信号量资源（模拟清理）
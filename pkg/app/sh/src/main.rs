@@ -9,13 +9,14 @@ mod utils; // 确保 utils 模块被正确导入
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use lib::*;
+use lib::signal::Signal;
 use owo_colors::OwoColorize;
 use alloc::borrow::Cow;
 
 /// 定义简单的高亮函数，根据预定义命令高亮首个单词
 fn highlight(input: &str) -> String {
     // 定义预期高亮的命令列表
-    let commands = ["ps", "ls", "exec", "kill", "help", "clear", "exit", "cat", "lsapp", "cd", "pwd"]; // 添加 cd 和 pwd
+    let commands = ["ps", "ls", "exec", "kill", "help", "clear", "exit", "cat", "lsapp", "cd", "pwd", "strace", "pstree", "stat", "mount", "umount", "sync", "echo", "mkdir", "rm", "mv"]; // 添加 cd 和 pwd
     // 尝试拆分输入，取第一个单词进行匹配
     if let Some((first, rest)) = input.split_once(' ') {
         for &cmd in commands.iter() {
@@ -213,6 +214,14 @@ fn normalize_path(current_dir: &str, target_path: &str) -> String {
     }
 }
 
+/// 含 '/' 的程序名按路径解析（如 ./hello、/APP/HELLO），否则按应用名查找
+fn resolve_program(current_dir: &str, name: &str) -> String {
+    if name.contains('/') {
+        normalize_path(current_dir, name)
+    } else {
+        name.to_string()
+    }
+}
 
 fn main() -> isize {
     utils::show_welcome_text();
@@ -221,6 +230,7 @@ fn main() -> isize {
     let mut current_working_directory = String::from("/");
 
     loop {
+        services::reap_jobs();
         // 调用 utils::print_prompt 并传递 CWD
         utils::print_prompt(&current_working_directory); // <--- 使用 utils::print_prompt
         lib::stdout().flush();
@@ -249,7 +259,63 @@ fn main() -> isize {
                 // println!(); // println! 会在 read_line_history 中处理回车时打印
                 break;
             }
-            &"ps" => sys_stat(),
+            &"ps" => services::ps(),
+            &"pstree" => services::pstree(),
+            &"stat" => services::stat(),
+            &"echo" => {
+                let args = &line[1..];
+                match args.iter().position(|arg| *arg == ">" || *arg == ">>") {
+                    Some(i) if i + 2 == args.len() => {
+                        let path = normalize_path(&current_working_directory, args[i + 1]);
+                        services::write_file(&path, &args[..i].join(" "), args[i] == ">>");
+                    }
+                    Some(_) => println!("Usage: echo [text] [> | >> <file>]"),
+                    None => println!("{}", args.join(" ")),
+                }
+            }
+            &"mkdir" => {
+                if line.len() < 2 {
+                    println!("Usage: mkdir <dir>");
+                } else {
+                    services::mkdir(&normalize_path(&current_working_directory, line[1]));
+                }
+            }
+            &"rm" => {
+                if line.len() < 2 {
+                    println!("Usage: rm <path>");
+                } else {
+                    services::rm(&normalize_path(&current_working_directory, line[1]));
+                }
+            }
+            &"mv" => {
+                if line.len() < 3 {
+                    println!("Usage: mv <src> <dst>");
+                } else {
+                    let src = normalize_path(&current_working_directory, line[1]);
+                    let dst = normalize_path(&current_working_directory, line[2]);
+                    services::mv(&src, &dst);
+                }
+            }
+            &"mount" => {
+                let read_only = line.get(1) == Some(&"-r");
+                let args = &line[1 + read_only as usize..];
+                match args {
+                    [] => services::cat_file("/proc/mounts"),
+                    [fs_type, source, target] => {
+                        let target = normalize_path(&current_working_directory, target);
+                        services::mount(fs_type, source, &target, read_only);
+                    }
+                    _ => println!("Usage: mount [-r] <type> <source> <target>"),
+                }
+            }
+            &"sync" => services::sync(),
+            &"umount" => {
+                if line.len() < 2 {
+                    println!("Usage: umount <target>");
+                } else {
+                    services::umount(&normalize_path(&current_working_directory, line[1]));
+                }
+            }
             &"ls" => {
                 let path_arg = if line.len() >= 2 { line[1] } else { "." }; // 默认为当前目录
                 let path_to_list = normalize_path(&current_working_directory, path_arg);
@@ -282,26 +348,62 @@ fn main() -> isize {
             }
             &"help" => utils::show_help_text(),
             &"clear" => utils::clear_screen(),
-            &"lsapp" => {
-                sys_list_app();
-            }
+            &"lsapp" => services::cat_file("/proc/apps"),
             &"exec" => {
                 if line.len() < 2 {
                     println!("Usage: exec <program_name> [args...]");
                 } else {
-                    println!("Executing: {}", line[1]);
-                    services::exec(line[1]);
+                    let program = resolve_program(&current_working_directory, line[1]);
+                    println!("Executing: {}", program);
+                    let pwd = format!("PWD={}", current_working_directory);
+                    services::exec(&program, &line[1..], &[&pwd]);
                     println!("Program {} executed.", line[1]);
                 }
             }
+            &"nohup" => {
+                if line.len() < 2 {
+                    println!("Usage: nohup <program_name> [args...]");
+                } else {
+                    let program = resolve_program(&current_working_directory, line[1]);
+                    let pwd = format!("PWD={}", current_working_directory);
+                    services::nohup(&program, &line[1..], &[&pwd]);
+                }
+            }
+            &"strace" => {
+                if line.len() < 2 {
+                    println!("Usage: strace <program_name> [args...]");
+                } else {
+                    let program = resolve_program(&current_working_directory, line[1]);
+                    let pwd = format!("PWD={}", current_working_directory);
+                    services::strace(&program, &line[1..], &[&pwd]);
+                }
+            }
             &"kill" => {
                  if line.len() < 2 {
-                    println!("Usage: kill <pid>");
+                    println!("Usage: kill <pid> [signal]");
+                } else {
+                    let sig = match line.get(2) {
+                        Some(sig) => services::parse_signal(sig),
+                        None => Some(Signal::Term.number()),
+                    };
+                    match (line[1].parse::<u16>(), sig) {
+                        (Ok(pid), Some(sig)) => services::kill(pid, sig),
+                        (Err(_), _) => errln!("kill: Invalid PID: {}", line[1]),
+                        (_, None) => errln!("kill: Invalid signal: {}", line[2]),
+                    }
+                }
+            }
+            &"nice" => {
+                if line.len() < 3 {
+                    println!("Usage: nice <pid> <nice>");
                 } else {
-                    if let Ok(pid) = line[1].parse::<u16>() {
-                        services::kill(pid);
-                    } else {
-                        errln!("kill: Invalid PID: {}", line[1]);
+                    match (line[1].parse::<u16>(), line[2].parse::<isize>()) {
+                        (Ok(pid), Ok(nice)) => match sys_set_priority(pid, nice) {
+                            Ok(nice) => println!("process #{} nice = {}", pid, nice),
+                            Err(e) => errln!("nice: #{}: {}", pid, e),
+                        },
+                        (Err(_), _) => errln!("nice: Invalid PID: {}", line[1]),
+                        (_, Err(_)) => errln!("nice: Invalid value: {}", line[2]),
                     }
                 }
             }
@@ -1,5 +1,8 @@
 use lib::*;
+use lib::signal::Signal;
+use alloc::string::String;
 use alloc::vec;
+use alloc::vec::Vec;
 
 const CAT_BUFFER_SIZE: usize = 512;
 
@@ -8,18 +11,18 @@ pub fn cat_file(path: &str) {
         Ok(fd) => {
             let mut buffer = vec![0u8; CAT_BUFFER_SIZE];
             loop {
-                let bytes_read_isize = read(fd, &mut buffer); // read returns isize
+                let bytes_read = match read(fd, &mut buffer) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        errln!("cat: read error: {}", e);
+                        break;
+                    }
+                };
 
-                if bytes_read_isize < 0 {
-                    // Error case
-                    errln!("cat: read error (code: {})", bytes_read_isize);
-                    break;
-                } else if bytes_read_isize == 0 {
+                if bytes_read == 0 {
                     // EOF
                     break;
                 } else {
-                    // Successfully read bytes_read_isize bytes
-                    let bytes_read = bytes_read_isize as usize;
                     // Attempt to print as UTF-8, fallback for invalid sequences
                     match core::str::from_utf8(&buffer[..bytes_read]) {
                         Ok(s) => print!("{}", s), // Use the imported print
@@ -37,36 +40,262 @@ pub fn cat_file(path: &str) {
                     }
                 }
             }
-            if let Err(e) = close(fd) { // Assuming close returns Result<(), &'static str>
-                errln!("cat: close error: {:?}", e);
+            if let Err(e) = close(fd) {
+                errln!("cat: close error: {}", e);
             }
         }
         Err(e) => {
-            errln!("cat: open error for '{}': {:?}", path, e);
+            errln!("cat: open error for '{}': {}", path, e);
+        }
+    }
+}
+
+pub fn exec(name: &str, args: &[&str], envs: &[&str]) {
+    let start = sys_time();
+
+    let pid = match spawn(name, args, envs) {
+        Ok(pid) => pid,
+        Err(e) => {
+            errln!("failed to spawn process {}: {}", name, e);
+            return;
+        }
+    };
+
+    wait(pid, start);
+}
+
+/// 跟踪子进程的所有系统调用
+pub fn strace(name: &str, args: &[&str], envs: &[&str]) {
+    // 子进程会继承跟踪标志，因此只在 spawn 期间跟踪 shell 自身
+    if let Err(e) = sys_trace(0, true) {
+        errln!("strace: {}", e);
+        return;
+    }
+
+    let start = sys_time();
+    let pid = spawn(name, args, envs);
+    let _ = sys_trace(0, false);
+
+    match pid {
+        Ok(pid) => wait(pid, start),
+        Err(e) => errln!("failed to spawn process {}: {}", name, e),
+    }
+}
+
+/// 含 '/' 的名字视为文件系统中的路径，否则为启动时加载的应用
+fn spawn(name: &str, args: &[&str], envs: &[&str]) -> SyscallResult<u16> {
+    if name.contains('/') {
+        sys_spawn(name, args, envs)
+    } else {
+        sys_spawn(name.to_ascii_lowercase().as_str(), args, envs)
+    }
+}
+
+fn wait(pid: u16, start: Duration) {
+    let status = match sys_wait_pid(pid) {
+        Ok(status) => status,
+        Err(e) => {
+            errln!("failed to wait for process #{}: {}", pid, e);
+            return;
+        }
+    };
+    let time = sys_time() - start;
+
+    println!(
+        "process {} in {}.{:03}s",
+        status,
+        time.num_seconds(),
+        time.num_milliseconds() % 1000
+    );
+}
+
+/// 在后台运行程序，不等待其退出
+pub fn nohup(name: &str, args: &[&str], envs: &[&str]) {
+    match spawn(name, args, envs) {
+        Ok(pid) => println!("[{}] running in background", pid),
+        Err(e) => errln!("failed to spawn process {}: {}", name, e),
+    }
+}
+
+/// 回收已经退出的后台进程，不阻塞
+pub fn reap_jobs() {
+    while let Ok(Some((pid, status))) = sys_waitpid(WAIT_ANY, WNOHANG) {
+        println!("[{}] {}", pid, status);
+    }
+}
+
+pub fn kill(pid: u16, sig: usize) {
+    if let Err(e) = sys_kill(pid, sig) {
+        errln!("kill: #{}: {}", pid, e);
+    }
+}
+
+/// Parse a signal given as a number or a name, e.g. `9`, `KILL` or `SIGKILL`
+pub fn parse_signal(s: &str) -> Option<usize> {
+    if let Ok(sig) = s.parse::<usize>() {
+        return Some(sig);
+    }
+
+    let name = s.to_ascii_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    let sig = match name {
+        "INT" => Signal::Int,
+        "KILL" => Signal::Kill,
+        "USR1" => Signal::Usr1,
+        "SEGV" => Signal::Segv,
+        "USR2" => Signal::Usr2,
+        "TERM" => Signal::Term,
+        "CHLD" => Signal::Chld,
+        _ => return None,
+    };
+    Some(sig.number())
+}
+
+/// 显示运行时间和内存使用情况，数据来自 procfs
+pub fn stat() {
+    print!("Uptime:       ");
+    cat_file("/proc/uptime");
+    cat_file("/proc/meminfo");
+}
+
+/// 将一行文本写入文件，`append` 时追加到文件末尾，否则覆盖原有内容
+pub fn write_file(path: &str, text: &str, append: bool) {
+    let flags = O_CREATE | if append { O_APPEND } else { O_TRUNC };
+    let fd = match sys_open(path, flags) {
+        Ok(fd) => fd,
+        Err(e) => {
+            errln!("echo: {}: {}", path, e);
+            return;
         }
+    };
+
+    let line = format!("{}\n", text);
+    if let Err(e) = sys_write(fd, line.as_bytes()) {
+        errln!("echo: {}: {}", path, e);
     }
+    let _ = close(fd);
 }
 
-pub fn exec(name: &str) {
-    // let start = sys_time();
+/// 创建目录
+pub fn mkdir(path: &str) {
+    if let Err(e) = sys_mkdir(path) {
+        errln!("mkdir: {}: {}", path, e);
+    }
+}
 
-    let pid = sys_spawn(name.to_ascii_lowercase().as_str());
+/// 删除文件，路径为目录时删除空目录
+pub fn rm(path: &str) {
+    let result = match sys_unlink(path) {
+        Err(SyscallError::IsADirectory) => sys_rmdir(path),
+        result => result,
+    };
+    if let Err(e) = result {
+        errln!("rm: {}: {}", path, e);
+    }
+}
 
-    if pid == 0 {
-        errln!("failed to spawn process: {}", name);
+/// 移动或重命名文件和目录
+pub fn mv(src: &str, dst: &str) {
+    if let Err(e) = sys_rename(src, dst) {
+        errln!("mv: {} -> {}: {}", src, dst, e);
+    }
+}
+
+/// 挂载文件系统，类型为 fat、tmpfs、procfs 或 devfs
+pub fn mount(fs_type: &str, source: &str, target: &str, read_only: bool) {
+    let Some(ty) = FsType::from_name(fs_type) else {
+        errln!("mount: unknown filesystem type: {}", fs_type);
         return;
+    };
+    let flags = if read_only { MOUNT_READ_ONLY } else { 0 };
+    if let Err(e) = sys_mount(source, target, ty, flags) {
+        errln!("mount: {}: {}", target, e);
+    }
+}
+
+/// 卸载挂载在 `target` 的文件系统
+pub fn umount(target: &str) {
+    if let Err(e) = sys_umount(target) {
+        errln!("umount: {}: {}", target, e);
     }
+}
 
-    let ret = sys_wait_pid(pid);
-    // let time = sys_time() - start;
+/// 将文件系统缓存写回磁盘
+pub fn sync() {
+    if let Err(e) = sys_sync() {
+        errln!("sync: {}", e);
+    }
+}
 
+/// 以内存大小的合适单位显示
+fn format_size(bytes: u64) -> String {
+    if bytes >= 1 << 20 {
+        format!("{}.{}M", bytes >> 20, (bytes >> 10) % 1024 * 10 / 1024)
+    } else {
+        format!("{}K", bytes >> 10)
+    }
+}
+
+/// 显示进程列表，数据来自 `sys_proc_info`
+pub fn ps() {
     println!(
-        "process exited with code {}",
-        ret,
-        // time.num_seconds()
+        "{:>5} {:>5} {:<16} {:>2} {:>4} {:>3} {:>4} {:>8} {:>8} {:>7} {:>9}",
+        "PID", "PPID", "NAME", "ST", "NICE", "LVL", "CPU", "UTICKS", "KTICKS", "MEM", "START"
     );
+    for info in sys_proc_info() {
+        let cpu = if info.is_running() {
+            format!("{}", info.cpu)
+        } else {
+            String::from("-")
+        };
+        let start = info.start_ns / 1_000_000;
+        println!(
+            "{:>5} {:>5} {:<16} {:>2} {:>4} {:>3} {:>4} {:>8} {:>8} {:>7} {:>5}.{:03}",
+            info.pid,
+            info.ppid,
+            info.name(),
+            info.status,
+            info.nice,
+            info.level,
+            cpu,
+            info.user_ticks,
+            info.kernel_ticks,
+            format_size(info.memory()),
+            start / 1000,
+            start % 1000
+        );
+    }
 }
 
-pub fn kill(pid: u16) {
-    sys_kill(pid);
+/// 按父子关系以树形显示进程
+pub fn pstree() {
+    let infos = sys_proc_info();
+    // 父进程不存在的进程作为树根
+    for root in infos
+        .iter()
+        .filter(|info| !infos.iter().any(|p| p.pid == info.ppid))
+    {
+        print_tree(&infos, root, &mut String::new(), true, true);
+    }
+}
+
+fn print_tree(infos: &[ProcInfo], info: &ProcInfo, prefix: &mut String, last: bool, root: bool) {
+    if root {
+        println!("{}({})", info.name(), info.pid);
+    } else {
+        let branch = if last { "└─ " } else { "├─ " };
+        println!("{}{}{}({})", prefix, branch, info.name(), info.pid);
+    }
+
+    let len = prefix.len();
+    if !root {
+        prefix.push_str(if last { "   " } else { "│  " });
+    }
+
+    let children: Vec<&ProcInfo> = infos.iter().filter(|c| c.ppid == info.pid && c.pid != info.pid).collect();
+    for (i, child) in children.iter().enumerate() {
+        print_tree(infos, child, prefix, i + 1 == children.len(), false);
+    }
+
+    prefix.truncate(len);
 }
@@ -86,15 +86,26 @@ fn format_cmds(cate: &str, actions: &[Action]) -> String {
 
 struct Action(&'static str, Option<&'static str>, &'static str);
 
-const ACTIONS_MAP: [Action; 9] = [
+const ACTIONS_MAP: [Action; 20] = [
     Action("help", None, "show this help"),
     Action("ps", None, "show process list"),
+    Action("pstree", None, "show process tree"),
+    Action("stat", None, "show uptime and memory usage"),
     Action("ls", None, "list directory"),
     Action("cd", Some("<path>"), "change directory"),
     Action("cat", Some("<file>"), "show file content"),
+    Action("mkdir", Some("<dir>"), "create directory"),
+    Action("rm", Some("<path>"), "remove file or empty directory"),
+    Action("mv", Some("<src> <dst>"), "move or rename"),
+    Action("echo", Some("[args]"), "<text> [> | >> <file>], print or write to file"),
+    Action("mount", Some("[args]"), "[-r] <type> <src> <dir>, list mounts without args"),
+    Action("umount", Some("<dir>"), "unmount filesystem"),
+    Action("sync", None, "write cached blocks to disk"),
     Action("exec", Some("<file>"), "execute file"),
     Action("nohup", Some("<file>"), "execute file in background"),
-    Action("kill", Some("<pid>"), "kill process"),
+    Action("strace", Some("<file>"), "trace syscalls"),
+    Action("kill", Some("<pid> [sig]"), "send signal to process"),
+    Action("nice", Some("<pid> <nice>"), "set process priority"),
     Action("clear", None, "clear screen"),
 ];
 
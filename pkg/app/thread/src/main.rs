@@ -32,7 +32,7 @@ fn main() -> isize {
     let result = factorial(n);
 
     // print system status
-    sys_stat();
+    print_processes();
 
     // print result
     println!("The factorial of {} under modulo {} is {}.", n, MOD, result);
@@ -0,0 +1,78 @@
+#![no_std]
+#![no_main]
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use lib::*;
+
+extern crate lib;
+
+/// Time between two refreshes
+const REFRESH_MS: i64 = 1000;
+/// Keyboard is polled at this interval while waiting
+const POLL_MS: i64 = 50;
+
+fn main() -> isize {
+    let mut last: BTreeMap<u16, u64> = BTreeMap::new();
+
+    loop {
+        let infos = sys_proc_info();
+
+        // %CPU is the share of the ticks used by every process since the last refresh
+        let deltas: BTreeMap<u16, u64> = infos
+            .iter()
+            .map(|info| {
+                let prev = last.get(&info.pid).copied().unwrap_or(0);
+                (info.pid, info.ticks().saturating_sub(prev))
+            })
+            .collect();
+        let busy = deltas.values().sum::<u64>().max(1);
+
+        print!("\x1b[1;1H\x1b[2J");
+        let uptime = sys_time();
+        println!(
+            "top - up {}.{:03}s, {} processes, press 'q' to quit\n",
+            uptime.num_seconds(),
+            uptime.num_milliseconds() % 1000,
+            infos.len()
+        );
+        println!(
+            "{:>5} {:>5} {:<16} {:>2} {:>4} {:>3} {:>3} {:>6} {:>8} {:>8}",
+            "PID", "PPID", "NAME", "ST", "NICE", "LVL", "CPU", "%CPU", "TICKS", "MEM(K)"
+        );
+        for info in infos.iter() {
+            let cpu = if info.is_running() {
+                format!("{}", info.cpu)
+            } else {
+                String::from("-")
+            };
+            let share = deltas[&info.pid] * 1000 / busy;
+            println!(
+                "{:>5} {:>5} {:<16} {:>2} {:>4} {:>3} {:>3} {:>4}.{} {:>8} {:>8}",
+                info.pid,
+                info.ppid,
+                info.name(),
+                info.status,
+                info.nice,
+                info.level,
+                cpu,
+                share / 10,
+                share % 10,
+                info.ticks(),
+                info.memory() >> 10
+            );
+        }
+        stdout().flush();
+
+        last = infos.iter().map(|info| (info.pid, info.ticks())).collect();
+
+        for _ in 0..REFRESH_MS / POLL_MS {
+            if let Some('q' | 'Q' | '\x03') = stdin().try_read_key() {
+                return 0;
+            }
+            sys_sleep(Duration::milliseconds(POLL_MS));
+        }
+    }
+}
+
+entry!(main);
@@ -18,6 +18,8 @@ pub struct Config<'a> {
     /// Load apps into memory, when no fs implemented in kernel
     pub load_apps: bool,
     pub log_level: &'a str,
+    /// Scheduling policy of the kernel: `rr`, `priority` or `mlfq`
+    pub sched_policy: &'a str,
 }
 
 const DEFAULT_CONFIG: Config = Config {
@@ -29,6 +31,7 @@ const DEFAULT_CONFIG: Config = Config {
     cmdline: "",
     load_apps: true,
     log_level: "info",
+    sched_policy: "rr",
 };
 
 impl<'a> Config<'a> {
@@ -67,6 +70,7 @@ impl<'a> Config<'a> {
             "kernel_stack_auto_grow" => self.kernel_stack_auto_grow = r10,
             "cmdline" => self.cmdline = value,
             "load_apps" => self.load_apps = r10 != 0,
+            "sched_policy" => self.sched_policy = value,
             _ => warn!("undefined config key: {}", key),
         }
     }
@@ -49,6 +49,9 @@ pub struct BootInfo {
 
     pub log_level: &'static str,
 
+    /// Scheduling policy, see `sched_policy` in the boot config
+    pub sched_policy: &'static str,
+
     pub load_apps: Option<AppList>,
 }
 
@@ -135,6 +135,7 @@ fn efi_main() -> Status {
         physical_memory_offset: config.physical_memory_offset,
         load_apps: apps,
         log_level: config.log_level,
+        sched_policy: config.sched_policy,
         system_table,
     };
 
@@ -0,0 +1,327 @@
+//! Device nodes, mounted at `/dev`
+//!
+//! - `null`: discards writes, reads nothing
+//! - `zero`: reads zeros
+//! - `random`: reads random bytes, from `rdrand` if supported
+//! - `ttyS0`: the serial port, reads the input buffer
+//! - `hda`..`hdd`: ATA drives, `hda1`.. their MBR partitions
+//!
+//! Each drive is accessed through one write-back block cache, shared by
+//! its device node, its partitions and the filesystems mounted from them.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use storage::mbr::MbrTable;
+use storage::*;
+use x86_64::instructions::random::RdRand;
+
+use super::ata::AtaDrive;
+use super::input::try_pop_key;
+use super::serial::get_serial_for_sure;
+
+/// Blocks of each drive kept in memory, 512 KiB
+const DRIVE_CACHE_BLOCKS: usize = 1024;
+
+/// An ATA drive behind its block cache
+pub type CachedDrive = Arc<BlockCache<Block512, AtaDrive>>;
+
+#[derive(Clone)]
+enum Device {
+    Null,
+    Zero,
+    Random,
+    Serial,
+    Drive(CachedDrive),
+    Partition(Partition<CachedDrive, Block512>),
+}
+
+impl core::fmt::Debug for Device {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Device::Null => write!(f, "Null"),
+            Device::Zero => write!(f, "Zero"),
+            Device::Random => write!(f, "Random"),
+            Device::Serial => write!(f, "Serial"),
+            Device::Drive(cache) => {
+                let drive = cache.inner();
+                write!(f, "Drive({}@{})", drive.bus, drive.drive)
+            }
+            Device::Partition(part) => write!(f, "{:?}", part),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DevFs {
+    devices: Vec<(String, Device)>,
+}
+
+impl DevFs {
+    /// Create the device nodes, probing every ATA drive
+    pub fn new() -> Self {
+        let mut devices = Vec::from([
+            ("null".into(), Device::Null),
+            ("zero".into(), Device::Zero),
+            ("random".into(), Device::Random),
+            ("ttyS0".into(), Device::Serial),
+        ]);
+
+        for (i, (bus, drive)) in [(0, 0), (0, 1), (1, 0), (1, 1)].into_iter().enumerate() {
+            let Some(ata) = AtaDrive::open(bus, drive) else {
+                continue;
+            };
+            let name = format!("hd{}", (b'a' + i as u8) as char);
+            let cache = Arc::new(BlockCache::new(ata, DRIVE_CACHE_BLOCKS));
+
+            let parts = MbrTable::parse(cache.clone()).and_then(|mbr| mbr.partitions());
+            devices.push((name.clone(), Device::Drive(cache)));
+            for (n, part) in parts.unwrap_or_default().into_iter().enumerate() {
+                devices.push((format!("{}{}", name, n + 1), Device::Partition(part)));
+            }
+        }
+
+        Self { devices }
+    }
+
+    fn find(&self, path: &str) -> FsResult<&Device> {
+        let name = path.trim_matches('/');
+        self.devices
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, dev)| dev)
+            .ok_or(FsError::FileNotFound)
+    }
+
+    /// The drive or partition `name`, e.g. `hda1`
+    pub fn block_device(&self, name: &str) -> FsResult<Box<dyn BlockDevice<Block512>>> {
+        match self.find(name)? {
+            Device::Drive(drive) => Ok(Box::new(drive.clone())),
+            Device::Partition(part) => Ok(Box::new(part.clone())),
+            _ => Err(FsError::InvalidOperation),
+        }
+    }
+
+    /// Write the dirty blocks of every drive back to the disk
+    pub fn sync(&self) -> FsResult {
+        for (_, dev) in self.devices.iter() {
+            if let Device::Drive(cache) = dev {
+                cache.sync()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Statistics of the block cache of drive `name`, e.g. `hda`
+    pub fn cache_stats(&self, name: &str) -> Option<CacheStats> {
+        match self.find(name) {
+            Ok(Device::Drive(cache)) => Some(cache.stats()),
+            _ => None,
+        }
+    }
+
+    fn meta(name: &str, dev: &Device) -> Metadata {
+        let len = match dev {
+            Device::Drive(drive) => drive.block_count().unwrap_or(0) * Block512::size(),
+            Device::Partition(part) => part.block_count().unwrap_or(0) * Block512::size(),
+            _ => 0,
+        };
+        Metadata::new(name.into(), FileType::File, len, None, None, None)
+    }
+}
+
+impl Default for DevFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileSystem for DevFs {
+    fn read_dir(&self, path: &str) -> FsResult<Box<dyn Iterator<Item = Metadata> + Send>> {
+        if !path.trim_matches('/').is_empty() {
+            self.find(path)?;
+            return Err(FsError::NotADirectory);
+        }
+
+        let entries: Vec<Metadata> = self
+            .devices
+            .iter()
+            .map(|(name, dev)| Self::meta(name, dev))
+            .collect();
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn open_file(&self, path: &str) -> FsResult<FileHandle> {
+        let dev = self.find(path)?;
+        let meta = Self::meta(path.trim_matches('/'), dev);
+        let file: Box<dyn FileIO + Send> = match dev.clone() {
+            Device::Drive(drive) => Box::new(BlockFile::new(drive)),
+            Device::Partition(part) => Box::new(BlockFile::new(part)),
+            dev => Box::new(CharFile(dev)),
+        };
+        Ok(FileHandle::new(meta, file))
+    }
+
+    fn metadata(&self, path: &str) -> FsResult<Metadata> {
+        let name = path.trim_matches('/');
+        if name.is_empty() {
+            return Ok(Metadata::new("dev".into(), FileType::Directory, 0, None, None, None));
+        }
+        Ok(Self::meta(name, self.find(path)?))
+    }
+
+    fn exists(&self, path: &str) -> FsResult<bool> {
+        Ok(path.trim_matches('/').is_empty() || self.find(path).is_ok())
+    }
+}
+
+/// A character device, read and written byte by byte
+struct CharFile(Device);
+
+impl Read for CharFile {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
+        match self.0 {
+            Device::Zero => buf.fill(0),
+            Device::Random => fill_random(buf),
+            Device::Serial => {
+                // never blocks, like stdin
+                return match (buf.first_mut(), try_pop_key()) {
+                    (Some(byte), Some(key)) => {
+                        *byte = key;
+                        Ok(1)
+                    }
+                    _ => Ok(0),
+                };
+            }
+            _ => return Ok(0),
+        }
+        Ok(buf.len())
+    }
+}
+
+impl Write for CharFile {
+    fn write(&mut self, buf: &[u8]) -> FsResult<usize> {
+        if let Device::Serial = self.0 {
+            let mut serial = get_serial_for_sure();
+            for byte in buf {
+                serial.send(*byte);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> FsResult {
+        Ok(())
+    }
+}
+
+impl Seek for CharFile {
+    fn seek(&mut self, _pos: SeekFrom) -> FsResult<usize> {
+        Ok(0)
+    }
+}
+
+fn fill_random(buf: &mut [u8]) {
+    static SEED: spin::Mutex<u64> = spin::Mutex::new(0);
+
+    let rdrand = RdRand::new();
+    for chunk in buf.chunks_mut(8) {
+        let value = match rdrand.and_then(|r| r.get_u64()) {
+            Some(value) => value,
+            None => {
+                // xorshift64, seeded by the time stamp counter
+                let mut seed = SEED.lock();
+                if *seed == 0 {
+                    *seed = unsafe { core::arch::x86_64::_rdtsc() } | 1;
+                }
+                *seed ^= *seed << 13;
+                *seed ^= *seed >> 7;
+                *seed ^= *seed << 17;
+                *seed
+            }
+        };
+        chunk.copy_from_slice(&value.to_ne_bytes()[..chunk.len()]);
+    }
+}
+
+/// A block device accessed by bytes
+///
+/// Partial blocks are read before they are written.
+struct BlockFile<T: BlockDevice<Block512>> {
+    dev: T,
+    offset: usize,
+    len: usize,
+}
+
+impl<T: BlockDevice<Block512>> BlockFile<T> {
+    fn new(dev: T) -> Self {
+        let len = dev.block_count().unwrap_or(0) * Block512::size();
+        Self { dev, offset: 0, len }
+    }
+}
+
+impl<T: BlockDevice<Block512>> Read for BlockFile<T> {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
+        let size = Block512::size();
+        let end = (self.offset + buf.len()).min(self.len);
+        let mut block = Block512::default();
+        let mut done = 0;
+
+        while self.offset < end {
+            let start = self.offset % size;
+            let len = (size - start).min(end - self.offset);
+            self.dev.read_block(self.offset / size, &mut block)?;
+            buf[done..done + len].copy_from_slice(&block[start..start + len]);
+            done += len;
+            self.offset += len;
+        }
+
+        Ok(done)
+    }
+}
+
+impl<T: BlockDevice<Block512>> Write for BlockFile<T> {
+    fn write(&mut self, buf: &[u8]) -> FsResult<usize> {
+        let size = Block512::size();
+        let end = (self.offset + buf.len()).min(self.len);
+        let mut block = Block512::default();
+        let mut done = 0;
+
+        while self.offset < end {
+            let start = self.offset % size;
+            let len = (size - start).min(end - self.offset);
+            if len < size {
+                self.dev.read_block(self.offset / size, &mut block)?;
+            }
+            block.as_mut()[start..start + len].copy_from_slice(&buf[done..done + len]);
+            self.dev.write_block(self.offset / size, &block)?;
+            done += len;
+            self.offset += len;
+        }
+
+        if done == 0 && !buf.is_empty() {
+            return Err(FsError::WriteZero);
+        }
+        Ok(done)
+    }
+
+    fn flush(&mut self) -> FsResult {
+        Ok(())
+    }
+}
+
+impl<T: BlockDevice<Block512>> Seek for BlockFile<T> {
+    fn seek(&mut self, pos: SeekFrom) -> FsResult<usize> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => offset as isize,
+            SeekFrom::End(offset) => self.len as isize + offset,
+            SeekFrom::Current(offset) => self.offset as isize + offset,
+        };
+        if offset < 0 || offset as usize > self.len {
+            return Err(FsError::InvalidOffset);
+        }
+        self.offset = offset as usize;
+        Ok(self.offset)
+    }
+}
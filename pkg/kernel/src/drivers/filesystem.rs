@@ -1,45 +1,203 @@
-use super::ata::*;
+use super::devfs::DevFs;
+use super::procfs::ProcFs;
+use crate::utils::clock;
 use alloc::boxed::Box;
+use alloc::sync::Arc;
 use chrono::DateTime;
-use storage::fat16::Fat16;
-use storage::mbr::*;
+use storage::open_fat;
+use storage::tmpfs::Tmpfs;
 use storage::*;
 use alloc::string::ToString;
+use alloc::vec::Vec;
+use syscall_def::{SyscallError, SyscallResult};
+use syscall_def::fs::{FsType, O_APPEND, O_CREATE, O_TRUNC};
 
-pub static ROOTFS: spin::Once<Mount> = spin::Once::new();
+/// Every mounted filesystem
+pub static VFS: Vfs = Vfs::new();
 
-pub fn get_rootfs() -> &'static Mount {
-    ROOTFS.get().unwrap()
+/// Device nodes, also used to find the block device of a mount
+static DEVFS: spin::Once<Arc<DevFs>> = spin::Once::new();
+
+/// Largest file `read_file` loads into memory, a quarter of the kernel heap
+const MAX_READ_FILE: usize = crate::memory::allocator::HEAP_SIZE / 4;
+
+/// Device the root filesystem is mounted from
+const ROOT_DEVICE: &str = "hda1";
+
+/// Directory searched by `lsapp` for programs on the disk
+pub const APP_PATH: &str = "/APP";
+
+/// Mount point of procfs
+pub const PROC_PATH: &str = "/proc";
+
+/// Mount point of devfs
+pub const DEV_PATH: &str = "/dev";
+
+pub fn get_vfs() -> &'static Vfs {
+    &VFS
 }
 
 pub fn init() {
     info!("Opening disk device...");
 
-    let drive = AtaDrive::open(0, 0).expect("Failed to open disk device");
-
-    // only get the first partition
-    let part = MbrTable::parse(drive)
-        .expect("Failed to parse MBR")
-        .partitions()
-        .expect("Failed to get partitions")
-        .remove(0);
+    // the first partition of the first drive, through the cache of devfs
+    let part = devfs()
+        .block_device(ROOT_DEVICE)
+        .expect("Failed to open root partition");
 
     info!("Mounting filesystem...");
 
-    ROOTFS.call_once(|| Mount::new(Box::new(Fat16::new(part)), "/".into()));
+    clock::init_wall_clock();
+    storage::set_clock(clock::wall_time);
+
+    let fs = open_fat(part).expect("Failed to open root filesystem");
+    let source = format!("{}/{}", DEV_PATH, ROOT_DEVICE);
+    let root = Mount::new(fs, "/".into()).with_source(&source);
+    VFS.mount(root).expect("Failed to mount root filesystem");
+
+    for (fs_type, mount_point) in [(FsType::Procfs, PROC_PATH), (FsType::Devfs, DEV_PATH)] {
+        if let Err(err) = mount("", mount_point, fs_type, false) {
+            warn!("Failed to mount {} at {}: {:?}", fs_type.name(), mount_point, err);
+        }
+    }
 
-    trace!("Root filesystem: {:#?}", ROOTFS.get().unwrap());
+    trace!("Mounted filesystems: {:#?}", VFS.mounts());
 
     info!("Initialized Filesystem.");
 }
 
-pub fn ls(root_path: &str) {
+/// Mount a filesystem of `fs_type` at `target`
+///
+/// `source` is the block device of a FAT16 or FAT32 filesystem, e.g. `/dev/hda1`,
+/// and ignored by other filesystems.
+pub fn mount(source: &str, target: &str, fs_type: FsType, read_only: bool) -> FsResult {
+    let (fs, source): (Box<dyn FileSystem>, _) = match fs_type {
+        FsType::Fat => {
+            let source = normalize(source);
+            let name = source
+                .strip_prefix(DEV_PATH)
+                .and_then(|name| name.strip_prefix('/'))
+                .ok_or_else(|| FsError::InvalidPath(source.clone()))?;
+            let dev = devfs().block_device(name)?;
+            (open_fat(dev)?, source)
+        }
+        FsType::Tmpfs => (Box::new(Tmpfs::new()), fs_type.name().into()),
+        FsType::Procfs => (Box::new(ProcFs::new()), fs_type.name().into()),
+        FsType::Devfs => (Box::new(devfs().clone()), fs_type.name().into()),
+    };
+
+    let mount = Mount::new(fs, target.into())
+        .with_source(&source)
+        .with_read_only(read_only);
+    VFS.mount(mount)
+}
+
+pub fn umount(target: &str) -> FsResult {
+    if normalize(target) == "/" {
+        return Err(FsError::Busy);
+    }
+    VFS.umount(target)?;
+    // the cache of the device outlives the mount
+    sync()
+}
+
+/// Write the dirty blocks of every drive back to the disk
+pub fn sync() -> FsResult {
+    match DEVFS.get() {
+        Some(devfs) => devfs.sync(),
+        None => Ok(()),
+    }
+}
+
+/// Statistics of the block cache of the drive holding the root filesystem
+pub fn cache_stats() -> Option<CacheStats> {
+    let drive = ROOT_DEVICE.trim_end_matches(|c: char| c.is_ascii_digit());
+    DEVFS.get()?.cache_stats(drive)
+}
+
+fn devfs() -> &'static Arc<DevFs> {
+    DEVFS.call_once(|| Arc::new(DevFs::new()))
+}
+
+/// Map a filesystem error to the errno reported to user space
+pub fn fs_error(err: FsError) -> SyscallError {
+    match err {
+        FsError::FileNotFound => SyscallError::NotFound,
+        FsError::NotADirectory => SyscallError::NotADirectory,
+        FsError::NotAFile => SyscallError::IsADirectory,
+        FsError::ReadOnly => SyscallError::ReadOnly,
+        FsError::NotSupported => SyscallError::NotSupported,
+        FsError::WriteZero => SyscallError::NoSpace,
+        FsError::FileNameError(_) | FsError::InvalidPath(_) => SyscallError::InvalidArgument,
+        FsError::InvalidOperation | FsError::InvalidOffset => SyscallError::InvalidArgument,
+        FsError::AlreadyExists => SyscallError::AlreadyExists,
+        FsError::Busy => SyscallError::Busy,
+        FsError::NotEmpty => SyscallError::NotEmpty,
+        FsError::DeviceError(_) | FsError::BadCluster => SyscallError::IoError,
+        FsError::EndOfFile | FsError::NotInSector => SyscallError::IoError,
+    }
+}
+
+/// Open the file at `path`, `flags` are the `O_*` flags of `open`
+pub fn open(path: &str, flags: usize) -> FsResult<FileHandle> {
+    if flags & (O_CREATE | O_TRUNC | O_APPEND) == 0 {
+        return VFS.open_file(path);
+    }
+
+    match VFS.exists(path)? {
+        false if flags & O_CREATE == 0 => Err(FsError::FileNotFound),
+        false => VFS.create_file(path),
+        true if flags & O_TRUNC != 0 => VFS.create_file(path),
+        true if flags & O_APPEND != 0 => VFS.append_file(path),
+        true => VFS.open_file(path),
+    }
+}
+
+/// Move a file or directory, both paths must be on the same mount
+pub fn rename(src: &str, dst: &str) -> FsResult {
+    if VFS.metadata(src)?.is_dir() {
+        VFS.move_dir(src, dst)
+    } else {
+        VFS.move_file(src, dst)
+    }
+}
+
+/// Read the whole file at `path`
+///
+/// Files larger than `MAX_READ_FILE` are refused, device files may not report
+/// their length so the read itself is bounded as well. Running out of kernel
+/// heap is reported as `NoMemory` instead of reaching the allocation error handler.
+pub fn read_file(path: &str) -> SyscallResult<Vec<u8>> {
+    let mut file = VFS.open_file(path).map_err(fs_error)?;
+    if file.meta.len > MAX_READ_FILE {
+        return Err(SyscallError::NoMemory);
+    }
+
+    let mut buf = Vec::new();
+    buf.try_reserve_exact(file.meta.len)
+        .map_err(|_| SyscallError::NoMemory)?;
+    let mut chunk = [0u8; 1024];
+    loop {
+        let n = file.read(&mut chunk).map_err(fs_error)?;
+        if n == 0 {
+            break;
+        }
+        if buf.len() + n > MAX_READ_FILE {
+            return Err(SyscallError::NoMemory);
+        }
+        buf.try_reserve(n).map_err(|_| SyscallError::NoMemory)?;
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    Ok(buf)
+}
+
+pub fn ls(root_path: &str) -> FsResult {
     info!("Listing directory: {}", root_path);
-    let iter = match get_rootfs().read_dir(root_path) {
+    let iter = match VFS.read_dir(root_path) {
         Ok(iter) => iter,
         Err(err) => {
             warn!("{:?}", err);
-            return;
+            return Err(err);
         }
     };
 
@@ -92,4 +250,6 @@ pub fn ls(root_path: &str) {
               modified_str,
               created_str);
     }
+
+    Ok(())
 }
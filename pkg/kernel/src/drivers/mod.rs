@@ -3,6 +3,8 @@ mod uart16550;
 pub mod input;
 pub mod serial;
 pub mod ata;
+pub mod devfs;
 pub mod filesystem;
+pub mod procfs;
 
 pub use input::{get_line, push_key};
@@ -0,0 +1,334 @@
+//! Virtual filesystem exposing kernel state, mounted at `/proc`
+//!
+//! - `/proc/meminfo`: physical frames and kernel heap
+//! - `/proc/uptime`: seconds since boot
+//! - `/proc/apps`: programs loaded by the bootloader
+//! - `/proc/mounts`: mounted filesystems
+//! - `/proc/cache`: block cache of the root filesystem
+//! - `/proc/<pid>/{status,maps,fd}`: state of a process, `self` is the caller
+//!
+//! Files are generated when opened, reading an open file returns the
+//! state at that time.
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write as _;
+use storage::*;
+use x86_64::structures::paging::PageTableFlags;
+
+use crate::memory::{PAGE_SIZE, allocator::ALLOCATOR, get_frame_alloc_for_sure};
+use crate::proc::{ProcessId, get_process_manager, processor};
+use crate::utils::clock;
+
+/// Files in the root directory
+const ROOT_FILES: [&str; 5] = ["meminfo", "uptime", "apps", "mounts", "cache"];
+
+/// Files in the directory of a process
+const PROC_FILES: [&str; 3] = ["status", "maps", "fd"];
+
+#[derive(Debug, Default)]
+pub struct ProcFs;
+
+/// A path inside procfs
+enum Node {
+    Root,
+    File(&'static str),
+    Proc(ProcessId),
+    ProcFile(ProcessId, &'static str),
+}
+
+impl ProcFs {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn parse(path: &str) -> FsResult<Node> {
+        let mut parts = path.split('/').filter(|p| !p.is_empty());
+
+        let Some(first) = parts.next() else {
+            return Ok(Node::Root);
+        };
+
+        let pid = match first {
+            "self" => processor::current_pid().ok_or(FsError::FileNotFound)?,
+            pid => match pid.parse::<u16>() {
+                Ok(pid) => ProcessId(pid),
+                Err(_) => {
+                    let file = find(&ROOT_FILES, first)?;
+                    return match parts.next() {
+                        None => Ok(Node::File(file)),
+                        Some(_) => Err(FsError::NotADirectory),
+                    };
+                }
+            },
+        };
+
+        if processor::exclusive(|| get_process_manager().get_proc(&pid)).is_none() {
+            return Err(FsError::FileNotFound);
+        }
+
+        let node = match parts.next() {
+            None => Node::Proc(pid),
+            Some(name) => Node::ProcFile(pid, find(&PROC_FILES, name)?),
+        };
+
+        match parts.next() {
+            None => Ok(node),
+            Some(_) => Err(FsError::NotADirectory),
+        }
+    }
+
+    /// Generate the content of a file
+    fn content(node: &Node) -> FsResult<String> {
+        let content = match node {
+            Node::Root | Node::Proc(_) => return Err(FsError::NotAFile),
+            Node::File("meminfo") => meminfo(),
+            Node::File("uptime") => uptime(),
+            Node::File("mounts") => mounts(),
+            Node::File("cache") => cache(),
+            Node::File(_) => apps(),
+            Node::ProcFile(pid, name) => processor::exclusive(|| {
+                let proc = get_process_manager().get_proc(pid)?;
+                Some(match *name {
+                    "status" => status(&proc.info()),
+                    "maps" => maps(&proc.read().vm_areas()),
+                    _ => fds(&proc.read().fds()),
+                })
+            })
+            .ok_or(FsError::FileNotFound)?,
+        };
+        Ok(content)
+    }
+}
+
+impl FileSystem for ProcFs {
+    fn read_dir(&self, path: &str) -> FsResult<Box<dyn Iterator<Item = Metadata> + Send>> {
+        let entries: Vec<Metadata> = match Self::parse(path)? {
+            Node::Root => ROOT_FILES
+                .iter()
+                .map(|name| file_meta(name, 0))
+                .chain(core::iter::once(dir_meta("self")))
+                .chain(
+                    crate::proc::proc_info()
+                        .iter()
+                        .map(|info| dir_meta(&info.pid.to_string())),
+                )
+                .collect(),
+            Node::Proc(_) => PROC_FILES.iter().map(|name| file_meta(name, 0)).collect(),
+            _ => return Err(FsError::NotADirectory),
+        };
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn open_file(&self, path: &str) -> FsResult<FileHandle> {
+        let node = Self::parse(path)?;
+        let content = Self::content(&node)?;
+        let name = path.rsplit('/').next().unwrap_or_default();
+        Ok(FileHandle::new(
+            file_meta(name, content.len()),
+            Box::new(MemFile::new(content.into_bytes())),
+        ))
+    }
+
+    fn metadata(&self, path: &str) -> FsResult<Metadata> {
+        let name = path.rsplit('/').find(|p| !p.is_empty()).unwrap_or("/");
+        match Self::parse(path)? {
+            Node::Root | Node::Proc(_) => Ok(dir_meta(name)),
+            _ => Ok(file_meta(name, 0)),
+        }
+    }
+
+    fn exists(&self, path: &str) -> FsResult<bool> {
+        match Self::parse(path) {
+            Ok(_) => Ok(true),
+            Err(FsError::FileNotFound) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn find(names: &[&'static str], name: &str) -> FsResult<&'static str> {
+    names
+        .iter()
+        .find(|n| **n == name)
+        .copied()
+        .ok_or(FsError::FileNotFound)
+}
+
+fn file_meta(name: &str, len: usize) -> Metadata {
+    Metadata::new(name.into(), FileType::File, len, None, None, None)
+}
+
+fn dir_meta(name: &str) -> Metadata {
+    Metadata::new(name.into(), FileType::Directory, 0, None, None, None)
+}
+
+fn meminfo() -> String {
+    let (total, used, recycled, shared) = {
+        let alloc = get_frame_alloc_for_sure();
+        (
+            alloc.frames_total(),
+            alloc.frames_used(),
+            alloc.frames_recycled_count(),
+            alloc.frames_shared_count(),
+        )
+    };
+    let (heap_size, heap_used) = {
+        let heap = ALLOCATOR.lock();
+        (heap.size(), heap.used())
+    };
+
+    let kb = |frames: usize| frames as u64 * PAGE_SIZE / 1024;
+    let in_use = used.saturating_sub(recycled);
+
+    let mut s = String::new();
+    let _ = writeln!(s, "MemTotal:     {:>10} kB", kb(total));
+    let _ = writeln!(s, "MemFree:      {:>10} kB", kb(total.saturating_sub(in_use)));
+    let _ = writeln!(s, "MemUsed:      {:>10} kB", kb(in_use));
+    let _ = writeln!(s, "MemShared:    {:>10} kB", kb(shared));
+    let _ = writeln!(s, "KernelHeap:   {:>10} kB", heap_size / 1024);
+    let _ = writeln!(s, "KernelHeapUsed: {:>8} kB", heap_used / 1024);
+    s
+}
+
+fn uptime() -> String {
+    let now = clock::now();
+    format!("{}.{:02}\n", now.as_secs(), now.subsec_millis() / 10)
+}
+
+fn apps() -> String {
+    let mut s = String::new();
+    if let Some(apps) = get_process_manager().app_list() {
+        for app in apps.iter() {
+            let _ = writeln!(s, "{}", app.name);
+        }
+    }
+    s
+}
+
+fn mounts() -> String {
+    let mut s = String::new();
+    for mount in super::filesystem::get_vfs().mounts() {
+        let mode = if mount.read_only { "ro" } else { "rw" };
+        let _ = writeln!(s, "{} {} {}", mount.source, mount.mount_point, mode);
+    }
+    s
+}
+
+fn cache() -> String {
+    let mut s = String::new();
+    if let Some(stats) = super::filesystem::cache_stats() {
+        let lookups = (stats.hits + stats.misses).max(1);
+        let _ = writeln!(s, "Hits:       {:>10}", stats.hits);
+        let _ = writeln!(s, "Misses:     {:>10}", stats.misses);
+        let _ = writeln!(s, "HitRate:    {:>9}%", stats.hits * 100 / lookups);
+        let _ = writeln!(s, "Writebacks: {:>10}", stats.writebacks);
+        let _ = writeln!(s, "Cached:     {:>10}", stats.cached);
+        let _ = writeln!(s, "Dirty:      {:>10}", stats.dirty);
+        let _ = writeln!(s, "Capacity:   {:>10}", stats.capacity);
+    }
+    s
+}
+
+fn status(info: &syscall_def::proc::ProcInfo) -> String {
+    let state = match info.status {
+        syscall_def::proc::ProcStatus::Running => "R (running)",
+        syscall_def::proc::ProcStatus::Ready => "S (ready)",
+        syscall_def::proc::ProcStatus::Blocked => "B (blocked)",
+        syscall_def::proc::ProcStatus::Dead => "Z (zombie)",
+    };
+    let start = info.start_ns / 1_000_000;
+
+    let mut s = String::new();
+    let _ = writeln!(s, "Name:        {}", info.name());
+    let _ = writeln!(s, "State:       {}", state);
+    let _ = writeln!(s, "Pid:         {}", info.pid);
+    let _ = writeln!(s, "PPid:        {}", info.ppid);
+    let _ = writeln!(s, "Nice:        {}", info.nice);
+    let _ = writeln!(s, "Level:       {}", info.level);
+    if info.is_running() {
+        let _ = writeln!(s, "Cpu:         {}", info.cpu);
+    }
+    let _ = writeln!(s, "UserTicks:   {}", info.user_ticks);
+    let _ = writeln!(s, "KernelTicks: {}", info.kernel_ticks);
+    let _ = writeln!(s, "StartTime:   {}.{:03}", start / 1000, start % 1000);
+    let _ = writeln!(s, "VmCode:      {:>8} kB", info.code / 1024);
+    let _ = writeln!(s, "VmStack:     {:>8} kB", info.stack / 1024);
+    let _ = writeln!(s, "VmHeap:      {:>8} kB", info.heap / 1024);
+    s
+}
+
+fn maps(areas: &[crate::proc::VmArea]) -> String {
+    let mut s = String::new();
+    for area in areas {
+        let flag = |f: PageTableFlags, c: char| if area.flags.contains(f) { c } else { '-' };
+        let exec = if area.flags.contains(PageTableFlags::NO_EXECUTE) { '-' } else { 'x' };
+        let _ = writeln!(
+            s,
+            "{:012x}-{:012x} {}{}{} {}",
+            area.start.as_u64(),
+            area.end.as_u64(),
+            flag(PageTableFlags::PRESENT, 'r'),
+            flag(PageTableFlags::WRITABLE, 'w'),
+            exec,
+            area.name
+        );
+    }
+    s
+}
+
+fn fds(fds: &[(u8, String)]) -> String {
+    let mut s = String::new();
+    for (fd, res) in fds {
+        let _ = writeln!(s, "{} {}", fd, res);
+    }
+    s
+}
+
+/// A read-only file backed by a buffer
+struct MemFile {
+    data: Vec<u8>,
+    offset: usize,
+}
+
+impl MemFile {
+    fn new(data: Vec<u8>) -> Self {
+        Self { data, offset: 0 }
+    }
+}
+
+impl Read for MemFile {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
+        let rest = &self.data[self.offset.min(self.data.len())..];
+        let len = rest.len().min(buf.len());
+        buf[..len].copy_from_slice(&rest[..len]);
+        self.offset += len;
+        Ok(len)
+    }
+}
+
+impl Write for MemFile {
+    fn write(&mut self, _buf: &[u8]) -> FsResult<usize> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn flush(&mut self) -> FsResult {
+        Ok(())
+    }
+}
+
+impl Seek for MemFile {
+    fn seek(&mut self, pos: SeekFrom) -> FsResult<usize> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => offset as isize,
+            SeekFrom::End(offset) => self.data.len() as isize + offset,
+            SeekFrom::Current(offset) => self.offset as isize + offset,
+        };
+        if offset < 0 {
+            return Err(FsError::InvalidOffset);
+        }
+        self.offset = offset as usize;
+        Ok(self.offset)
+    }
+}
@@ -9,12 +9,21 @@ use crate::interrupt::consts::{Interrupts, Irq};
 /// 默认 xAPIC 物理地址
 pub const LAPIC_ADDR: u64 = 0xFEE00000;
 
+/// 定时器初始计数，决定时钟中断的周期
+const TIMER_INIT_COUNT: u32 = 0x20000;
+
+/// PIT 的输入频率 (Hz)
+const PIT_FREQUENCY: u64 = 1_193_182;
+/// 校准定时器时 PIT 计时的长度 (ms)
+const CALIBRATE_MS: u64 = 10;
+
 /// 用枚举表示 LAPIC 寄存器偏移
 #[repr(u32)]
 pub enum LapicRegister {
     SVR      = 0xF0,  // Spurious Interrupt Vector Register
     TDCR     = 0x3E0, // Timer Divide Configuration Register
     TICR     = 0x380, // Timer Initial Count Register
+    TCCR     = 0x390, // Timer Current Count Register
     LvtTimer = 0x320, // LVT Timer Register
     LvtLInt0 = 0x350, // LVT LINT0 Register
     LvtLInt1 = 0x360, // LVT LINT1 Register
@@ -74,6 +83,48 @@ impl XApic {
         }
     }
 
+    /// 用 PIT 通道 2 计时，测量定时器每毫秒的计数
+    ///
+    /// 需要在定时器中断开启之前调用，PIT 不响应时返回 None
+    unsafe fn calibrate_timer(&mut self) -> Option<u64> {
+        use x86_64::instructions::port::Port;
+
+        let mut gate = Port::<u8>::new(0x61);
+        let mut command = Port::<u8>::new(0x43);
+        let mut channel2 = Port::<u8>::new(0x42);
+        let count = PIT_FREQUENCY * CALIBRATE_MS / 1000;
+
+        unsafe {
+            // 关闭扬声器与门控，通道 2 使用模式 0 (计数结束时 OUT 拉高)
+            let value = gate.read() & !0x03;
+            gate.write(value);
+            command.write(0b1011_0000);
+            channel2.write(count as u8);
+            channel2.write((count >> 8) as u8);
+
+            // 计时期间屏蔽定时器中断
+            self.write(LapicRegister::LvtTimer, LvtTimerFlags::MASKED.bits());
+            self.write(LapicRegister::TICR, u32::MAX);
+            gate.write(value | 0x01);
+
+            let mut spins = 0u64;
+            while gate.read() & 0x20 == 0 {
+                spins += 1;
+                if spins > 100_000_000 {
+                    warn!("PIT does not respond, timer is not calibrated");
+                    return None;
+                }
+                core::hint::spin_loop();
+            }
+
+            let elapsed = u32::MAX - self.read(LapicRegister::TCCR);
+            self.write(LapicRegister::TICR, 0);
+            gate.write(value);
+
+            Some((elapsed as u64 / CALIBRATE_MS).max(1))
+        }
+    }
+
     /// 使用枚举写入寄存器
     unsafe fn write(&mut self, reg: LapicRegister, value: u32) {
         unsafe {
@@ -100,8 +151,15 @@ impl LocalApic for XApic {
             self.write(LapicRegister::SVR, svr.bits());
 
             // 配置定时器除法器和初始计数器
-            self.write(LapicRegister::TDCR, 0b1000); // 除法器设置为 1
-            self.write(LapicRegister::TICR, 0x20000);  // 初始计数器
+            self.write(LapicRegister::TDCR, 0b1000); // 除法器设置为 32
+            // 所有 CPU 的定时器周期相同，只在 BSP 上校准一次
+            static CALIBRATED: spin::Once = spin::Once::new();
+            CALIBRATED.call_once(|| {
+                if let Some(per_ms) = self.calibrate_timer() {
+                    crate::utils::clock::set_tick_ns(TIMER_INIT_COUNT as u64 * 1_000_000 / per_ms);
+                }
+            });
+            self.write(LapicRegister::TICR, TIMER_INIT_COUNT);  // 初始计数器
 
             // 设置 LVT Timer 寄存器
             let lvt_timer_val = self.read(LapicRegister::LvtTimer);
@@ -12,7 +12,13 @@ pub unsafe fn register_idt(idt: &mut InterruptDescriptorTable) {
 }
 
 pub extern "C" fn clock(mut context: ProcessContext) {
+    // time is counted by the BSP only, every CPU has its own timer
+    if crate::proc::processor::is_bsp() {
+        let now = crate::utils::clock::tick();
+        crate::proc::wake_sleepers(now);
+    }
     crate::proc::switch(&mut context);
+    crate::proc::handle_signals(&mut context);
     super::ack(Interrupts::IrqBase as u8);
 }
 as_handler!(clock);
\ No newline at end of file
@@ -1,4 +1,5 @@
 use crate::memory::*;
+use crate::proc::ProcessContext;
 use x86_64::registers::control::Cr2;
 use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
 
@@ -137,18 +138,27 @@ pub extern "x86-interrupt" fn simd_floating_point_handler(stack_frame: Interrupt
     panic!("EXCEPTION: SIMD FLOATING POINT\n\n{:#?}", stack_frame);
 }
 
-pub extern "x86-interrupt" fn page_fault_handler(
-    stack_frame: InterruptStackFrame,
-    err_code: PageFaultErrorCode,
-) {
+pub extern "C" fn page_fault(mut context: ProcessContext, err_code: u64) {
+    let err_code = PageFaultErrorCode::from_bits_truncate(err_code);
     let addr = Cr2::read().unwrap();
 
-    if !crate::proc::handle_page_fault(addr, err_code) {
-        warn!(
-            "EXCEPTION: PAGE FAULT, ERROR_CODE: {:?}\n\nTrying to access: {:#x}\n{:#?}",
-            err_code, addr, stack_frame
-        );
-        crate::proc::current_proc_info();
-        panic!("Failed to handle page fault.");
+    if crate::proc::handle_page_fault(addr, err_code) {
+        return;
+    }
+
+    warn!(
+        "EXCEPTION: PAGE FAULT, ERROR_CODE: {:?}\n\nTrying to access: {:#x}\n{:#?}",
+        err_code, addr, context
+    );
+
+    // a bad access of user space kills the process instead of the kernel
+    if context.is_user_mode() {
+        crate::proc::segfault(&mut context);
+        return;
     }
+
+    crate::proc::current_proc_info();
+    panic!("Failed to handle page fault.");
 }
+
+as_handler_with_error!(page_fault, PageFaultErrorCode);
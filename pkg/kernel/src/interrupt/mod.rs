@@ -28,6 +28,11 @@ lazy_static! {
 pub fn init() {
     IDT.load();
 
+    // keep `int 0x80` for compatibility, but prefer `syscall`
+    unsafe {
+        syscall::init();
+    }
+
     // FIXME: check and init APIC
     unsafe {
         let mut lapic = XApic::new(physical_to_virtual(LAPIC_ADDR));
@@ -39,6 +44,17 @@ pub fn init() {
     info!("Interrupts Initialized.");
 }
 
+/// init interrupts of an application processor, the IO APIC is left to the BSP
+pub fn init_ap() {
+    IDT.load();
+
+    unsafe {
+        syscall::init();
+        let mut lapic = XApic::new(physical_to_virtual(LAPIC_ADDR));
+        lapic.cpu_init();
+    }
+}
+
 #[inline(always)]
 pub fn enable_irq(irq: u8, cpuid: u8) {
     let mut ioapic = unsafe { IoApic::new(physical_to_virtual(IOAPIC_ADDR)) };
@@ -50,3 +66,9 @@ pub fn ack(_irq: u8) {
     let mut lapic = unsafe { XApic::new(physical_to_virtual(LAPIC_ADDR)) };
     lapic.eoi();
 }
+
+/// Send an inter-processor interrupt from the current CPU
+pub fn send_ipi(icr: u64) {
+    let mut lapic = unsafe { XApic::new(physical_to_virtual(LAPIC_ADDR)) };
+    lapic.set_icr(icr);
+}
@@ -0,0 +1,156 @@
+//! Fast system call entry through `syscall` / `sysret`
+//!
+//! The entry stub builds the same frame as an interrupt gate would
+//! (`ProcessContext`), so the dispatcher and context switching work
+//! unchanged. It leaves with `sysretq` when that restores the frame
+//! exactly, and falls back to `iretq` otherwise, e.g. after switching
+//! to a process that was preempted by the timer.
+
+use x86_64::VirtAddr;
+use x86_64::registers::model_specific::{Efer, EferFlags, KernelGsBase, LStar, SFMask, Star};
+use x86_64::registers::rflags::RFlags;
+
+use crate::memory::gdt;
+use crate::proc::processor::{MAX_CPU_COUNT, cpu_id};
+
+/// Data reached through `swapgs` on syscall entry
+#[repr(C)]
+struct SyscallScratch {
+    kernel_rsp: u64,
+    user_rsp: u64,
+    user_cs: u64,
+    user_ss: u64,
+}
+
+impl SyscallScratch {
+    const fn new() -> Self {
+        Self {
+            kernel_rsp: 0,
+            user_rsp: 0,
+            user_cs: 0,
+            user_ss: 0,
+        }
+    }
+}
+
+/// One for each CPU, as they enter the kernel on their own stacks
+static mut SCRATCH: [SyscallScratch; MAX_CPU_COUNT] = [const { SyscallScratch::new() }; MAX_CPU_COUNT];
+
+/// Enable `syscall` / `sysret` and point LSTAR to the entry stub
+///
+/// Called on every CPU, after its GDT is loaded.
+pub unsafe fn init() {
+    let kernel = gdt::get_kernel_selector();
+    let user = gdt::get_user_selector();
+
+    unsafe {
+        let scratch = &raw mut SCRATCH[cpu_id()];
+        scratch.write(SyscallScratch {
+            kernel_rsp: gdt::get_syscall_stack_top().align_down(16u64).as_u64(),
+            user_rsp: 0,
+            user_cs: user.user_code_selector.0 as u64,
+            user_ss: user.user_data_selector.0 as u64,
+        });
+        KernelGsBase::write(VirtAddr::from_ptr(scratch));
+
+        Star::write(
+            user.user_code_selector,
+            user.user_data_selector,
+            kernel.code_selector,
+            kernel.data_selector,
+        )
+        .expect("Invalid GDT layout for sysret");
+        LStar::write(VirtAddr::new(syscall_entry as usize as u64));
+        // run the kernel with interrupts masked, just like the interrupt gate
+        SFMask::write(
+            RFlags::INTERRUPT_FLAG
+                | RFlags::TRAP_FLAG
+                | RFlags::DIRECTION_FLAG
+                | RFlags::ALIGNMENT_CHECK,
+        );
+        Efer::update(|flags| flags.insert(EferFlags::SYSTEM_CALL_EXTENSIONS));
+    }
+}
+
+#[naked]
+unsafe extern "C" fn syscall_entry() {
+    unsafe {
+        core::arch::naked_asm!(
+            // switch to the kernel stack and build an interrupt frame
+            "swapgs",
+            "mov qword ptr gs:[8], rsp",
+            "mov rsp, qword ptr gs:[0]",
+            "push qword ptr gs:[24]",
+            "push qword ptr gs:[8]",
+            "push r11",
+            "push qword ptr gs:[16]",
+            "push rcx",
+            "swapgs",
+            // same layout as `as_handler!`
+            "push rbp",
+            "push rax",
+            "push rbx",
+            "push rcx",
+            "push rdx",
+            "push rsi",
+            "push rdi",
+            "push r8",
+            "push r9",
+            "push r10",
+            "push r11",
+            "push r12",
+            "push r13",
+            "push r14",
+            "push r15",
+            "call {dispatch}",
+            // `sysretq` restores rip from rcx and rflags from r11,
+            // and may only return to a canonical user space address
+            "mov rax, [rsp + 88]",
+            "cmp rax, [rsp + 120]",
+            "jne 2f",
+            "mov rax, [rsp + 32]",
+            "cmp rax, [rsp + 136]",
+            "jne 2f",
+            "test qword ptr [rsp + 128], 3",
+            "jz 2f",
+            "mov rax, [rsp + 120]",
+            "shr rax, 47",
+            "jnz 2f",
+            "pop r15",
+            "pop r14",
+            "pop r13",
+            "pop r12",
+            "pop r11",
+            "pop r10",
+            "pop r9",
+            "pop r8",
+            "pop rdi",
+            "pop rsi",
+            "pop rdx",
+            "pop rcx",
+            "pop rbx",
+            "pop rax",
+            "pop rbp",
+            "mov rsp, [rsp + 24]",
+            "sysretq",
+            "2:",
+            "pop r15",
+            "pop r14",
+            "pop r13",
+            "pop r12",
+            "pop r11",
+            "pop r10",
+            "pop r9",
+            "pop r8",
+            "pop rdi",
+            "pop rsi",
+            "pop rdx",
+            "pop rcx",
+            "pop rbx",
+            "pop rax",
+            "pop rbp",
+            "iretq",
+            dispatch = sym super::syscall,
+        );
+    }
+}
@@ -1,13 +1,19 @@
 use crate::{memory::gdt, proc::*};
 use alloc::format;
+use core::time::Duration;
 use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
 
 // NOTE: import `ysos_syscall` package as `syscall_def` in Cargo.toml
-use syscall_def::Syscall;
+use syscall_def::{Syscall, SyscallError, encode_result};
 
+mod fast;
 mod service;
+mod trace;
 use super::consts;
 use service::*;
+use trace::SyscallTrace;
+
+pub use fast::init;
 
 // FIXME: write syscall service handler in `service.rs`
 
@@ -26,6 +32,7 @@ pub unsafe fn register_idt(idt: &mut InterruptDescriptorTable) {
 pub extern "C" fn syscall(mut context: ProcessContext) {
     x86_64::instructions::interrupts::without_interrupts(|| {
         super::syscall::dispatcher(&mut context);
+        crate::proc::handle_signals(&mut context);
     });
 }
 
@@ -37,6 +44,9 @@ pub struct SyscallArgs {
     pub arg0: usize,
     pub arg1: usize,
     pub arg2: usize,
+    pub arg3: usize,
+    pub arg4: usize,
+    pub arg5: usize,
 }
 
 pub fn dispatcher(context: &mut ProcessContext) {
@@ -45,60 +55,128 @@ pub fn dispatcher(context: &mut ProcessContext) {
         context.regs.rdi,
         context.regs.rsi,
         context.regs.rdx,
+        context.regs.r10,
+        context.regs.r8,
+        context.regs.r9,
     );
 
     // NOTE: you may want to trace syscall arguments
     // trace!("{}", args);
+    let strace = SyscallTrace::enter(&args);
 
     match args.syscall {
         // fd: arg0 as u8, buf: &[u8] (arg1 as *const u8, arg2 as len)
-        Syscall::Read => context.set_rax(sys_read(&args)),
+        Syscall::Read => context.set_rax(encode_result(sys_read(&args))),
         // fd: arg0 as u8, buf: &[u8] (arg1 as *const u8, arg2 as len)
-        Syscall::Write => context.set_rax(sys_write(&args)),
+        Syscall::Write => context.set_rax(encode_result(sys_write(&args))),
         // None -> pid: u16
         Syscall::GetPid => context.set_rax(sys_get_pid() as usize),
-        // path: &str (arg0 as *const u8, arg1 as len) -> pid: u16
-        Syscall::Spawn => context.set_rax(spawn_process(&args)),
+        // path: &str (arg0 as *const u8, arg1 as len),
+        // argv: &[&str] (arg2 as *const [ptr, len], arg3 as argc),
+        // envp: &[&str] (arg4 as *const [ptr, len], arg5 as envc) -> pid: u16
+        Syscall::Spawn => context.set_rax(encode_result(spawn_process(&args))),
+        // path: &str (arg0 as *const u8, arg1 as len),
+        // argv: &[&str] (arg2 as *const [ptr, len], arg3 as argc),
+        // envp: &[&str] (arg4 as *const [ptr, len], arg5 as envc) -> does not return on success
+        Syscall::Exec => sys_exec(&args, context),
         // pid: arg0 as u16
         Syscall::Exit => exit_process(&args, context),
-        // pid: arg0 as u16 -> status: isize
+        // pid: arg0 as isize (-1 = any child), options: arg1 as usize -> pid | status << 16
         Syscall::WaitPid => sys_wait_pid(&args, context),
-        // pid: arg0 as u16
-        Syscall::Kill => sys_kill(&args, context),
-        // None
-        Syscall::Stat => list_process(),
-        // None
-        Syscall::ListApp => list_app(),
+        // pid: arg0 as u16, signal: arg1 as usize -> result: usize (0 = success)
+        Syscall::Kill => context.set_rax(encode_result(sys_kill(&args))),
+        // buf: &mut [ProcInfo] (arg0 as *mut ProcInfo, arg1 as len) -> process count: usize
+        Syscall::ProcInfo => context.set_rax(encode_result(sys_proc_info(&args))),
         // None -> pid: u16
         Syscall::VFork => sys_vfork(context),
+        // None -> pid: u16
+        Syscall::Fork => sys_fork(context),
+        // op: arg0 as usize, key: arg1 as u32, value: arg2 as usize -> result: usize (0 = success)
         Syscall::Sem => sys_sem(&args, context),
         // path: &str (arg0 as *const u8, arg1 as len) -> result: usize (0 = success)
-        Syscall::ListDir => context.set_rax(list_dir(&args)),
-        // path: &str (arg0 as *const u8, arg1 as len) -> fd: u8
-        Syscall::Open => context.set_rax(sys_open(&args)),
+        Syscall::ListDir => context.set_rax(encode_result(list_dir(&args))),
+        // path: &str (arg0 as *const u8, arg1 as len), flags: arg2 -> fd: u8
+        Syscall::Open => context.set_rax(encode_result(sys_open(&args))),
         // fd: arg0 as u8 -> result: usize (0 = success)
-        Syscall::Close => context.set_rax(sys_close(&args)),
-        Syscall::Brk => {
-            let ret = sys_brk(&args);
-            context.set_rax(ret as usize);
-        },
-
-        // layout: arg0 as *const Layout -> ptr: *mut u8
-        Syscall::Allocate => context.set_rax(sys_allocate(&args)),
-        // ptr: arg0 as *mut u8
-        Syscall::Deallocate => sys_deallocate(&args),
+        Syscall::Close => context.set_rax(encode_result(sys_close(&args))),
+        // addr: arg0 as usize (0 = query) -> heap end: usize
+        Syscall::Brk => context.set_rax(encode_result(sys_brk(&args))),
+        // pid: arg0 as u16 (0 = current), enable: arg1 as bool -> result: usize (0 = success)
+        Syscall::Trace => context.set_rax(encode_result(sys_trace(&args))),
+        // entry: arg0 as fn(usize) -> !, arg: arg1 as usize -> tid: u16
+        Syscall::ThreadCreate => context.set_rax(encode_result(sys_thread_create(&args))),
+        // tid: arg0 as u16 -> tid | status << 16
+        Syscall::ThreadJoin => sys_thread_join(&args, context),
+        // signal: arg0 as usize, handler: arg1 as usize, mask: arg2 as u64,
+        // restorer: arg3 as usize -> old handler: usize
+        Syscall::SigAction => context.set_rax(encode_result(sys_sigaction(&args))),
+        // how: arg0 as usize, set: arg1 as u64 -> old set: u64
+        Syscall::SigProcMask => context.set_rax(encode_result(sys_sigprocmask(&args))),
+        // None -> does not return
+        Syscall::SigReturn => sigreturn(context),
+        // None -> 0
+        Syscall::Yield => yield_now(context),
+        // duration: arg0 as u64 (ns) -> 0
+        Syscall::Sleep => sleep(Duration::from_nanos(args.arg0 as u64), context),
+        // None -> time since boot: u64 (ns)
+        Syscall::Time => context.set_rax(sys_time()),
+        // inc: arg0 as isize -> nice - NICE_MIN: usize
+        Syscall::Nice => context.set_rax(encode_result(sys_nice(&args))),
+        // pid: arg0 as u16 (0 = current), nice: arg1 as isize -> nice - NICE_MIN: usize
+        Syscall::SetPriority => context.set_rax(encode_result(sys_set_priority(&args))),
+
+        // path: &str (arg0 as *const u8, arg1 as len) -> result: usize (0 = success)
+        Syscall::Mkdir => context.set_rax(encode_result(sys_mkdir(&args))),
+        // path: &str (arg0 as *const u8, arg1 as len) -> result: usize (0 = success)
+        Syscall::Rmdir => context.set_rax(encode_result(sys_rmdir(&args))),
+        // path: &str (arg0 as *const u8, arg1 as len) -> result: usize (0 = success)
+        Syscall::Unlink => context.set_rax(encode_result(sys_unlink(&args))),
+        // src: &str (arg0 as *const u8, arg1 as len), dst: &str (arg2 as *const u8, arg3 as len)
+        // -> result: usize (0 = success)
+        Syscall::Rename => context.set_rax(encode_result(sys_rename(&args))),
+
+        // None -> result: usize (0 = success)
+        Syscall::Sync => context.set_rax(encode_result(sys_sync())),
+        // source: &str (arg0 as *const u8, arg1 as len), target: &str (arg2 as *const u8, arg3 as len),
+        // fs_type: arg4 as FsType, flags: arg5 -> result: usize (0 = success)
+        Syscall::Mount => context.set_rax(encode_result(sys_mount(&args))),
+        // target: &str (arg0 as *const u8, arg1 as len) -> result: usize (0 = success)
+        Syscall::Umount => context.set_rax(encode_result(sys_umount(&args))),
+
+        // layout: arg0 as *const [usize; 2] (size, align) -> ptr: *mut u8
+        Syscall::Allocate => context.set_rax(encode_result(sys_allocate(&args))),
+        // ptr: arg0 as *mut u8, layout: arg1 as *const [usize; 2] (size, align) -> result: usize (0 = success)
+        Syscall::Deallocate => context.set_rax(encode_result(sys_deallocate(&args))),
         // None
-        Syscall::Unknown => {}
+        Syscall::Unknown => {
+            warn!("Unhandled syscall: {:?}", context.regs.rax);
+            context.set_rax(SyscallError::NotSupported.encode());
+        }
+    }
+
+    if let Some(strace) = strace {
+        strace.exit(context);
     }
 }
 
 impl SyscallArgs {
-    pub fn new(syscall: Syscall, arg0: usize, arg1: usize, arg2: usize) -> Self {
+    pub fn new(
+        syscall: Syscall,
+        arg0: usize,
+        arg1: usize,
+        arg2: usize,
+        arg3: usize,
+        arg4: usize,
+        arg5: usize,
+    ) -> Self {
         Self {
             syscall,
             arg0,
             arg1,
             arg2,
+            arg3,
+            arg4,
+            arg5,
         }
     }
 }
@@ -107,11 +185,14 @@ impl core::fmt::Display for SyscallArgs {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(
             f,
-            "SYSCALL: {:<10} (0x{:016x}, 0x{:016x}, 0x{:016x})",
+            "SYSCALL: {:<10} (0x{:016x}, 0x{:016x}, 0x{:016x}, 0x{:016x}, 0x{:016x}, 0x{:016x})",
             format!("{:?}", self.syscall),
             self.arg0,
             self.arg1,
-            self.arg2
+            self.arg2,
+            self.arg3,
+            self.arg4,
+            self.arg5
         )
     }
 }
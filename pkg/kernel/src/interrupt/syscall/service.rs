@@ -1,61 +1,111 @@
+use alloc::vec;
 use core::alloc::Layout;
 
 use crate::proc::*;
-use crate::memory::*;
-use crate::drivers::filesystem;
+use crate::memory::uaccess::*;
+use crate::memory::user::{USER_HEAP_SIZE, USER_HEAP_START};
+use crate::drivers::filesystem::{self, fs_error};
 use crate::utils::resource::Resource;
 use crate::proc::get_process_manager;
+use storage::FileSystem;
+use syscall_def::fs::{FsType, MOUNT_READ_ONLY};
+use syscall_def::proc::ProcInfo;
+use syscall_def::sched::encode_nice;
+use syscall_def::signal::SigSet;
+use syscall_def::{SyscallError, SyscallResult, encode_result};
 use x86_64::VirtAddr;
 
 use super::SyscallArgs;
 
-pub fn spawn_process(args: &SyscallArgs) -> usize {
+pub fn spawn_process(args: &SyscallArgs) -> SyscallResult {
     // FIXME: get app name by args
     //       - core::str::from_utf8_unchecked
     //       - core::slice::from_raw_parts
     // FIXME: spawn the process by name
-    // FIXME: handle spawn error, return 0 if failed
+    // FIXME: handle spawn error
     // FIXME: return pid as usize
-    let name = unsafe {
-        core::str::from_utf8_unchecked(core::slice::from_raw_parts(
-            args.arg0 as *const u8,
-            args.arg1,
-        ))
-    };
-
-    let pid = crate::proc::spawn(name);
+    let name = read_user_str(args.arg0, args.arg1)?;
+    let argv = read_user_strs(args.arg2, args.arg3)?;
+    let envp = read_user_strs(args.arg4, args.arg5)?;
+
+    match crate::proc::spawn(&name, argv, envp) {
+        Ok(pid) => Ok(pid.0 as usize),
+        Err(err) => {
+            warn!("spawn_process: failed to spawn process {}: {}", name, err);
+            Err(err)
+        }
+    }
+}
 
-    if pid.is_err() {
-        warn!("spawn_process: failed to spawn process: {}", name);
-        return 0;
+pub fn sys_exec(args: &SyscallArgs, context: &mut ProcessContext) {
+    // on success the context already belongs to the new program
+    if let Err(err) = exec_process(args, context) {
+        context.set_rax(err.encode());
     }
+}
 
-    pid.unwrap().0 as usize
+fn exec_process(args: &SyscallArgs, context: &mut ProcessContext) -> SyscallResult<()> {
+    let path = read_user_str(args.arg0, args.arg1)?;
+    let argv = read_user_strs(args.arg2, args.arg3)?;
+    let envp = read_user_strs(args.arg4, args.arg5)?;
+
+    crate::proc::exec(&path, argv, envp, context).inspect_err(|err| {
+        warn!("sys_exec: failed to exec {}: {}", path, err);
+    })
 }
 
-pub fn sys_write(args: &SyscallArgs) -> usize {
+/// Bytes copied between user and kernel at a time by `sys_read` and `sys_write`
+const IO_CHUNK_SIZE: usize = 4096;
+
+pub fn sys_write(args: &SyscallArgs) -> SyscallResult {
     // FIXME: get buffer and fd by args
     //       - core::slice::from_raw_parts
     // FIXME: call proc::write -> isize
     // FIXME: return the result as usize
-    let buf = match as_user_slice(args.arg1, args.arg2) {
-        Some(buf) => buf,
-        None => return usize::MAX,
-    };
-
+    check_user_range(args.arg1, args.arg2, false)?;
     let fd = args.arg0 as u8;
-    write(fd, buf) as usize
+
+    // copy through a bounce buffer, the length is only bounded by user memory
+    let mut buf = vec![0u8; IO_CHUNK_SIZE.min(args.arg2)];
+    let mut done = 0;
+    while done < args.arg2 {
+        let len = buf.len().min(args.arg2 - done);
+        copy_from_user(&mut buf[..len], args.arg1 + done)?;
+        let written = match write(fd, &buf[..len]) {
+            Ok(written) => written,
+            Err(_) if done > 0 => break,
+            Err(err) => return Err(err),
+        };
+        done += written;
+        if written < len {
+            break;
+        }
+    }
+    Ok(done)
 }
 
-pub fn sys_read(args: &SyscallArgs) -> usize {
+pub fn sys_read(args: &SyscallArgs) -> SyscallResult {
     // FIXME: just like sys_write
-    let buf = match as_user_slice_mut(args.arg1, args.arg2) {
-        Some(buf) => buf,
-        None => return usize::MAX,
-    };
-
+    check_user_range(args.arg1, args.arg2, true)?;
     let fd = args.arg0 as u8;
-    read(fd, buf) as usize
+
+    let mut buf = vec![0u8; IO_CHUNK_SIZE.min(args.arg2)];
+    let mut done = 0;
+    while done < args.arg2 {
+        let len = buf.len().min(args.arg2 - done);
+        let count = match read(fd, &mut buf[..len]) {
+            Ok(count) => count,
+            Err(_) if done > 0 => break,
+            Err(err) => return Err(err),
+        };
+        copy_to_user(args.arg1 + done, &buf[..count])?;
+        done += count;
+        // a short read, e.g. the end of the file or no more input
+        if count < len {
+            break;
+        }
+    }
+    Ok(done)
 }
 
 pub fn exit_process(args: &SyscallArgs, context: &mut ProcessContext) {
@@ -63,163 +113,148 @@ pub fn exit_process(args: &SyscallArgs, context: &mut ProcessContext) {
     process_exit(args.arg0 as isize, context);
 }
 
-pub fn list_process() {
-    // FIXME: list all processes
-    print_process_list();
+/// Fill the user buffer with process records, returns the number of processes
+pub fn sys_proc_info(args: &SyscallArgs) -> SyscallResult {
+    let infos = proc_info();
+    let count = infos.len().min(args.arg1);
+
+    let bytes = unsafe {
+        core::slice::from_raw_parts(infos.as_ptr() as *const u8, count * size_of::<ProcInfo>())
+    };
+    copy_to_user(args.arg0, bytes)?;
+    Ok(infos.len())
 }
 
 pub fn sys_wait_pid(args: &SyscallArgs, context: &mut ProcessContext) {
-    let pid = ProcessId(args.arg0 as u16);
-    wait_pid(pid, context);
+    wait_pid(args.arg0 as isize, args.arg1, context);
+}
+
+pub fn sys_thread_create(args: &SyscallArgs) -> SyscallResult {
+    thread_create(VirtAddr::new_truncate(args.arg0 as u64), args.arg1).map(|tid| tid.0 as usize)
+}
+
+pub fn sys_thread_join(args: &SyscallArgs, context: &mut ProcessContext) {
+    thread_join(ProcessId(args.arg0 as u16), context);
 }
 
-pub fn sys_kill(args: &SyscallArgs, context: &mut ProcessContext) {
+pub fn sys_kill(args: &SyscallArgs) -> SyscallResult {
     if args.arg0 == 1 {
         warn!("sys_kill: cannot kill kernel!");
-        return;
+        return Err(SyscallError::PermissionDenied);
     }
 
-    kill(ProcessId(args.arg0 as u16), context);
+    kill(ProcessId(args.arg0 as u16), args.arg1).map(|_| 0)
+}
+
+pub fn sys_sigaction(args: &SyscallArgs) -> SyscallResult {
+    let action = SigAction {
+        handler: args.arg1,
+        mask: SigSet(args.arg2 as u64),
+        restorer: args.arg3,
+    };
+    sigaction(args.arg0, action)
+}
+
+pub fn sys_sigprocmask(args: &SyscallArgs) -> SyscallResult {
+    sigprocmask(args.arg0, SigSet(args.arg1 as u64)).map(|old| old.0 as usize)
 }
 
 pub fn sys_get_pid() -> u16 {
     get_pid().0
 }
 
-pub fn sys_allocate(args: &SyscallArgs) -> usize {
-    let layout = unsafe { (args.arg0 as *const Layout).as_ref().unwrap() };
+pub fn sys_allocate(args: &SyscallArgs) -> SyscallResult {
+    let layout = read_user_layout(args.arg0)?;
 
     if layout.size() == 0 {
-        return 0;
+        return Err(SyscallError::InvalidArgument);
     }
 
-    let ret = crate::memory::user::USER_ALLOCATOR
-        .lock()
-        .allocate_first_fit(*layout);
-
-    match ret {
-        Ok(ptr) => ptr.as_ptr() as usize,
-        Err(_) => 0,
+    match allocate(layout) {
+        Some(ptr) => Ok(ptr.as_ptr() as usize),
+        None => Err(SyscallError::NoMemory),
     }
 }
 
-macro_rules! check_access {
-    ($addr:expr, $fmt:expr) => {
-        if !is_user_accessable($addr) {
-            warn!($fmt, $addr);
-            return;
-        }
-    };
-}
-
-pub fn sys_deallocate(args: &SyscallArgs) {
+pub fn sys_deallocate(args: &SyscallArgs) -> SyscallResult {
     if args.arg0 == 0 {
-        return;
+        return Ok(0);
     }
 
-    check_access!(args.arg0, "sys_deallocate: invalid access to {:#x}");
-
-    check_access!(args.arg1, "sys_deallocate: invalid access to {:#x}");
-
-    let layout = unsafe { (args.arg1 as *const Layout).as_ref().unwrap() };
+    let layout = read_user_layout(args.arg1)?;
 
     if layout.size() == 0 {
-        return;
+        return Ok(0);
     }
 
-    check_access!(
-        args.arg0 + layout.size() - 1,
-        "sys_deallocate: invalid access to {:#x}"
-    );
+    let heap = USER_HEAP_START..USER_HEAP_START + USER_HEAP_SIZE;
+    if !heap.contains(&args.arg0) || args.arg0 + layout.size() > heap.end {
+        return Err(SyscallError::InvalidArgument);
+    }
+    check_user_range(args.arg0, layout.size(), true)?;
 
     unsafe {
-        crate::memory::user::USER_ALLOCATOR.lock().deallocate(
-            core::ptr::NonNull::new_unchecked(args.arg0 as *mut u8),
-            *layout,
-        );
+        deallocate(core::ptr::NonNull::new_unchecked(args.arg0 as *mut u8), layout);
     }
+
+    Ok(0)
+}
+
+/// Read a layout passed by pointer as `[size, align]` and check that it is well-formed
+fn read_user_layout(ptr: usize) -> SyscallResult<Layout> {
+    let [size, align]: [usize; 2] = read_user(ptr)?;
+    Layout::from_size_align(size, align).map_err(|_| SyscallError::InvalidArgument)
 }
 
 pub fn sys_vfork(context: &mut ProcessContext) {
     vfork(context);
 }
 
+pub fn sys_fork(context: &mut ProcessContext) {
+    fork(context);
+}
+
 pub fn sys_sem(args: &SyscallArgs, context: &mut ProcessContext) {
     match args.arg0 {
-        0 => context.set_rax(sem_new(args.arg1 as u32, args.arg2) as usize),
+        0 => context.set_rax(encode_result(sem_new(args.arg1 as u32, args.arg2))),
         1 => sem_wait(args.arg1 as u32, context),
         2 => sem_signal(args.arg1 as u32, context),
-        3 => context.set_rax(remove_sem(args.arg1 as u32)),
-        _ => context.set_rax(usize::MAX),
+        3 => context.set_rax(encode_result(remove_sem(args.arg1 as u32))),
+        _ => context.set_rax(SyscallError::InvalidArgument.encode()),
     }
 }
 
-pub fn list_dir(args: &SyscallArgs) -> usize {
-    let path_ptr = args.arg0 as *const u8;
-    let path_len = args.arg1;
-
+pub fn list_dir(args: &SyscallArgs) -> SyscallResult {
     // 参数验证
-    if path_ptr.is_null() || path_len == 0 {
-        warn!("list_dir: Invalid parameters (ptr: {:p}, len: {})", path_ptr, path_len);
-        return 1; // 返回错误码 1
-    }
-
-    // 长度合理性检查
-    if path_len > 4096 {  // 防止过长的路径
-        warn!("list_dir: Path too long: {}", path_len);
-        return 2; // 返回错误码 2
+    if args.arg0 == 0 || args.arg1 == 0 {
+        warn!("list_dir: Invalid parameters (ptr: {:#x}, len: {})", args.arg0, args.arg1);
+        return Err(SyscallError::InvalidArgument);
     }
 
-    // 安全地从用户空间读取路径字符串
-    let path_slice = unsafe {
-        core::slice::from_raw_parts(path_ptr, path_len)
-    };
-
-    let path_str = match core::str::from_utf8(path_slice) {
-        Ok(s) => s,
-        Err(e) => {
-            warn!("list_dir: Invalid UTF-8 in path: {:?}", e);
-            return 3; // 返回错误码 3
-        }
-    };
+    // 安全地从用户空间复制路径字符串
+    let path = read_user_str(args.arg0, args.arg1)?;
+    let path_str = path.as_str();
 
     trace!("list_dir: Listing directory '{}'", path_str);
 
     // 调用文件系统的 ls 函数
-    filesystem::ls(path_str);
-    
-    0 // 成功返回 0
+    filesystem::ls(path_str).map_err(fs_error)?;
+
+    Ok(0)
 }
 
 /// 打开文件
-/// path: &str (arg0 as *const u8, arg1 as len) -> fd: u8 (or -1 on error)
-pub fn sys_open(args: &SyscallArgs) -> usize {
-    let path_ptr = args.arg0 as *const u8;
-    let path_len = args.arg1;
-
+/// path: &str (arg0 as *const u8, arg1 as len) -> fd: u8
+pub fn sys_open(args: &SyscallArgs) -> SyscallResult {
     // 参数验证
-    if path_ptr.is_null() || path_len == 0 {
-        warn!("sys_open: Invalid parameters");
-        return usize::MAX; // 返回 -1（转换为 usize）
+    if args.arg0 == 0 || args.arg1 == 0 {
+        warn!("sys_open: Invalid parameters (ptr: {:#x}, len: {})", args.arg0, args.arg1);
+        return Err(SyscallError::InvalidArgument);
     }
 
-    if path_len > 4096 {
-        warn!("sys_open: Path too long: {}", path_len);
-        return usize::MAX;
-    }
-
-    // 安全地从用户空间读取路径字符串
-    let path_slice = unsafe {
-        core::slice::from_raw_parts(path_ptr, path_len)
-    };
-
-    let path_str = match core::str::from_utf8(path_slice) {
-        Ok(s) => s,
-        Err(_) => {
-            warn!("sys_open: Invalid UTF-8 in path");
-            return usize::MAX;
-        }
-    };
+    // 安全地从用户空间复制路径字符串
+    let path = read_user_str(args.arg0, args.arg1)?;
+    let path_str = path.as_str();
 
     trace!("sys_open: Opening file '{}'", path_str);
 
@@ -227,23 +262,23 @@ pub fn sys_open(args: &SyscallArgs) -> usize {
     let process_arc = get_process_manager().current(); // Corrected: Use get_process_manager().current()
 
     // 通过文件系统打开文件
-    match filesystem::get_rootfs().fs.open_file(path_str) {
+    match filesystem::open(path_str, args.arg2) {
         Ok(file_handle) => {
             // 将文件句柄添加到进程的资源集合中
-            let fd = process_arc.write().open_resource(Resource::File(file_handle));
+            let fd = process_arc.write().open_resource(Resource::File(file_handle))?;
             trace!("sys_open: Opened file '{}' with fd {}", path_str, fd);
-            fd as usize
+            Ok(fd as usize)
         }
         Err(e) => {
             warn!("sys_open: Failed to open file '{}': {:?}", path_str, e);
-            usize::MAX
+            Err(fs_error(e))
         }
     }
 }
 
 /// 关闭文件描述符
-/// fd: arg0 as u8 -> result: usize (0 = success, -1 = error)
-pub fn sys_close(args: &SyscallArgs) -> usize {
+/// fd: arg0 as u8 -> result: usize (0 = success)
+pub fn sys_close(args: &SyscallArgs) -> SyscallResult {
     let fd = args.arg0 as u8;
 
     trace!("sys_close: Closing fd {}", fd);
@@ -254,21 +289,94 @@ pub fn sys_close(args: &SyscallArgs) -> usize {
     // 关闭文件描述符
     if process_arc.write().close_resource(fd) {
         trace!("sys_close: Successfully closed fd {}", fd);
-        0
+        Ok(0)
     } else {
         warn!("sys_close: Failed to close fd {} (not found)", fd);
-        usize::MAX
+        Err(SyscallError::BadFd)
     }
 }
 
-pub fn sys_brk(args: &SyscallArgs) -> usize {
+pub fn sys_mkdir(args: &SyscallArgs) -> SyscallResult {
+    let path = read_user_str(args.arg0, args.arg1)?;
+    filesystem::get_vfs().create_dir(&path).map_err(fs_error)?;
+    Ok(0)
+}
+
+pub fn sys_rmdir(args: &SyscallArgs) -> SyscallResult {
+    let path = read_user_str(args.arg0, args.arg1)?;
+    filesystem::get_vfs().remove_dir(&path).map_err(fs_error)?;
+    Ok(0)
+}
+
+pub fn sys_unlink(args: &SyscallArgs) -> SyscallResult {
+    let path = read_user_str(args.arg0, args.arg1)?;
+    filesystem::get_vfs().remove_file(&path).map_err(fs_error)?;
+    Ok(0)
+}
+
+pub fn sys_rename(args: &SyscallArgs) -> SyscallResult {
+    let src = read_user_str(args.arg0, args.arg1)?;
+    let dst = read_user_str(args.arg2, args.arg3)?;
+    filesystem::rename(&src, &dst).map_err(fs_error)?;
+    Ok(0)
+}
+
+pub fn sys_mount(args: &SyscallArgs) -> SyscallResult {
+    let source = read_user_str(args.arg0, args.arg1)?;
+    let target = read_user_str(args.arg2, args.arg3)?;
+    let fs_type = FsType::try_from(args.arg4).map_err(|_| SyscallError::InvalidArgument)?;
+    let read_only = args.arg5 & MOUNT_READ_ONLY != 0;
+
+    filesystem::mount(&source, &target, fs_type, read_only).map_err(fs_error)?;
+    Ok(0)
+}
+
+/// Write every cached dirty block back to the disk
+pub fn sys_sync() -> SyscallResult {
+    filesystem::sync().map_err(fs_error)?;
+    Ok(0)
+}
+
+pub fn sys_umount(args: &SyscallArgs) -> SyscallResult {
+    let target = read_user_str(args.arg0, args.arg1)?;
+
+    filesystem::umount(&target).map_err(fs_error)?;
+    Ok(0)
+}
+
+/// pid: arg0 as u16 (0 = current), enable: arg1 != 0
+pub fn sys_trace(args: &SyscallArgs) -> SyscallResult {
+    let pid = match args.arg0 as u16 {
+        0 => None,
+        pid => Some(ProcessId(pid)),
+    };
+
+    set_trace(pid, args.arg1 != 0)?;
+    Ok(0)
+}
+
+pub fn sys_time() -> usize {
+    crate::utils::clock::now().as_nanos() as usize
+}
+
+pub fn sys_set_priority(args: &SyscallArgs) -> SyscallResult {
+    set_priority(ProcessId(args.arg0 as u16), args.arg1 as isize).map(encode_nice)
+}
+
+pub fn sys_nice(args: &SyscallArgs) -> SyscallResult {
+    nice(args.arg0 as isize).map(encode_nice)
+}
+
+pub fn sys_brk(args: &SyscallArgs) -> SyscallResult {
     let new_heap_end = if args.arg0 == 0 {
         None
+    } else if args.arg0 as u64 >= USER_SPACE_END {
+        return Err(SyscallError::InvalidArgument);
     } else {
         Some(VirtAddr::new(args.arg0 as u64))
     };
     match brk(new_heap_end) {
-        Some(new_heap_end) => new_heap_end.as_u64() as usize,
-        None => !0,
+        Some(new_heap_end) => Ok(new_heap_end.as_u64() as usize),
+        None => Err(SyscallError::NoMemory),
     }
 }
\ No newline at end of file
@@ -0,0 +1,197 @@
+//! Per-process syscall tracing, enabled by `Syscall::Trace`
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use syscall_def::sched::decode_nice;
+use syscall_def::wait::decode_wait;
+use syscall_def::{Syscall, decode_result};
+
+use super::SyscallArgs;
+use crate::memory::uaccess::*;
+use crate::proc::*;
+
+/// Longest prefix of a write buffer shown in the trace
+const MAX_TRACE_BYTES: usize = 32;
+
+/// A syscall entered by a traced process, logged once it returns
+pub struct SyscallTrace {
+    pid: ProcessId,
+    name: String,
+    syscall: Syscall,
+    call: String,
+}
+
+impl SyscallTrace {
+    /// Start tracing `args` if the current process is traced
+    ///
+    /// The arguments are decoded here, as user buffers may be gone
+    /// by the time the syscall returns (e.g. `Exit`).
+    pub fn enter(args: &SyscallArgs) -> Option<Self> {
+        if !is_traced() {
+            return None;
+        }
+
+        let proc = get_process_manager().current();
+        let name = proc.read().name().to_string();
+
+        Some(Self {
+            pid: proc.pid(),
+            name,
+            syscall: args.syscall.clone(),
+            call: describe(args),
+        })
+    }
+
+    /// Log the syscall together with its return value
+    pub fn exit(self, context: &ProcessContext) {
+        // the syscall blocked or exited, `rax` belongs to another process now
+        if processor::current_pid() != Some(self.pid) {
+            info!("[strace] {}#{}: {} = ? <unfinished>", self.name, self.pid, self.call);
+            return;
+        }
+
+        info!(
+            "[strace] {}#{}: {} = {}",
+            self.name,
+            self.pid,
+            self.call,
+            describe_ret(&self.syscall, context.regs.rax)
+        );
+    }
+}
+
+fn describe(args: &SyscallArgs) -> String {
+    let name = args.syscall.clone();
+    match args.syscall {
+        Syscall::Read => format!(
+            "{:?}(fd={}, buf={:#x}, len={})",
+            name, args.arg0 as u8, args.arg1, args.arg2
+        ),
+        Syscall::Write => format!(
+            "{:?}(fd={}, {}, len={})",
+            name,
+            args.arg0 as u8,
+            user_bytes(args.arg1, args.arg2),
+            args.arg2
+        ),
+        Syscall::Spawn | Syscall::Exec => format!(
+            "{:?}({}, argv={}, envp={})",
+            name,
+            user_str(args.arg0, args.arg1),
+            user_strs(args.arg2, args.arg3),
+            user_strs(args.arg4, args.arg5)
+        ),
+        Syscall::Open => format!(
+            "{:?}({}, flags={:#x})",
+            name,
+            user_str(args.arg0, args.arg1),
+            args.arg2
+        ),
+        Syscall::ListDir | Syscall::Mkdir | Syscall::Rmdir | Syscall::Unlink => {
+            format!("{:?}({})", name, user_str(args.arg0, args.arg1))
+        }
+        Syscall::Rename => format!(
+            "{:?}({}, {})",
+            name,
+            user_str(args.arg0, args.arg1),
+            user_str(args.arg2, args.arg3)
+        ),
+        Syscall::Close => format!("{:?}(fd={})", name, args.arg0 as u8),
+        Syscall::Exit => format!("{:?}({})", name, args.arg0 as isize),
+        Syscall::WaitPid => format!("{:?}(pid={}, options={:#x})", name, args.arg0 as isize, args.arg1),
+        Syscall::Kill => format!("{:?}(pid={}, sig={})", name, args.arg0 as u16, args.arg1),
+        Syscall::SigAction => format!(
+            "{:?}(sig={}, handler={:#x}, mask={:#x})",
+            name, args.arg0, args.arg1, args.arg2
+        ),
+        Syscall::SigProcMask => format!("{:?}(how={}, set={:#x})", name, args.arg0, args.arg1),
+        Syscall::Sem => {
+            let op = match args.arg0 {
+                0 => "new",
+                1 => "wait",
+                2 => "signal",
+                3 => "free",
+                _ => "?",
+            };
+            match args.arg0 {
+                0 => format!("{:?}({}, key={:#x}, value={})", name, op, args.arg1 as u32, args.arg2),
+                _ => format!("{:?}({}, key={:#x})", name, op, args.arg1 as u32),
+            }
+        }
+        Syscall::ThreadCreate => format!("{:?}(entry={:#x}, arg={:#x})", name, args.arg0, args.arg1),
+        Syscall::ThreadJoin => format!("{:?}(tid={})", name, args.arg0 as u16),
+        Syscall::Sleep => format!("{:?}({}ns)", name, args.arg0),
+        Syscall::Nice => format!("{:?}({})", name, args.arg0 as isize),
+        Syscall::SetPriority => {
+            format!("{:?}(pid={}, nice={})", name, args.arg0 as u16, args.arg1 as isize)
+        }
+        Syscall::Mount => format!(
+            "{:?}({}, {}, type={}, flags={:#x})",
+            name,
+            user_str(args.arg0, args.arg1),
+            user_str(args.arg2, args.arg3),
+            args.arg4,
+            args.arg5
+        ),
+        Syscall::Umount => format!("{:?}({})", name, user_str(args.arg0, args.arg1)),
+        Syscall::ProcInfo => format!("{:?}(buf={:#x}, len={})", name, args.arg0, args.arg1),
+        Syscall::Brk => format!("{:?}({:#x})", name, args.arg0),
+        Syscall::Trace => format!("{:?}(pid={}, enable={})", name, args.arg0 as u16, args.arg1 != 0),
+        Syscall::Allocate => format!("{:?}({})", name, user_layout(args.arg0)),
+        Syscall::Deallocate => format!("{:?}({:#x}, {})", name, args.arg0, user_layout(args.arg1)),
+        Syscall::GetPid
+        | Syscall::VFork
+        | Syscall::Fork
+        | Syscall::Yield
+        | Syscall::Time
+        | Syscall::Sync
+        | Syscall::SigReturn => format!("{:?}()", name),
+        Syscall::Unknown => format!("{}", args),
+    }
+}
+
+fn describe_ret(syscall: &Syscall, rax: usize) -> String {
+    match decode_result(rax) {
+        Ok(value) => match syscall {
+            Syscall::Brk | Syscall::Allocate => format!("{:#x}", value),
+            Syscall::Nice | Syscall::SetPriority => format!("{}", decode_nice(value)),
+            Syscall::WaitPid | Syscall::ThreadJoin if value != 0 => {
+                let (pid, status) = decode_wait(value);
+                format!("{} ({})", pid, status)
+            }
+            _ => format!("{}", value),
+        },
+        Err(err) => format!("-{} {:?} ({})", err.errno(), err, err),
+    }
+}
+
+fn user_str(ptr: usize, len: usize) -> String {
+    match read_user_str(ptr, len) {
+        Ok(s) => format!("{:?}", s),
+        Err(_) => format!("{:#x}", ptr),
+    }
+}
+
+fn user_strs(ptr: usize, count: usize) -> String {
+    match read_user_strs(ptr, count) {
+        Ok(strs) => format!("{:?}", strs),
+        Err(_) => format!("{:#x}", ptr),
+    }
+}
+
+fn user_bytes(ptr: usize, len: usize) -> String {
+    match read_user_bytes(ptr, len.min(MAX_TRACE_BYTES)) {
+        Ok(bytes) if len > MAX_TRACE_BYTES => {
+            format!("{:?}...", String::from_utf8_lossy(&bytes))
+        }
+        Ok(bytes) => format!("{:?}", String::from_utf8_lossy(&bytes)),
+        Err(_) => format!("{:#x}", ptr),
+    }
+}
+
+fn user_layout(ptr: usize) -> String {
+    match read_user::<[usize; 2]>(ptr) {
+        Ok([size, align]) => format!("size={}, align={}", size, align),
+        Err(_) => format!("{:#x}", ptr),
+    }
+}
@@ -29,6 +29,7 @@ pub use drivers::*;
 pub mod memory;
 pub mod interrupt;
 pub mod proc;
+pub mod smp;
 
 pub use alloc::format;
 
@@ -48,13 +49,14 @@ pub fn init(boot_info: &'static BootInfo) {
     memory::allocator::init(); // init kernel heap allocator
     interrupt::init(); // init interrupts
     memory::init(boot_info); // init memory manager
-    memory::user::init(); // init user memory manager
     proc::init(boot_info); // init process manager
     filesystem::init();
 
     x86_64::instructions::interrupts::enable();
     info!("Interrupts Enabled.");
 
+    smp::init(boot_info); // start application processors
+
     info!("Growing stack test...");
     grow_stack();
     info!("Stack test done.");
@@ -85,15 +87,19 @@ pub fn grow_stack() {
 
 pub fn wait(init: proc::ProcessId) {
     loop {
-        if proc::wait_no_block(init).is_none() {
-            x86_64::instructions::hlt();
-        } else {
+        if proc::wait_no_block(init).is_some() {
             break;
         }
+        // the kernel is the init process, it reaps adopted orphans
+        proc::reap_orphans(init);
+        x86_64::instructions::hlt();
     }
 }
 
 pub fn shutdown() -> ! {
+    if let Err(err) = filesystem::sync() {
+        warn!("Failed to sync filesystem: {:?}", err);
+    }
     info!("YatSenOS shutting down.");
     uefi::runtime::reset(ResetType::SHUTDOWN, Status::SUCCESS, None);
 }
@@ -6,6 +6,8 @@ use ysos_kernel as ysos;
 
 extern crate alloc;
 
+use alloc::vec::Vec;
+
 boot::entry_point!(kernel_main);
 
 pub fn kernel_main(boot_info: &'static boot::BootInfo) -> ! {
@@ -63,5 +65,5 @@ fn test_mbr(drive: &drivers::ata::AtaDrive) {
 
 pub fn spawn_init() -> proc::ProcessId {
     proc::list_app();
-    proc::spawn("sh").unwrap()
+    proc::spawn("sh", Vec::new(), Vec::new()).unwrap()
 }
\ No newline at end of file
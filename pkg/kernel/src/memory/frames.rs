@@ -2,6 +2,7 @@ use alloc::boxed::Box;
 use boot::{MemoryMap, MemoryType};
 use x86_64::structures::paging::{FrameAllocator, FrameDeallocator, PhysFrame, Size4KiB};
 use x86_64::PhysAddr;
+use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
 
 once_mutex!(pub FRAME_ALLOCATOR: BootInfoFrameAllocator);
@@ -10,6 +11,9 @@ guard_access_fn! {
     pub get_frame_alloc(FRAME_ALLOCATOR: BootInfoFrameAllocator)
 }
 
+/// Frames below are never allocated, see `crate::smp`
+const LOW_MEMORY_END: u64 = 0x10_0000;
+
 type BootInfoFrameIter = Box<dyn Iterator<Item = PhysFrame> + Send>;
 
 /// A FrameAllocator that returns usable frames from the bootloader's memory map.
@@ -18,6 +22,10 @@ pub struct BootInfoFrameAllocator {
     used: usize,
     frames: BootInfoFrameIter,
     recycled: Vec<PhysFrame>,
+    /// extra owners of frames shared by copy-on-write mappings
+    ///
+    /// a frame not in this map has exactly one owner
+    shared: BTreeMap<PhysFrame, usize>,
 }
 
 impl BootInfoFrameAllocator {
@@ -32,6 +40,7 @@ impl BootInfoFrameAllocator {
             frames: create_frame_iter(memory_map),
             used: 0,
             recycled: Vec::new(),
+            shared: BTreeMap::new(),
         }
     }
 
@@ -46,6 +55,20 @@ impl BootInfoFrameAllocator {
     pub fn frames_recycled_count(&self) -> usize {
         self.recycled.len()
     }
+
+    pub fn frames_shared_count(&self) -> usize {
+        self.shared.len()
+    }
+
+    /// Add an owner to `frame`, it is recycled after the last owner deallocates it
+    pub fn share_frame(&mut self, frame: PhysFrame) {
+        *self.shared.entry(frame).or_insert(0) += 1;
+    }
+
+    /// Number of owners of an allocated `frame`
+    pub fn frame_ref_count(&self, frame: PhysFrame) -> usize {
+        self.shared.get(&frame).map_or(1, |extra| extra + 1)
+    }
 }
 
 unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
@@ -69,6 +92,15 @@ unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
 impl FrameDeallocator<Size4KiB> for BootInfoFrameAllocator {
     unsafe fn deallocate_frame(&mut self, _frame: PhysFrame) {
         // DONE: deallocate frame (not for lab 2)
+        // drop one owner of a shared frame, recycle it when it is the last one
+        if let Some(extra) = self.shared.get_mut(&_frame) {
+            *extra -= 1;
+            if *extra == 0 {
+                self.shared.remove(&_frame);
+            }
+            return;
+        }
+
         self.recycled.push(_frame);
     }
 }
@@ -82,7 +114,9 @@ fn create_frame_iter(memory_map: &MemoryMap) -> BootInfoFrameIter {
         // align to page boundary
         .flat_map(|r| (0..r.page_count).map(move |v| (v * 4096 + r.phys_start)))
         // create `PhysFrame` types from the start addresses
-        .map(|addr| PhysFrame::containing_address(PhysAddr::new(addr)));
+        .map(|addr| PhysFrame::containing_address(PhysAddr::new(addr)))
+        // keep low memory for the AP trampoline
+        .filter(|frame| frame.start_address().as_u64() >= LOW_MEMORY_END);
 
     Box::new(iter)
 }
@@ -1,4 +1,7 @@
+use alloc::boxed::Box;
+use core::alloc::Layout;
 use core::ptr::addr_of_mut;
+use core::sync::atomic::{AtomicU64, Ordering};
 use lazy_static::lazy_static;
 use x86_64::VirtAddr;
 use x86_64::registers::segmentation::Segment;
@@ -12,6 +15,11 @@ pub const CONTEXT_SWITCH_IST_INDEX: u16 = 0;
 
 pub const IST_SIZES: [usize; 4] = [0x1000, 0x1000, 0x4000, 0x1000];
 
+use crate::proc::processor::{MAX_CPU_COUNT, cpu_id};
+
+/// Top of the syscall stack of each CPU
+static SYSCALL_STACKS: [AtomicU64; MAX_CPU_COUNT] = [const { AtomicU64::new(0) }; MAX_CPU_COUNT];
+
 lazy_static! {
     static ref TSS: TaskStateSegment = {
         let mut tss = TaskStateSegment::new();
@@ -73,8 +81,9 @@ lazy_static! {
         let code_selector = gdt.append(Descriptor::kernel_code_segment());
         let data_selector = gdt.append(Descriptor::kernel_data_segment());
         let tss_selector = gdt.append(Descriptor::tss_segment(&TSS));
-        let user_code_selector = gdt.append(Descriptor::user_code_segment());
+        // NOTE: `sysret` requires the user data segment to precede user code
         let user_data_selector = gdt.append(Descriptor::user_data_segment());
+        let user_code_selector = gdt.append(Descriptor::user_code_segment());
         (
             gdt,
             KernelSelectors {
@@ -90,11 +99,11 @@ lazy_static! {
     };
 }
 
-#[derive(Debug)]
-struct KernelSelectors {
-    code_selector: SegmentSelector,
-    data_selector: SegmentSelector,
-    tss_selector: SegmentSelector,
+#[derive(Clone, Copy, Debug)]
+pub struct KernelSelectors {
+    pub code_selector: SegmentSelector,
+    pub data_selector: SegmentSelector,
+    pub tss_selector: SegmentSelector,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -104,20 +113,14 @@ pub struct UserSelectors {
 }
 
 pub fn init() {
-    use x86_64::PrivilegeLevel;
-    use x86_64::instructions::segmentation::{CS, DS, ES, FS, GS, SS};
-    use x86_64::instructions::tables::load_tss;
-
     GDT.0.load();
     unsafe {
-        CS::set_reg(GDT.1.code_selector);
-        DS::set_reg(GDT.1.data_selector);
-        SS::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
-        ES::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
-        FS::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
-        GS::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
-        load_tss(GDT.1.tss_selector);
+        load_segments(GDT.1);
     }
+    SYSCALL_STACKS[cpu_id()].store(
+        TSS.interrupt_stack_table[SYSCALL_IST_INDEX as usize].as_u64(),
+        Ordering::Relaxed,
+    );
 
     let mut size = 0;
 
@@ -134,3 +137,68 @@ pub fn init() {
 pub fn get_user_selector() -> UserSelectors {
     GDT.2
 }
+
+pub fn get_kernel_selector() -> KernelSelectors {
+    GDT.1
+}
+
+/// Load a GDT and TSS of its own for an application processor
+///
+/// The descriptors are appended in the same order as `GDT`,
+/// so the selectors are the same on every CPU.
+pub fn init_ap() {
+    let mut tss = TaskStateSegment::new();
+    tss.privilege_stack_table[0] = alloc_stack(IST_SIZES[0]);
+    tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = alloc_stack(IST_SIZES[1]);
+    tss.interrupt_stack_table[SYSCALL_IST_INDEX as usize] = alloc_stack(IST_SIZES[2]);
+    tss.interrupt_stack_table[PAGE_FAULT_IST_INDEX as usize] = alloc_stack(IST_SIZES[3]);
+    let tss: &'static TaskStateSegment = Box::leak(Box::new(tss));
+
+    let mut gdt = GlobalDescriptorTable::new();
+    let selectors = KernelSelectors {
+        code_selector: gdt.append(Descriptor::kernel_code_segment()),
+        data_selector: gdt.append(Descriptor::kernel_data_segment()),
+        tss_selector: gdt.append(Descriptor::tss_segment(tss)),
+    };
+    gdt.append(Descriptor::user_data_segment());
+    gdt.append(Descriptor::user_code_segment());
+    let gdt: &'static GlobalDescriptorTable = Box::leak(Box::new(gdt));
+
+    gdt.load();
+    unsafe {
+        load_segments(selectors);
+    }
+    SYSCALL_STACKS[cpu_id()].store(
+        tss.interrupt_stack_table[SYSCALL_IST_INDEX as usize].as_u64(),
+        Ordering::Relaxed,
+    );
+}
+
+unsafe fn load_segments(selectors: KernelSelectors) {
+    use x86_64::PrivilegeLevel;
+    use x86_64::instructions::segmentation::{CS, DS, ES, FS, GS, SS};
+    use x86_64::instructions::tables::load_tss;
+
+    unsafe {
+        CS::set_reg(selectors.code_selector);
+        DS::set_reg(selectors.data_selector);
+        SS::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
+        ES::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
+        FS::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
+        GS::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
+        load_tss(selectors.tss_selector);
+    }
+}
+
+/// Allocate a stack on the kernel heap, returns its top
+pub fn alloc_stack(size: usize) -> VirtAddr {
+    let layout = Layout::from_size_align(size, 16).unwrap();
+    let stack = unsafe { alloc::alloc::alloc_zeroed(layout) };
+    assert!(!stack.is_null(), "Failed to allocate stack");
+    VirtAddr::from_ptr(stack) + size as u64
+}
+
+/// Top of the stack used by system calls on the current CPU
+pub fn get_syscall_stack_top() -> VirtAddr {
+    VirtAddr::new(SYSCALL_STACKS[cpu_id()].load(Ordering::Relaxed))
+}
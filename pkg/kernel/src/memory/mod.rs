@@ -3,6 +3,7 @@ pub mod allocator;
 mod frames;
 
 pub mod gdt;
+pub mod uaccess;
 pub mod user;
 
 pub use address::*;
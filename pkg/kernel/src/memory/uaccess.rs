@@ -0,0 +1,183 @@
+//! Validated access to user space memory
+//!
+//! Every pointer that comes from a syscall argument must go through this
+//! module: the whole range is checked against the current page table before
+//! the kernel touches it, and a bad range is reported as `BadAddress`.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::{MaybeUninit, size_of};
+use syscall_def::{SyscallError, SyscallResult};
+use x86_64::VirtAddr;
+use x86_64::structures::paging::mapper::TranslateResult;
+use x86_64::structures::paging::*;
+
+use super::PAGE_SIZE;
+use crate::proc::{COW_FLAG, PageTableContext, get_process_manager};
+
+/// Upper bound (exclusive) of user space addresses
+pub const USER_SPACE_END: u64 = 0x0000_8000_0000_0000;
+
+/// Longest path or name accepted from user space
+pub const MAX_USER_STR_LEN: usize = 4096;
+
+/// Most strings accepted in a single vector (e.g. argv)
+pub const MAX_USER_STRS: usize = 64;
+
+/// Check that `[addr, addr + len)` is mapped and user accessible in the
+/// current page table, and writable when `write` is set.
+pub fn check_user_range(addr: usize, len: usize, write: bool) -> SyscallResult<()> {
+    if len == 0 {
+        return Ok(());
+    }
+
+    let start = addr as u64;
+    let end = start
+        .checked_add(len as u64 - 1)
+        .filter(|&end| end < USER_SPACE_END)
+        .ok_or(SyscallError::BadAddress)?;
+
+    let mapper = PageTableContext::new().mapper();
+
+    let mut page = start & !(PAGE_SIZE - 1);
+    while page <= end {
+        let mut translated = mapper.translate(VirtAddr::new(page));
+
+        // grow the stack now, just like the page fault handler would
+        if write
+            && matches!(translated, TranslateResult::NotMapped)
+            && get_process_manager().current().write().handle_page_fault(VirtAddr::new(page))
+        {
+            translated = mapper.translate(VirtAddr::new(page));
+        }
+
+        let mut flags = match translated {
+            TranslateResult::Mapped { flags, .. } => flags,
+            _ => {
+                warn!("uaccess: unmapped user address {:#x}", page.max(start));
+                return Err(SyscallError::BadAddress);
+            }
+        };
+
+        // copy the page now, the kernel writes to it without faulting
+        if write
+            && flags.contains(COW_FLAG)
+            && get_process_manager().current().write().handle_cow_fault(VirtAddr::new(page))
+        {
+            flags = (flags | PageTableFlags::WRITABLE) - COW_FLAG;
+        }
+
+        if !flags.contains(PageTableFlags::USER_ACCESSIBLE)
+            || (write && !flags.contains(PageTableFlags::WRITABLE))
+        {
+            warn!("uaccess: invalid access to {:#x} ({:?})", page.max(start), flags);
+            return Err(SyscallError::BadAddress);
+        }
+
+        page += PAGE_SIZE;
+    }
+
+    Ok(())
+}
+
+/// Copy `dst.len()` bytes from user address `src` into `dst`
+pub fn copy_from_user(dst: &mut [u8], src: usize) -> SyscallResult<()> {
+    check_user_range(src, dst.len(), false)?;
+
+    unsafe {
+        core::ptr::copy_nonoverlapping(src as *const u8, dst.as_mut_ptr(), dst.len());
+    }
+
+    Ok(())
+}
+
+/// Copy `src` into user memory starting at `dst`
+pub fn copy_to_user(dst: usize, src: &[u8]) -> SyscallResult<()> {
+    check_user_range(dst, src.len(), true)?;
+
+    unsafe {
+        core::ptr::copy_nonoverlapping(src.as_ptr(), dst as *mut u8, src.len());
+    }
+
+    Ok(())
+}
+
+/// Copy `len` bytes from user space into a new kernel buffer
+///
+/// Only for lengths bounded by the kernel, large buffers are copied in chunks.
+pub fn read_user_bytes(src: usize, len: usize) -> SyscallResult<Vec<u8>> {
+    // validate before allocating, so a bogus length can not exhaust the heap
+    check_user_range(src, len, false)?;
+
+    let mut buf = vec![0u8; len];
+    copy_from_user(&mut buf, src)?;
+    Ok(buf)
+}
+
+/// Types that are valid for any bit pattern and may be copied from user space
+///
+/// # Safety
+///
+/// The type must have no invalid values: no references, enums, `bool`,
+/// `char`, `NonZero*` or other types with a niche, at any depth.
+pub unsafe trait Pod: Copy {}
+
+unsafe impl Pod for u8 {}
+unsafe impl Pod for u16 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for u64 {}
+unsafe impl Pod for usize {}
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// Copy a `T` out of user space
+///
+/// The value is still untrusted, validate it before using it.
+pub fn read_user<T: Pod>(src: usize) -> SyscallResult<T> {
+    if src % align_of::<T>() != 0 {
+        return Err(SyscallError::BadAddress);
+    }
+
+    let mut value = MaybeUninit::<T>::uninit();
+    let dst = unsafe {
+        core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>())
+    };
+    copy_from_user(dst, src)?;
+
+    Ok(unsafe { value.assume_init() })
+}
+
+/// Copy a UTF-8 string of `len` bytes out of user space
+pub fn read_user_str(src: usize, len: usize) -> SyscallResult<String> {
+    if len > MAX_USER_STR_LEN {
+        return Err(SyscallError::NameTooLong);
+    }
+
+    let bytes = read_user_bytes(src, len)?;
+    String::from_utf8(bytes).map_err(|_| {
+        warn!("uaccess: invalid utf8 string at {:#x}", src);
+        SyscallError::InvalidArgument
+    })
+}
+
+/// Copy `count` strings passed as an array of `[ptr, len]` pairs
+pub fn read_user_strs(src: usize, count: usize) -> SyscallResult<Vec<String>> {
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+
+    if count > MAX_USER_STRS {
+        return Err(SyscallError::ArgListTooLong);
+    }
+
+    let pairs = read_user_bytes(src, count * 2 * size_of::<usize>())?;
+    pairs
+        .chunks_exact(2 * size_of::<usize>())
+        .map(|pair| {
+            let (ptr, len) = pair.split_at(size_of::<usize>());
+            let ptr = usize::from_ne_bytes(ptr.try_into().unwrap());
+            let len = usize::from_ne_bytes(len.try_into().unwrap());
+            read_user_str(ptr, len)
+        })
+        .collect()
+}
@@ -1,7 +1,11 @@
-use crate::proc::PageTableContext;
-use linked_list_allocator::LockedHeap;
+use alloc::sync::Arc;
+use core::{alloc::Layout, ptr::NonNull};
+use linked_list_allocator::Heap;
+use spin::Mutex;
 use x86_64::structures::paging::{
-    mapper::MapToError, FrameAllocator, Mapper, Page, PageTableFlags, Size4KiB,
+    mapper::{MapToError, UnmapError},
+    page::PageRangeInclusive,
+    FrameAllocator, FrameDeallocator, Mapper, Page, Size4KiB,
 };
 use x86_64::VirtAddr;
 
@@ -9,52 +13,126 @@ pub const USER_HEAP_START: usize = 0x4000_0000_0000;
 pub const USER_HEAP_SIZE: usize = 1024 * 1024; // 1 MiB
 const USER_HEAP_PAGE: usize = USER_HEAP_SIZE / crate::memory::PAGE_SIZE as usize;
 
-pub static USER_ALLOCATOR: LockedHeap = LockedHeap::empty();
+/// Heap behind the `sys_allocate` and `sys_deallocate` syscalls
+///
+/// Every address space has its own, shared by threads and vforked children.
+/// The pages are mapped on the first allocation, `fork` shares them copy on
+/// write like the rest of the address space.
+pub struct UserHeap {
+    allocator: Arc<Mutex<Heap>>,
+}
+
+impl UserHeap {
+    pub fn empty() -> Self {
+        Self {
+            allocator: Arc::new(Mutex::new(Heap::empty())),
+        }
+    }
+
+    /// Share the heap with a vforked child or a thread
+    pub fn vfork(&self) -> Self {
+        Self {
+            allocator: self.allocator.clone(),
+        }
+    }
+
+    /// Copy the allocator state for a forked child
+    ///
+    /// NOTE: the pages in `range` must be shared with the child, as the
+    ///       free list lives in the heap itself
+    pub fn fork(&self) -> Self {
+        let heap = self.allocator.lock();
+        // SAFETY: `Heap` only holds the bounds and pointers into the heap,
+        //         which the child maps at the same address with the same
+        //         content, the two copies never touch the other's memory
+        let heap = unsafe { core::ptr::read(&*heap) };
+
+        Self {
+            allocator: Arc::new(Mutex::new(heap)),
+        }
+    }
+
+    /// Mapped pages of the heap, `None` before the first allocation
+    pub fn range(&self) -> Option<PageRangeInclusive> {
+        if self.allocator.lock().size() == 0 {
+            return None;
+        }
+
+        let start = Page::containing_address(VirtAddr::new(USER_HEAP_START as u64));
+        Some(Page::range_inclusive(start, start + USER_HEAP_PAGE as u64 - 1))
+    }
+
+    /// Map the pages and set up the allocator, if not done yet
+    pub fn init(
+        &self,
+        mapper: &mut impl Mapper<Size4KiB>,
+        alloc: &mut impl FrameAllocator<Size4KiB>,
+    ) -> Result<(), MapToError<Size4KiB>> {
+        let mut heap = self.allocator.lock();
+        if heap.size() != 0 {
+            return Ok(());
+        }
+
+        let start = Page::containing_address(VirtAddr::new(USER_HEAP_START as u64));
+        elf::map_range(
+            Page::range_inclusive(start, start + USER_HEAP_PAGE as u64 - 1),
+            mapper,
+            alloc,
+            true,
+        )?;
 
-// NOTE: export mod user / call in the kernel init / after frame allocator
-pub fn init() {
-    init_user_heap().expect("User Heap Initialization Failed.");
-    info!("User Heap Initialized.");
+        debug!(
+            "User Heap        : 0x{:016x}-0x{:016x}",
+            USER_HEAP_START,
+            USER_HEAP_START + USER_HEAP_SIZE
+        );
+
+        unsafe { heap.init(USER_HEAP_START as *mut u8, USER_HEAP_SIZE) };
+
+        Ok(())
+    }
+
+    /// Allocate from the heap, which must be the one of the active page table
+    ///
+    /// The free list may sit in copy-on-write pages after a fork, the kernel
+    /// writes to them and the page fault handler copies them as needed.
+    pub fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        self.allocator.lock().allocate_first_fit(layout).ok()
+    }
+
+    /// Return a block to the heap, which must be the one of the active page table
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from `allocate` on this heap with the same `layout`
+    pub unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe { self.allocator.lock().deallocate(ptr, layout) };
+    }
+
+    pub fn clean_up(
+        &mut self,
+        mapper: &mut impl Mapper<Size4KiB>,
+        dealloc: &mut impl FrameDeallocator<Size4KiB>,
+    ) -> Result<(), UnmapError> {
+        let Some(range) = self.range() else {
+            return Ok(());
+        };
+
+        *self.allocator.lock() = Heap::empty();
+        elf::unmap_range(range, mapper, dealloc, true)
+    }
+
+    pub fn memory_usage(&self) -> u64 {
+        self.allocator.lock().size() as u64
+    }
 }
 
-pub fn init_user_heap() -> Result<(), MapToError<Size4KiB>> {
-    // Get current pagetable mapper
-    let mapper = &mut PageTableContext::new().mapper();
-    // Get global frame allocator
-    let frame_allocator = &mut *super::get_frame_alloc_for_sure();
-
-    // FIXME: use elf::map_range to allocate & map
-    //        frames (R/W/User Access)
-    let page_range = {
-        let heap_start = VirtAddr::new(USER_HEAP_START as u64);
-        let heap_start_page = Page::containing_address(heap_start);
-        let heap_end_page = heap_start_page + USER_HEAP_PAGE as u64 - 1u64;
-        Page::range(heap_start_page, heap_end_page)
-    };
-
-    debug!(
-        "User Heap        : 0x{:016x}-0x{:016x}",
-        page_range.start.start_address().as_u64(),
-        page_range.end.start_address().as_u64()
-    );
-
-    let (size, unit) = crate::humanized_size(USER_HEAP_SIZE as u64);
-    info!("User Heap Size   : {:>7.*} {}", 3, size, unit);
-
-    for page in page_range {
-        let frame = frame_allocator
-            .allocate_frame()
-            .ok_or(MapToError::FrameAllocationFailed)?;
-        let flags =
-            PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
-        unsafe { mapper.map_to(page, frame, flags, frame_allocator)?.flush() };
-    }
-
-    unsafe {
-        USER_ALLOCATOR
-            .lock()
-            .init(USER_HEAP_START as *mut u8, USER_HEAP_SIZE);
-    }
-
-    Ok(())
+impl core::fmt::Debug for UserHeap {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let heap = self.allocator.lock();
+        f.debug_struct("UserHeap")
+            .field("size", &heap.size())
+            .field("used", &heap.used())
+            .finish()
+    }
 }
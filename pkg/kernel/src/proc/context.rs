@@ -2,7 +2,7 @@ use volatile::{access::ReadOnly, VolatileRef};
 use x86_64::{registers::rflags::RFlags, structures::idt::InterruptStackFrameValue, VirtAddr};
 use x86_64::structures::gdt::SegmentSelector;
 
-use crate::{memory::gdt::get_user_selector, RegistersValue};
+use crate::{memory::gdt::{get_kernel_selector, get_user_selector}, RegistersValue};
 
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -56,6 +56,69 @@ impl ProcessContext {
         trace!("Init stack frame: {:#?}", &self.stack_frame);
     }
 
+    /// Run `entry` in ring 0 on `stack_top`, e.g. the idle loop of a CPU
+    pub fn init_kernel(&mut self, entry: VirtAddr, stack_top: VirtAddr) {
+        self.value = ProcessContextValue::default();
+        self.value.stack_frame.stack_pointer = stack_top;
+        self.value.stack_frame.instruction_pointer = entry;
+        self.value.stack_frame.cpu_flags = RFlags::INTERRUPT_FLAG;
+
+        let selector = get_kernel_selector();
+        self.value.stack_frame.code_segment = selector.code_selector;
+        self.value.stack_frame.stack_segment = selector.data_selector;
+    }
+
+    /// Pass argv and envp to `_start(argc, argv, envc, envp)`
+    pub fn init_args(&mut self, args: &super::ProcessArgs) {
+        self.value.regs.rdi = args.argc;
+        self.value.regs.rsi = args.argv.as_u64() as usize;
+        self.value.regs.rdx = args.envc;
+        self.value.regs.rcx = args.envp.as_u64() as usize;
+    }
+
+    /// Start a thread at `entry(arg)`
+    pub fn init_thread(&mut self, entry: VirtAddr, stack_top: VirtAddr, arg: usize) {
+        self.init_stack_frame(entry, stack_top);
+        self.value.regs.rdi = arg;
+    }
+
+    /// Continue at the signal handler `entry(arg)` on `stack`
+    pub fn enter_handler(&mut self, entry: VirtAddr, stack: VirtAddr, arg: usize) {
+        self.value.stack_frame.instruction_pointer = entry;
+        self.value.stack_frame.stack_pointer = stack;
+        self.value.regs.rdi = arg;
+    }
+
+    /// Force user segments and enable interrupts, e.g. for a context from user space
+    pub fn set_user_segments(&mut self, code: SegmentSelector, data: SegmentSelector) {
+        self.value.stack_frame.code_segment = code;
+        self.value.stack_frame.stack_segment = data;
+        self.value.stack_frame.cpu_flags |= RFlags::INTERRUPT_FLAG;
+    }
+
+    /// Keep the flags user code may change, take the rest from `current`
+    ///
+    /// Only the arithmetic flags and DF, so that a context written by user
+    /// space can not raise IOPL, set TF or clear IF.
+    pub fn set_user_flags(&mut self, current: RFlags) {
+        let user = RFlags::CARRY_FLAG
+            | RFlags::PARITY_FLAG
+            | RFlags::AUXILIARY_CARRY_FLAG
+            | RFlags::ZERO_FLAG
+            | RFlags::SIGN_FLAG
+            | RFlags::DIRECTION_FLAG
+            | RFlags::OVERFLOW_FLAG;
+        let flags = self.value.stack_frame.cpu_flags;
+        self.value.stack_frame.cpu_flags =
+            (flags & user) | (current - user) | RFlags::INTERRUPT_FLAG;
+    }
+
+    /// Whether this context returns to ring 3
+    #[inline]
+    pub fn is_user_mode(&self) -> bool {
+        self.value.stack_frame.code_segment.rpl() == x86_64::PrivilegeLevel::Ring3
+    }
+
     pub fn set_stack_offset(&mut self, offset: u64) {
         self.value.stack_frame.stack_pointer += offset;
     }
@@ -1,6 +1,7 @@
 use alloc::{collections::BTreeMap, sync::Arc};
 use spin::RwLock;
 use crate::{resource::ResourceSet, Resource};
+use syscall_def::SyscallResult;
 use super::*;
 
 #[derive(Debug, Clone)]
@@ -26,6 +27,19 @@ impl ProcessData {
         Self::default()
     }
 
+    /// Data for a child created by `fork`
+    ///
+    /// The environment and the fd table are copied, so changes made by one
+    /// process are not seen by the other. Open resources and semaphores
+    /// are still shared with the parent.
+    pub fn fork(&self) -> Self {
+        Self {
+            env: Arc::new(RwLock::new(self.env.read().clone())),
+            resources: Arc::new(RwLock::new(self.resources.read().clone())),
+            semaphores: self.semaphores.clone(),
+        }
+    }
+
     pub fn env(&self, key: &str) -> Option<String> {
         self.env.read().get(key).cloned()
     }
@@ -34,17 +48,29 @@ impl ProcessData {
         self.env.write().insert(key.into(), val.into());
     }
 
-    pub fn read(&self, fd: u8, buf: &mut [u8]) -> isize {
+    /// Replace the environment with `KEY=VALUE` strings
+    ///
+    /// A new map is used, processes sharing the old one are not affected.
+    pub fn set_envs(&mut self, envs: &[String]) {
+        let env = envs
+            .iter()
+            .filter_map(|env| env.split_once('='))
+            .map(|(key, val)| (key.into(), val.into()))
+            .collect();
+        self.env = Arc::new(RwLock::new(env));
+    }
+
+    pub fn read(&self, fd: u8, buf: &mut [u8]) -> SyscallResult {
         self.resources.read().read(fd, buf)
     }
 
-    pub fn write(&self, fd: u8, buf: &[u8]) -> isize {
+    pub fn write(&self, fd: u8, buf: &[u8]) -> SyscallResult {
         self.resources.read().write(fd, buf)
     }
 
     /// Opens a resource and adds it to the process's resource set.
-    /// Returns the file descriptor.
-    pub fn open_resource(&self, resource: Resource) -> u8 {
+    /// Returns the file descriptor, or `TooManyFiles` if none is free.
+    pub fn open_resource(&self, resource: Resource) -> SyscallResult<u8> {
         self.resources.write().open(resource)
     }
 
@@ -54,6 +80,16 @@ impl ProcessData {
         self.resources.write().close(fd)
     }
 
+    /// Open file descriptors and what they refer to
+    pub fn fds(&self) -> Vec<(u8, String)> {
+        self.resources
+            .read()
+            .handles
+            .iter()
+            .map(|(fd, res)| (*fd, res.lock().to_string()))
+            .collect()
+    }
+
     #[inline]
     pub fn sem_new(&mut self, key: u32, value: usize) -> bool {
         self.semaphores.write().insert(key, value)
@@ -67,6 +103,10 @@ impl ProcessData {
         self.semaphores.read().wait(key, pid)
     }
     #[inline]
+    pub fn sem_cancel(&self, pid: ProcessId) {
+        self.semaphores.read().cancel(pid)
+    }
+    #[inline]
     pub fn sem_signal(&self, key: u32) -> SemaphoreResult {
         self.semaphores.read().signal(key)
     }
@@ -1,16 +1,15 @@
 use super::*;
-use alloc::{format, collections::BTreeMap, collections::BTreeSet, collections::VecDeque, sync::Weak};
+use alloc::{boxed::Box, collections::BTreeMap, collections::BTreeSet, sync::Weak};
 use spin::{Mutex, RwLock};
-use crate::memory::{PAGE_SIZE, get_frame_alloc_for_sure, FRAME_ALLOCATOR}; // 确保导入 FRAME_ALLOCATOR 和 PAGE_SIZE
-use crate::humanized_size; // 确保导入 humanized_size 函数
+use syscall_def::proc::ProcInfo;
 
 pub static PROCESS_MANAGER: spin::Once<ProcessManager> = spin::Once::new();
 
-pub fn init(init: Arc<Process>, app_list: boot::AppListRef) {
+pub fn init(init: Arc<Process>, app_list: boot::AppListRef, scheduler: Box<dyn Scheduler>) {
     // FIXME: set init process as Running
     // FIXME: set processor's current pid to init's pid
     processor::set_pid(init.pid());
-    PROCESS_MANAGER.call_once(|| ProcessManager::new(init, app_list));
+    PROCESS_MANAGER.call_once(|| ProcessManager::new(init, app_list, scheduler));
 }
 
 pub fn get_process_manager() -> &'static ProcessManager {
@@ -19,44 +18,27 @@ pub fn get_process_manager() -> &'static ProcessManager {
         .expect("Process Manager has not been initialized")
 }
 
-fn format_system_memory_usage(name: &str, used_bytes: u64, total_bytes: u64) -> String {
-    if total_bytes == 0 {
-        return format!(
-            "{:<6} : {:>6} {:>3} / {:>6} {:>3} (  N/A  %)\n", // 中文冒号
-            name,
-            humanized_size(used_bytes as u64).0,
-            humanized_size(used_bytes as u64).1,
-            humanized_size(total_bytes as u64).0,
-            humanized_size(total_bytes as u64).1
-        );
-    }
-    let (used_float, used_unit) = humanized_size(used_bytes as u64);
-    let (total_float, total_unit) = humanized_size(total_bytes as u64);
-
-    format!(
-        "{:<6} ：{:>6.*} {:>3} / {:>6.*} {:>3} ({:>5.2}%)\n", // 中文冒号
-        name,
-        2, // 小数点后两位
-        used_float,
-        used_unit,
-        2, // 小数点后两位
-        total_float,
-        total_unit,
-        used_bytes as f32 / total_bytes as f32 * 100.0
-    )
+/// What a blocked process is waiting for in `wait_pid`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WaitFor {
+    /// the process with this pid
+    Pid(ProcessId),
+    /// any child of the process with this pid
+    ChildOf(ProcessId),
 }
 
 pub struct ProcessManager {
     processes: RwLock<BTreeMap<ProcessId, Arc<Process>>>,
-    ready_queue: Mutex<VecDeque<ProcessId>>,
+    scheduler: Mutex<Box<dyn Scheduler>>,
     app_list: boot::AppListRef,
-    wait_queue: Mutex<BTreeMap<ProcessId, BTreeSet<ProcessId>>>,
+    wait_queue: Mutex<BTreeMap<WaitFor, BTreeSet<ProcessId>>>,
+    /// sleeping processes ordered by the tick to wake up at
+    sleep_queue: Mutex<BTreeSet<(u64, ProcessId)>>,
 }
 
 impl ProcessManager {
-    pub fn new(init: Arc<Process>, app_list: boot::AppListRef) -> Self {
+    pub fn new(init: Arc<Process>, app_list: boot::AppListRef, scheduler: Box<dyn Scheduler>) -> Self {
         let mut processes = BTreeMap::new();
-        let ready_queue = VecDeque::new();
         let pid = init.pid();
 
         trace!("Init {:#?}", init);
@@ -64,9 +46,10 @@ impl ProcessManager {
         processes.insert(pid, init);
         Self {
             processes: RwLock::new(processes),
-            ready_queue: Mutex::new(ready_queue),
+            scheduler: Mutex::new(scheduler),
             app_list: app_list,
             wait_queue: Mutex::new(BTreeMap::new()),
+            sleep_queue: Mutex::new(BTreeSet::new()),
         }
     }
     #[inline]
@@ -74,9 +57,11 @@ impl ProcessManager {
         self.app_list
     }
 
-    #[inline]
     pub fn push_ready(&self, pid: ProcessId) {
-        self.ready_queue.lock().push_back(pid);
+        if let Some(proc) = self.get_proc(&pid) {
+            let mut inner = proc.write();
+            self.scheduler.lock().push(pid, inner.sched_mut());
+        }
     }
 
     #[inline]
@@ -89,8 +74,29 @@ impl ProcessManager {
         self.processes.read().get(pid).cloned()
     }
 
-    pub(super) fn get_exit_code(&self, pid: ProcessId) -> Option<isize> {
-        self.get_proc(&pid).and_then(|p| p.read().exit_code())
+    pub(super) fn get_exit_status(&self, pid: ProcessId) -> Option<ExitStatus> {
+        self.get_proc(&pid).and_then(|p| p.read().exit_status())
+    }
+
+    /// Find a process matching `target` that has exited
+    pub(super) fn try_wait(&self, target: WaitFor) -> SyscallResult<Option<(ProcessId, ExitStatus)>> {
+        match target {
+            WaitFor::Pid(pid) => {
+                let proc = self.get_proc(&pid).ok_or(SyscallError::NoSuchProcess)?;
+                Ok(proc.read().exit_status().map(|status| (pid, status)))
+            }
+            WaitFor::ChildOf(parent) => {
+                let parent = self.get_proc(&parent).ok_or(SyscallError::NoSuchProcess)?;
+                let parent = parent.read();
+                if parent.children().is_empty() {
+                    return Err(SyscallError::NoChild);
+                }
+                Ok(parent
+                    .children()
+                    .iter()
+                    .find_map(|c| c.read().exit_status().map(|status| (c.pid(), status))))
+            }
+        }
     }
 
     pub fn current(&self) -> Arc<Process> {
@@ -98,19 +104,41 @@ impl ProcessManager {
             .expect("No current process")
     }
 
-    pub fn wait_pid(&self, pid: ProcessId) {
+    pub fn wait_for(&self, target: WaitFor) {
         let mut wait_queue = self.wait_queue.lock();
-        let entry = wait_queue.entry(pid).or_default();
+        let entry = wait_queue.entry(target).or_default();
         entry.insert(processor::get_pid());
     }
 
+    /// Wake the current process up at tick `deadline`
+    pub fn sleep_until(&self, deadline: u64) {
+        self.sleep_queue.lock().insert((deadline, processor::get_pid()));
+    }
+
+    /// Wake up sleeping processes whose deadline has passed
+    pub fn wake_sleepers(&self, now: u64) {
+        loop {
+            let pid = {
+                let mut sleep_queue = self.sleep_queue.lock();
+                match sleep_queue.first() {
+                    Some(&(deadline, pid)) if deadline <= now => {
+                        sleep_queue.pop_first();
+                        pid
+                    }
+                    _ => break,
+                }
+            };
+            self.wake_up(pid, Some(0));
+        }
+    }
+
     #[inline]
-    pub fn read(&self, fd: u8, buf: &mut [u8]) -> isize {
+    pub fn read(&self, fd: u8, buf: &mut [u8]) -> SyscallResult {
         self.current().read().read(fd, buf)
     }
 
     #[inline]
-    pub fn write(&self, fd: u8, buf: &[u8]) -> isize {
+    pub fn write(&self, fd: u8, buf: &[u8]) -> SyscallResult {
         self.current().read().write(fd, buf)
     }
 
@@ -125,38 +153,85 @@ impl ProcessManager {
         let pid = cur.pid();
         {
             let mut cur_w = cur.write();
-            cur_w.tick();
             cur_w.save(context);
         }
         pid
     }
 
-    pub fn switch_next(&self, context: &mut ProcessContext) -> ProcessId {
-        let mut pid = processor::get_pid();
-        let mut ready_q = self.ready_queue.lock();
-        while let Some(next) = ready_q.pop_front() {
-            let proc = {
-                let map = self.processes.read();
-                map.get(&next).expect("Process not found").clone()
+    /// Account a timer tick to the current process,
+    /// returns whether it should be preempted
+    pub fn tick(&self, user: bool) -> bool {
+        let cur = self.current();
+        let mut cur_w = cur.write();
+        cur_w.tick(user);
+        self.scheduler.lock().tick(cur_w.sched_mut())
+    }
+
+    /// Switch `context` to the next ready process, the CPU idles if there is none
+    ///
+    /// TLB flushes only reach the current CPU, so an address space runs on
+    /// one CPU at a time: a process whose page table is in use on another
+    /// CPU is skipped and queued again. Loading the page table on a switch
+    /// flushes whatever this CPU still caches of it.
+    pub fn switch_next(&self, context: &mut ProcessContext) -> Option<ProcessId> {
+        let pid = processor::current_pid();
+        let mut deferred = Vec::new();
+        let mut found = None;
+        while let Some(next) = self.scheduler.lock().pop() {
+            // the process may have been reaped already
+            let Some(proc) = self.get_proc(&next) else {
+                continue;
             };
             if !proc.read().is_ready() {
                 debug!("Process #{} is {:?}", next, proc.read().status());
                 continue;
             }
-            if pid != next {
+            if pid != Some(next) && self.vm_used_elsewhere(&proc) {
+                deferred.push(next);
+                continue;
+            }
+            if pid != Some(next) {
                 proc.write().restore(context);
                 processor::set_pid(next);
-                pid = next;
+            } else {
+                proc.write().resume();
             }
+            found = Some(next);
             break;
         }
-        pid
+
+        for pid in deferred {
+            self.push_ready(pid);
+        }
+        if found.is_some() {
+            return found;
+        }
+
+        // the current process is blocked or dead, and others run elsewhere
+        processor::idle_context(context);
+        self.load_kernel_page_table();
+        None
+    }
+
+    /// Whether another CPU runs a process sharing the page table of `proc`
+    fn vm_used_elsewhere(&self, proc: &Process) -> bool {
+        let inner = proc.read();
+        processor::running_elsewhere()
+            .filter_map(|pid| self.get_proc(&pid))
+            .any(|other| other.read().shares_vm_with(&inner))
+    }
+
+    /// Leave the page table of the current process, e.g. before it is freed
+    fn load_kernel_page_table(&self) {
+        self.get_proc(&KERNEL_PID).unwrap().read().vm().page_table.load();
     }
 
     pub fn spawn(
         &self,
         elf: &ElfFile,
         name: String,
+        args: &[String],
+        envs: &[String],
         parent: Option<Weak<Process>>,
         proc_data: Option<ProcessData>,
     ) -> ProcessId {
@@ -168,20 +243,53 @@ impl ProcessManager {
             let mut proc_w = proc.write();
             proc_w.pause();
             proc_w.load_elf(elf);
-            proc_w.init_stack_frame(
-                VirtAddr::new_truncate(elf.header.pt2.entry_point()),
-                VirtAddr::new_truncate(super::stack::STACK_INIT_TOP),
-            );
+            let args = proc_w.vm().init_args(args, envs);
+            proc_w.init_stack_frame(VirtAddr::new_truncate(elf.header.pt2.entry_point()), &args);
         }
         trace!("New {:#?}", &proc);
+        if let Some(parent) = proc.read().parent() {
+            parent.write().add_child(proc.clone());
+        }
         let pid = proc.pid();
         self.add_proc(pid, proc);
         self.push_ready(pid);
         pid
     }
 
-    pub fn kill_self(&self, ret: isize) {
-        self.kill(processor::get_pid(), ret);
+    /// Load `elf` into the current process, see `proc::exec`
+    pub fn exec(
+        &self,
+        elf: &ElfFile,
+        name: String,
+        args: &[String],
+        envs: &[String],
+        context: &mut ProcessContext,
+    ) {
+        let kproc = self.get_proc(&KERNEL_PID).unwrap();
+        let page_table = kproc.read().clone_page_table();
+        let proc = self.current();
+        let mut proc_w = proc.write();
+        proc_w.exec(elf, name, page_table, args, envs);
+        proc_w.set_envs(envs);
+        proc_w.restore(context);
+        trace!("Exec {:#?}", &proc);
+    }
+
+    /// Change the nice value of `pid`, it applies the next time it is queued
+    pub fn set_priority(&self, pid: ProcessId, nice: isize) -> SyscallResult<isize> {
+        let proc = self.get_proc(&pid).ok_or(SyscallError::NoSuchProcess)?;
+        let mut inner = proc.write();
+        if inner.status() == ProgramStatus::Dead {
+            return Err(SyscallError::NoSuchProcess);
+        }
+
+        let sched = inner.sched_mut();
+        sched.nice = clamp_nice(nice);
+        Ok(sched.nice)
+    }
+
+    pub fn kill_self(&self, status: ExitStatus) {
+        self.kill(processor::get_pid(), status);
     }
 
     pub fn handle_page_fault(&self, addr: VirtAddr, err_code: PageFaultErrorCode) -> bool {
@@ -199,76 +307,155 @@ impl ProcessManager {
 
             let mut inner = cur_proc.write();
             inner.handle_page_fault(addr)
+        } else if err_code.contains(PageFaultErrorCode::CAUSED_BY_WRITE) {
+            // write to a page shared by `fork`
+            self.current().write().handle_cow_fault(addr)
         } else {
             false
         }
     }
 
-    pub fn kill(&self, pid: ProcessId, ret: isize) {
-        match self.get_proc(&pid) {
-            Some(proc) => {
-                trace!("Kill {:#?}", &proc);
-                proc.kill(ret);
-                if let Some(waiters) = self.wait_queue.lock().remove(&pid) {
-                    for waiter in waiters {
-                        self.wake_up(waiter, Some(ret));
-                    }
+    pub fn kill(&self, pid: ProcessId, status: ExitStatus) {
+        let Some(proc) = self.get_proc(&pid) else {
+            warn!("Process #{} not found.", pid);
+            return;
+        };
+        if proc.read().status() == ProgramStatus::Dead {
+            return;
+        }
+
+        trace!("Kill {:#?}", &proc);
+        if processor::current_pid() == Some(pid) {
+            self.load_kernel_page_table();
+        }
+        let parent = proc.read().parent();
+        let orphans = proc.kill(status);
+        self.adopt(orphans);
+
+        if let Some(parent) = &parent {
+            parent.write().signals_mut().raise(Signal::Chld);
+        }
+
+        let waiters = {
+            let mut wait_queue = self.wait_queue.lock();
+            let mut waiters = wait_queue.remove(&WaitFor::Pid(pid)).unwrap_or_default();
+            if let Some(parent) = &parent {
+                if let Some(any) = wait_queue.remove(&WaitFor::ChildOf(parent.pid())) {
+                    waiters.extend(any);
                 }
             }
-            None => {
-                warn!("Process #{} not found.", pid);
-            }
+            waiters
+        };
+        let ret = encode_wait(pid.0, status);
+        for &waiter in &waiters {
+            self.wake_up(waiter, Some(ret));
+        }
+
+        // nobody else will collect the exit status
+        if !waiters.is_empty() || parent.is_none() {
+            self.reap(pid);
+        }
+    }
+
+    /// Reparent `orphans` to the init process
+    fn adopt(&self, orphans: Vec<Arc<Process>>) {
+        if orphans.is_empty() {
+            return;
+        }
+
+        let init = self.get_proc(&KERNEL_PID).unwrap();
+        let weak = Arc::downgrade(&init);
+        let mut init_w = init.write();
+        for orphan in orphans {
+            debug!("Process #{} adopted by init", orphan.pid());
+            orphan.write().set_parent(weak.clone());
+            init_w.add_child(orphan);
+        }
+    }
+
+    /// Remove the zombie `pid` once its exit code has been collected
+    pub fn reap(&self, pid: ProcessId) {
+        let Some(proc) = self.processes.write().remove(&pid) else {
+            return;
+        };
+        trace!("Reap {:#?}", &proc);
+
+        if let Some(parent) = proc.read().parent() {
+            parent.write().remove_child(pid);
+        }
+    }
+
+    /// Reap zombie children of the init process, except `keep`
+    pub fn reap_orphans(&self, keep: ProcessId) {
+        let zombies: Vec<ProcessId> = self
+            .get_proc(&KERNEL_PID)
+            .unwrap()
+            .read()
+            .children()
+            .iter()
+            .filter(|c| c.pid() != keep && c.read().exit_status().is_some())
+            .map(|c| c.pid())
+            .collect();
+
+        for pid in zombies {
+            self.reap(pid);
         }
     }
 
     pub fn vfork(&self) {
         let child = self.current().vfork();
+        self.add_forked(child);
+    }
+
+    pub fn fork(&self) {
+        let child = self.current().fork();
+        self.add_forked(child);
+    }
+
+    pub fn thread_create(&self, entry: VirtAddr, arg: usize) -> ProcessId {
+        let child = self.current().thread(entry, arg);
+        let tid = child.pid();
+        self.add_forked(child);
+        tid
+    }
+
+    /// Whether `pid` runs in the address space of the current process
+    pub fn is_thread(&self, pid: ProcessId) -> bool {
+        let Some(proc) = self.get_proc(&pid) else {
+            return false;
+        };
+        let current = self.current();
+        if Arc::ptr_eq(&proc, &current) {
+            return false;
+        }
+
+        proc.read().shares_vm_with(&current.read())
+    }
+
+    fn add_forked(&self, child: Arc<Process>) {
         let pid = child.pid();
         self.add_proc(pid, child);
         self.push_ready(pid);
-        debug!("Current queue: {:?}", self.ready_queue.lock());
+        debug!("Current queue: {:?}", self.scheduler.lock());
     }
 
-    pub fn wake_up(&self, pid: ProcessId, ret: Option<isize>) {
+    pub fn wake_up(&self, pid: ProcessId, ret: Option<usize>) {
        if let Some(proc) = self.get_proc(&pid) {
             let mut inner = proc.write();
+            if inner.status() == ProgramStatus::Dead {
+                return;
+            }
             if let Some(ret) = ret {
-                inner.set_return(ret as usize);
+                inner.set_return(ret);
             }
             inner.pause();
+            drop(inner);
             self.push_ready(pid);
        } 
     }
 
-    pub fn print_process_list(&self) {
-        let mut output = String::from("  PID | PPID | ProcesName       | MemoryUsage |  Ticks  | Status\n"); // 修改表头为中文，并调整列名
-
-        self.processes
-            .read()
-            .values()
-            .filter(|p| p.read().status() != ProgramStatus::Dead)
-            .for_each(|p| output += &format!("{}\n", p));
-
-        if let Some(alloc_mutex) = FRAME_ALLOCATOR.get() {
-            let alloc = alloc_mutex.lock();
-            let frames_used = alloc.frames_used();
-            let frames_recycled = alloc.frames_recycled_count();
-            let frames_total = alloc.frames_total();
-
-            // (已用帧 - 已回收帧) * 页大小
-            let active_system_frames = frames_used.saturating_sub(frames_recycled);
-            let used_mem_bytes = active_system_frames as u64 * PAGE_SIZE;
-            let total_mem_bytes = frames_total as u64 * PAGE_SIZE;
-            
-            output += &format_system_memory_usage("SystemMemory", used_mem_bytes, total_mem_bytes);
-        } else {
-            output += "SystemMemory: frame allocator do not init\n";
-        }
-
-        output += &format!("ready_queue：{:?}\n", self.ready_queue.lock()); // 中文标签
-
-        output += &processor::print_processors(); // 假设这个函数存在
-
-        print!("{}", output); // 最终打印
+    /// Records of every process, ordered by pid
+    pub fn proc_info(&self) -> Vec<ProcInfo> {
+        self.processes.read().values().map(|p| p.info()).collect()
     }
 }
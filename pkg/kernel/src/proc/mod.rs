@@ -4,20 +4,26 @@ mod manager;
 mod paging;
 mod pid;
 mod process;
-mod processor;
+pub mod processor;
+mod sched;
+mod signal;
 mod vm;
 mod sync;
 
 use alloc::sync::Arc;
+use core::{alloc::Layout, ptr::NonNull};
 use alloc::vec::Vec;
 use manager::*;
 use process::*;
+use sched::*;
+use signal::*;
 use sync::*;
 
 pub use context::ProcessContext;
 pub use data::ProcessData;
 pub use paging::PageTableContext;
 pub use pid::ProcessId;
+pub use signal::SigAction;
 pub use vm::*;
 pub use manager::get_process_manager;
 use xmas_elf::ElfFile;
@@ -25,6 +31,17 @@ use xmas_elf::ElfFile;
 use alloc::string::{String, ToString};
 use x86_64::VirtAddr;
 use x86_64::structures::idt::PageFaultErrorCode;
+use syscall_def::{SyscallError, SyscallResult};
+use syscall_def::signal::{SIG_IGN, SigSet, Signal};
+use syscall_def::proc::ProcInfo;
+use syscall_def::sched::*;
+use syscall_def::wait::*;
+use storage::FileSystem;
+
+use crate::drivers::filesystem;
+use crate::memory::uaccess::USER_SPACE_END;
+use crate::utils::clock;
+use core::time::Duration;
 
 pub const KERNEL_PID: ProcessId = ProcessId(1);
 
@@ -46,108 +63,430 @@ pub fn init(boot_info: &'static boot::BootInfo) {
     let kproc = Process::new(String::from("kernel"), None, Some(proc_vm), None);
 
     kproc.write().resume();
+    // the kernel only idles, run it when nothing else is ready
+    kproc.write().sched_mut().nice = NICE_MAX;
     let app_list = boot_info.load_apps.as_ref();
-    manager::init(kproc, app_list);
+    let scheduler = new_scheduler(boot_info.sched_policy);
+    info!("Scheduler: {}", scheduler.name());
+    manager::init(kproc, app_list, scheduler);
 
     info!("Process Manager Initialized.");
 }
 
 pub fn switch(context: &mut ProcessContext) {
-    x86_64::instructions::interrupts::without_interrupts(|| {
+    processor::exclusive(|| {
+        let manager = get_process_manager();
+        // an idle CPU has nothing to save
+        if processor::current_pid().is_some() {
+            if !manager.tick(context.is_user_mode()) {
+                return;
+            }
+            let pid = manager.save_current(context);
+            manager.push_ready(pid);
+        }
+        manager.switch_next(context);
+    });
+}
+
+/// Give up the CPU, the current process is queued again at once
+pub fn yield_now(context: &mut ProcessContext) {
+    processor::exclusive(|| {
         let manager = get_process_manager();
         let pid = manager.save_current(context);
         manager.push_ready(pid);
         manager.switch_next(context);
-    });
+    })
+}
+
+/// Block the current process for at least `duration`
+pub fn sleep(duration: Duration, context: &mut ProcessContext) {
+    context.set_rax(0);
+    if duration.is_zero() {
+        return;
+    }
+
+    processor::exclusive(|| {
+        let manager = get_process_manager();
+        manager.sleep_until(clock::ticks() + clock::ticks_for(duration));
+        manager.save_current(context);
+        manager.current().write().block();
+        manager.switch_next(context);
+    })
 }
 
-pub fn print_process_list() {
-    x86_64::instructions::interrupts::without_interrupts(|| {
-        get_process_manager().print_process_list();
+/// Called by the timer interrupt
+pub fn wake_sleepers(now: u64) {
+    processor::exclusive(|| {
+        get_process_manager().wake_sleepers(now);
     })
 }
 
+/// Set the nice value of `pid`, or the current process if `pid` is 0
+pub fn set_priority(pid: ProcessId, nice: isize) -> SyscallResult<isize> {
+    if pid == KERNEL_PID {
+        return Err(SyscallError::PermissionDenied);
+    }
+
+    processor::exclusive(|| {
+        let manager = get_process_manager();
+        let pid = if pid.0 == 0 { processor::get_pid() } else { pid };
+        manager.set_priority(pid, nice)
+    })
+}
+
+/// Add `inc` to the nice value of the current process
+pub fn nice(inc: isize) -> SyscallResult<isize> {
+    processor::exclusive(|| {
+        let manager = get_process_manager();
+        let nice = manager.current().read().sched().nice;
+        manager.set_priority(processor::get_pid(), nice.saturating_add(inc))
+    })
+}
+
+/// Records of every process, see `syscall_def::proc`
+pub fn proc_info() -> Vec<ProcInfo> {
+    processor::exclusive(|| get_process_manager().proc_info())
+}
+
 pub fn env(key: &str) -> Option<String> {
-    x86_64::instructions::interrupts::without_interrupts(|| {
+    processor::exclusive(|| {
         get_process_manager().current().read().env(key)
     })
 }
 
 pub fn process_exit(ret: isize, context: &mut ProcessContext) {
-    x86_64::instructions::interrupts::without_interrupts(|| {
+    processor::exclusive(|| {
         let manager = get_process_manager();
         //DONE: implement process exit
-        manager.kill_self(ret);
+        manager.kill_self(ExitStatus::Exited(ret));
         manager.switch_next(context);
     })
 }
 
-pub fn wait_pid(pid: ProcessId, context: &mut ProcessContext) {
-    x86_64::instructions::interrupts::without_interrupts(|| {
+/// Wait for `pid` to exit, or any child with `WAIT_ANY`
+///
+/// The reaped pid and its status are returned as in `syscall_def::wait`,
+/// or 0 with `WNOHANG` if nothing has exited yet.
+pub fn wait_pid(pid: isize, options: usize, context: &mut ProcessContext) {
+    processor::exclusive(|| {
         let manager = get_process_manager();
-        if let Some(ret) = manager.get_exit_code(pid) {
-            context.set_rax(ret as usize);
-        } else {
-            manager.wait_pid(pid);
-            manager.save_current(context);
-            manager.current().write().block();
-            manager.switch_next(context);
+        let target = match pid {
+            WAIT_ANY => WaitFor::ChildOf(processor::get_pid()),
+            0..=0xffff => WaitFor::Pid(ProcessId(pid as u16)),
+            _ => {
+                context.set_rax(SyscallError::InvalidArgument.encode());
+                return;
+            }
+        };
+
+        match manager.try_wait(target) {
+            Err(err) => context.set_rax(err.encode()),
+            Ok(Some((pid, status))) => {
+                context.set_rax(encode_wait(pid.0, status));
+                manager.reap(pid);
+            }
+            Ok(None) if options & WNOHANG != 0 => context.set_rax(0),
+            Ok(None) => {
+                manager.wait_for(target);
+                manager.save_current(context);
+                manager.current().write().block();
+                manager.switch_next(context);
+            }
         }
     })
 }
 
-pub(crate) fn wait_no_block(pid: ProcessId) -> Option<isize> {
-    x86_64::instructions::interrupts::without_interrupts(|| {
-        get_process_manager().get_exit_code(pid)
+/// Create a thread running `entry(arg)` in the current process
+pub fn thread_create(entry: VirtAddr, arg: usize) -> SyscallResult<ProcessId> {
+    if entry.as_u64() >= USER_SPACE_END {
+        return Err(SyscallError::BadAddress);
+    }
+
+    Ok(processor::exclusive(|| {
+        get_process_manager().thread_create(entry, arg)
+    }))
+}
+
+/// Wait for thread `tid` to exit, just like `wait_pid`
+pub fn thread_join(tid: ProcessId, context: &mut ProcessContext) {
+    let is_thread = processor::exclusive(|| {
+        let manager = get_process_manager();
+        manager.get_exit_status(tid).is_some() || manager.is_thread(tid)
+    });
+
+    if !is_thread {
+        context.set_rax(SyscallError::NoSuchProcess.encode());
+        return;
+    }
+
+    wait_pid(tid.0 as isize, 0, context);
+}
+
+/// Collect the exit status of `pid` if it has exited
+pub(crate) fn wait_no_block(pid: ProcessId) -> Option<ExitStatus> {
+    processor::exclusive(|| {
+        let manager = get_process_manager();
+        let ret = manager.get_exit_status(pid)?;
+        manager.reap(pid);
+        Some(ret)
     })
 }
 
-pub fn read(fd: u8, buf: &mut [u8]) -> isize {
-    x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().read(fd, buf))
+/// Reap orphans adopted by the kernel, `keep` is waited by `wait_no_block`
+pub(crate) fn reap_orphans(keep: ProcessId) {
+    processor::exclusive(|| {
+        get_process_manager().reap_orphans(keep)
+    })
 }
 
-pub fn write(fd: u8, buf: &[u8]) -> isize {
-    x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().write(fd, buf))
+pub fn read(fd: u8, buf: &mut [u8]) -> SyscallResult {
+    processor::exclusive(|| get_process_manager().read(fd, buf))
+}
+
+pub fn write(fd: u8, buf: &[u8]) -> SyscallResult {
+    processor::exclusive(|| get_process_manager().write(fd, buf))
 }
 
 pub fn get_pid() -> ProcessId {
     x86_64::instructions::interrupts::without_interrupts(processor::get_pid)
 }
 
-pub fn kill(pid: ProcessId, context: &mut ProcessContext) {
-    x86_64::instructions::interrupts::without_interrupts(|| {
+pub fn is_traced() -> bool {
+    processor::exclusive(|| {
+        get_process_manager().current().read().is_traced()
+    })
+}
+
+/// Enable or disable syscall tracing for `pid`, `None` for the current process
+pub fn set_trace(pid: Option<ProcessId>, enable: bool) -> SyscallResult<()> {
+    processor::exclusive(|| {
         let manager = get_process_manager();
-        if pid == processor::get_pid() {
-            manager.kill_self(0xdead);
-            manager.switch_next(context);
-        } else {
-            manager.kill(pid, 0xdead);
+        let proc = match pid {
+            Some(pid) => manager.get_proc(&pid).ok_or(SyscallError::NoSuchProcess)?,
+            None => manager.current(),
+        };
+
+        if proc.read().status() == ProgramStatus::Dead {
+            return Err(SyscallError::NoSuchProcess);
         }
+
+        proc.write().set_traced(enable);
+        Ok(())
     })
 }
 
-pub fn spawn(name: &str) -> Result<ProcessId, String> {
-    let app = x86_64::instructions::interrupts::without_interrupts(|| {
+/// Send signal `sig` to `pid`, signal `0` only checks that `pid` exists
+pub fn kill(pid: ProcessId, sig: usize) -> SyscallResult<()> {
+    processor::exclusive(|| {
+        let manager = get_process_manager();
+        let proc = match manager.get_proc(&pid) {
+            Some(proc) if proc.read().status() != ProgramStatus::Dead => proc,
+            _ => return Err(SyscallError::NoSuchProcess),
+        };
+
+        if sig == 0 {
+            return Ok(());
+        }
+
+        let sig = Signal::try_from(sig).map_err(|_| SyscallError::InvalidArgument)?;
+        signal::send(manager, &proc, sig);
+        Ok(())
+    })
+}
+
+/// Register the handler of `sig` for the current process, returns the old one
+pub fn sigaction(sig: usize, action: SigAction) -> SyscallResult<usize> {
+    let sig = Signal::try_from(sig).map_err(|_| SyscallError::InvalidArgument)?;
+    if action.handler > SIG_IGN && action.handler as u64 >= USER_SPACE_END {
+        return Err(SyscallError::BadAddress);
+    }
+
+    processor::exclusive(|| {
+        let proc = get_process_manager().current();
+        let old = proc.write().signals_mut().set_action(sig, action)?;
+        Ok(old.handler)
+    })
+}
+
+/// Change the blocked signals of the current process, returns the old set
+pub fn sigprocmask(how: usize, set: SigSet) -> SyscallResult<SigSet> {
+    processor::exclusive(|| {
+        get_process_manager()
+            .current()
+            .write()
+            .signals_mut()
+            .set_blocked(how, set)
+    })
+}
+
+/// Return from a signal handler, a bad frame kills the process with `SIGSEGV`
+pub fn sigreturn(context: &mut ProcessContext) {
+    processor::exclusive(|| {
+        let proc = get_process_manager().current();
+        if let Err(err) = signal::sigreturn(&proc, context) {
+            warn!("sigreturn: bad signal frame of #{}: {}", proc.pid(), err);
+            proc.write().signals_mut().force(Signal::Segv);
+        }
+    })
+}
+
+/// Deliver pending signals before returning to user mode
+pub fn handle_signals(context: &mut ProcessContext) {
+    processor::exclusive(|| {
+        signal::handle(get_process_manager(), context);
+    })
+}
+
+/// The current process touched memory it can not access
+pub fn segfault(context: &mut ProcessContext) {
+    processor::exclusive(|| {
+        let manager = get_process_manager();
+        manager.current().write().signals_mut().force(Signal::Segv);
+        signal::handle(manager, context);
+    })
+}
+
+/// Spawn an app by name from the boot app list, or by path from the filesystem
+pub fn spawn(name: &str, args: Vec<String>, envs: Vec<String>) -> SyscallResult<ProcessId> {
+    with_program(name, |name, elf| elf_spawn(name, elf, args, envs))
+}
+
+/// Replace the program of the current process with `name`
+///
+/// The pid, the parent and open files are kept. On success, `context`
+/// is set to the entry of the new program.
+pub fn exec(
+    name: &str,
+    mut args: Vec<String>,
+    envs: Vec<String>,
+    context: &mut ProcessContext,
+) -> SyscallResult<()> {
+    with_program(name, |name, elf| {
+        check_args(&name, &mut args, &envs)?;
+
+        processor::exclusive(|| {
+            let manager = get_process_manager();
+            debug!("Exec {} in process #{}", name, manager.current().pid());
+            manager.exec(elf, name, &args, &envs, context);
+        });
+
+        Ok(())
+    })
+}
+
+/// Find the program `name` and call `f` with its ELF file
+///
+/// Names containing '/' are paths on the root filesystem,
+/// e.g. `/APP/HELLO`, others are apps loaded by the bootloader.
+fn with_program<T>(
+    name: &str,
+    f: impl FnOnce(String, &ElfFile) -> SyscallResult<T>,
+) -> SyscallResult<T> {
+    if name.contains('/') {
+        let data = filesystem::read_file(name)?;
+
+        let elf = ElfFile::new(&data).map_err(|err| {
+            warn!("Invalid ELF file {}: {}", name, err);
+            SyscallError::ExecFormat
+        })?;
+        check_elf(&elf)?;
+
+        let file_name = name.rsplit('/').next().unwrap_or(name).to_string();
+        return f(file_name, &elf);
+    }
+
+    let app = processor::exclusive(|| {
         let app_list = get_process_manager().app_list()?;
 
         app_list.iter().find(|&app| app.name.eq(name))
     });
 
-    if app.is_none() {
-        return Err(format!("App not found: {}", name));
+    let Some(app) = app else {
+        warn!("App not found: {}", name);
+        return Err(SyscallError::NotFound);
     };
 
-    elf_spawn(name.to_string(), &app.unwrap().elf)
+    f(name.to_string(), &app.elf)
 }
 
-pub fn elf_spawn(name: String, elf: &ElfFile) -> Result<ProcessId, String> {
-    let pid = x86_64::instructions::interrupts::without_interrupts(|| {
+/// Make sure an untrusted ELF file only loads into user space
+fn check_elf(elf: &ElfFile) -> SyscallResult<()> {
+    use xmas_elf::header::{Class, Machine, Type, sanity_check};
+    use xmas_elf::program;
+
+    let invalid = |reason: &str| {
+        warn!("Invalid ELF file: {}", reason);
+        SyscallError::ExecFormat
+    };
+
+    sanity_check(elf).map_err(invalid)?;
+
+    let header = &elf.header;
+    if header.pt1.class() != Class::SixtyFour
+        || header.pt2.machine().as_machine() != Machine::X86_64
+        || header.pt2.type_().as_type() != Type::Executable
+    {
+        return Err(invalid("not an x86_64 executable"));
+    }
+
+    if header.pt2.entry_point() >= USER_SPACE_END {
+        return Err(invalid("entry point out of user space"));
+    }
+
+    // program_iter 直接用 zero::read 读取程序头，越界或未对齐会 panic
+    let ph_offset = header.pt2.ph_offset();
+    let ph_entry_size = header.pt2.ph_entry_size() as u64;
+    let ph_end = ph_entry_size
+        .checked_mul(header.pt2.ph_count() as u64)
+        .and_then(|size| ph_offset.checked_add(size));
+    if ph_offset % 8 != 0
+        || (elf.input.as_ptr() as usize) % 8 != 0
+        || ph_entry_size != size_of::<program::ProgramHeader64>() as u64
+        || ph_end.is_none_or(|end| end > elf.input.len() as u64)
+    {
+        return Err(invalid("bad program header table"));
+    }
+
+    for segment in elf.program_iter() {
+        if segment.get_type() != Ok(program::Type::Load) {
+            continue;
+        }
+
+        let mem_end = segment.virtual_addr().checked_add(segment.mem_size());
+        let file_end = segment.offset().checked_add(segment.file_size());
+
+        if mem_end.is_none_or(|end| end > USER_SPACE_END)
+            || file_end.is_none_or(|end| end > elf.input.len() as u64)
+            || segment.file_size() > segment.mem_size()
+        {
+            return Err(invalid("bad segment"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Spawn a process from `elf`
+///
+/// `args` defaults to `[name]`, `envs` are `KEY=VALUE` strings.
+pub fn elf_spawn(
+    name: String,
+    elf: &ElfFile,
+    mut args: Vec<String>,
+    envs: Vec<String>,
+) -> SyscallResult<ProcessId> {
+    check_args(&name, &mut args, &envs)?;
+
+    let mut proc_data = ProcessData::new();
+    proc_data.set_envs(&envs);
+
+    let pid = processor::exclusive(|| {
         let manager = get_process_manager();
         let process_name = name.to_lowercase();
 
         let parent = Arc::downgrade(&manager.current());
 
-        let pid = manager.spawn(elf, name, Some(parent), None);
+        let pid = manager.spawn(elf, name, &args, &envs, Some(parent), Some(proc_data));
 
         debug!("Spawned process: {}#{}", process_name, pid);
         pid
@@ -156,37 +495,69 @@ pub fn elf_spawn(name: String, elf: &ElfFile) -> Result<ProcessId, String> {
     Ok(pid)
 }
 
+/// Default `args` to `[name]`, and check that `args` and `envs` fit on the init stack
+fn check_args(name: &str, args: &mut Vec<String>, envs: &[String]) -> SyscallResult<()> {
+    if args.is_empty() {
+        args.push(name.to_string());
+    }
+
+    if stack::stack_args_size(args, envs) > stack::STACK_ARGS_MAX {
+        return Err(SyscallError::ArgListTooLong);
+    }
+
+    if !envs.iter().all(|env| env.contains('=')) {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    Ok(())
+}
+
 pub fn current_proc_info() {
-    debug!("{:#?}", get_process_manager().current())
+    match processor::current_pid() {
+        Some(_) => debug!("{:#?}", get_process_manager().current()),
+        None => debug!("CPU {} is idle", processor::cpu_id()),
+    }
 }
 
 pub fn handle_page_fault(addr: VirtAddr, err_code: PageFaultErrorCode) -> bool {
-    x86_64::instructions::interrupts::without_interrupts(|| {
+    processor::exclusive(|| {
         get_process_manager().handle_page_fault(addr, err_code)
     })
 }
 
 pub fn list_app() {
-    x86_64::instructions::interrupts::without_interrupts(|| {
-        let app_list = get_process_manager().app_list();
-        if app_list.is_none() {
-            println!(">>> No app found in list!");
-            return;
+    processor::exclusive(|| {
+        match get_process_manager().app_list() {
+            Some(app_list) => {
+                let apps = app_list
+                    .iter()
+                    .map(|app| app.name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ");
+
+                println!(">>> App list: {}", apps);
+            }
+            None => println!(">>> No app found in list!"),
         }
+    });
 
-        let apps = app_list
-            .unwrap()
-            .iter()
-            .map(|app| app.name.as_str())
-            .collect::<Vec<&str>>()
-            .join(", ");
+    // apps on the disk can be spawned by path
+    match filesystem::get_vfs().read_dir(filesystem::APP_PATH) {
+        Ok(iter) => {
+            let apps = iter
+                .filter(|meta| meta.is_file())
+                .map(|meta| format!("{}/{}", filesystem::APP_PATH, meta.name))
+                .collect::<Vec<String>>()
+                .join(", ");
 
-        println!(">>> App list: {}", apps);
-    });
+            println!(">>> Disk apps: {}", apps);
+        }
+        Err(_) => println!(">>> No app found in {}", filesystem::APP_PATH),
+    }
 }
 
 pub fn vfork(context: &mut ProcessContext) {
-    x86_64::instructions::interrupts::without_interrupts(|| {
+    processor::exclusive(|| {
         let manager = get_process_manager();
         let pid = manager.save_current(context);
         manager.vfork();
@@ -195,26 +566,36 @@ pub fn vfork(context: &mut ProcessContext) {
     })
 }
 
-pub fn sem_new(key: u32, value: usize) -> usize {
-    x86_64::instructions::interrupts::without_interrupts(|| {
+pub fn fork(context: &mut ProcessContext) {
+    processor::exclusive(|| {
+        let manager = get_process_manager();
+        let pid = manager.save_current(context);
+        manager.fork();
+        manager.push_ready(pid);
+        manager.switch_next(context);
+    })
+}
+
+pub fn sem_new(key: u32, value: usize) -> SyscallResult {
+    processor::exclusive(|| {
         if get_process_manager().current().write().sem_new(key, value) {
-            return 0;
+            return Ok(0);
         }
-        1
+        Err(SyscallError::AlreadyExists)
     })
 }
 
-pub fn remove_sem(key: u32) -> usize {
-    x86_64::instructions::interrupts::without_interrupts(|| {
+pub fn remove_sem(key: u32) -> SyscallResult {
+    processor::exclusive(|| {
         if get_process_manager().current().write().sem_remove(key) {
-            return 0;
+            return Ok(0);
         }
-        1
+        Err(SyscallError::NotFound)
     })
 }
 
 pub fn sem_wait(key: u32, context: &mut ProcessContext) {
-    x86_64::instructions::interrupts::without_interrupts(|| {
+    processor::exclusive(|| {
         let manager = get_process_manager();
         let pid = manager.current().pid();
         let result = manager.current().write().sem_wait(key, pid);
@@ -225,28 +606,54 @@ pub fn sem_wait(key: u32, context: &mut ProcessContext) {
                 manager.block(pid);
                 manager.switch_next(context);
             },
-            SemaphoreResult::NotExist => context.set_rax(1),
+            SemaphoreResult::NotExist => context.set_rax(SyscallError::NotFound.encode()),
             _ => unreachable!(),
         }
     })
 }
 
 pub fn sem_signal(key: u32, context: &mut ProcessContext) {
-    x86_64::instructions::interrupts::without_interrupts(|| {
+    processor::exclusive(|| {
         let manager = get_process_manager();
         let result = manager.current().write().sem_signal(key);
         match result {
             SemaphoreResult::Ok => context.set_rax(0),
-            SemaphoreResult::WakeUp(pid) => manager.wake_up(pid, None),
-            SemaphoreResult::NotExist => context.set_rax(1),
+            SemaphoreResult::WakeUp(pid) => {
+                context.set_rax(0);
+                manager.wake_up(pid, Some(0));
+            }
+            SemaphoreResult::NotExist => context.set_rax(SyscallError::NotFound.encode()),
             _ => unreachable!(),
         }
     })
 }
 
 pub fn brk(addr: Option<VirtAddr>) -> Option<VirtAddr> {
-    x86_64::instructions::interrupts::without_interrupts(|| {
+    processor::exclusive(|| {
         // NOTE: `brk` does not need to get write lock
         get_process_manager().current().read().vm().brk(addr)
     })
-}
\ No newline at end of file
+}
+
+/// Allocate from the `sys_allocate` heap of the current address space
+pub fn allocate(layout: Layout) -> Option<NonNull<u8>> {
+    processor::exclusive(|| {
+        // drop the process lock before touching the heap,
+        // a write to a copy-on-write page takes it again
+        let heap = get_process_manager().current().read().vm().user_heap().ok()?;
+        heap.allocate(layout)
+    })
+}
+
+/// Return a block from `allocate` to the heap of the current address space
+///
+/// # Safety
+///
+/// `ptr` must come from `allocate` in this address space with the same `layout`
+pub unsafe fn deallocate(ptr: NonNull<u8>, layout: Layout) {
+    processor::exclusive(|| {
+        if let Ok(heap) = get_process_manager().current().read().vm().user_heap() {
+            unsafe { heap.deallocate(ptr, layout) };
+        }
+    })
+}
@@ -68,6 +68,10 @@ impl PageTableContext {
         }
     }
 
+    pub fn shares_with(&self, other: &PageTableContext) -> bool {
+        Arc::ptr_eq(&self.reg, &other.reg)
+    }
+
     pub fn mapper(&self) -> OffsetPageTable<'static> {
         unsafe {
             OffsetPageTable::new(
@@ -4,7 +4,9 @@ use alloc::vec::Vec;
 use alloc::sync::Arc;
 use crate::proc::vm::ProcessVm;
 use spin::*;
-use crate::humanized_size;
+use crate::utils::clock;
+use core::time::Duration;
+use syscall_def::proc::{NO_CPU, ProcInfo, ProcStatus};
 
 #[derive(Clone)]
 pub struct Process {
@@ -16,12 +18,21 @@ pub struct ProcessInner {
     name: String,
     parent: Option<Weak<Process>>,
     children: Vec<Arc<Process>>,
-    ticks_passed: usize,
+    /// timer ticks spent in user mode
+    user_ticks: u64,
+    /// timer ticks spent in the kernel
+    kernel_ticks: u64,
+    /// time since boot the process was created at
+    start_time: Duration,
     status: ProgramStatus,
     context: ProcessContext,
-    exit_code: Option<isize>,
+    exit_status: Option<ExitStatus>,
     proc_data: Option<ProcessData>,
     proc_vm: Option<ProcessVm>,
+    /// log every syscall made by this process (inherited by children)
+    traced: bool,
+    signals: SignalState,
+    sched: SchedInfo,
 }
 
 impl Process {
@@ -51,17 +62,25 @@ impl Process {
         // create context
         let pid = ProcessId::new();
         let proc_vm = proc_vm.unwrap_or_else(|| ProcessVm::new(PageTableContext::new()));
+        let parent_proc = parent.as_ref().and_then(|p| p.upgrade());
+        let traced = parent_proc.as_ref().is_some_and(|p| p.read().is_traced());
+        let sched = parent_proc.map(|p| p.read().sched().fork()).unwrap_or_default();
 
         let inner = ProcessInner {
             name,
             parent,
             status: ProgramStatus::Ready,
             context: ProcessContext::default(),
-            ticks_passed: 0,
-            exit_code: None,
+            user_ticks: 0,
+            kernel_ticks: 0,
+            start_time: clock::now(),
+            exit_status: None,
             children: Vec::new(),
             proc_vm: Some(proc_vm),
             proc_data: Some(proc_data.unwrap_or_default()),
+            traced,
+            signals: SignalState::default(),
+            sched,
         };
 
         trace!("New process {}#{} created.", &inner.name, pid);
@@ -73,29 +92,79 @@ impl Process {
         })
     }
 
-    pub fn kill(&self, ret: isize) {
+    /// Snapshot of the process for `Syscall::ProcInfo`
+    pub fn info(&self) -> ProcInfo {
+        let inner = self.inner.read();
+        let mut info = ProcInfo::default();
+        info.pid = self.pid.0;
+        info.ppid = inner.parent().map_or(0, |p| p.pid.0);
+        info.status = match inner.status {
+            ProgramStatus::Running => ProcStatus::Running,
+            ProgramStatus::Ready => ProcStatus::Ready,
+            ProgramStatus::Blocked => ProcStatus::Blocked,
+            ProgramStatus::Dead => ProcStatus::Dead,
+        };
+        info.nice = inner.sched.nice as i8;
+        info.level = inner.sched.level as u8;
+        info.cpu = processor::cpu_of(self.pid).map_or(NO_CPU, |cpu| cpu as u8);
+        info.user_ticks = inner.user_ticks;
+        info.kernel_ticks = inner.kernel_ticks;
+        info.start_ns = inner.start_time.as_nanos() as u64;
+        info.set_name(&inner.name);
+        if let Some(vm) = &inner.proc_vm {
+            (info.code, info.stack, info.heap) = vm.usage();
+        }
+        info
+    }
+
+    /// Terminate the process, returns the children left as orphans
+    pub fn kill(&self, status: ExitStatus) -> Vec<Arc<Process>> {
         let mut inner = self.inner.write();
 
+        debug!("Killing process {}#{}: {}", inner.name(), self.pid, status);
+
+        inner.kill(self.pid(), status)
+    }
+
+    pub fn vfork(self: &Arc<Self>) -> Arc<Self> {
+        self.fork_with(ProcessInner::vfork)
+    }
+
+    pub fn fork(self: &Arc<Self>) -> Arc<Self> {
+        self.fork_with(ProcessInner::fork)
+    }
+
+    /// Create a thread running `entry(arg)` in the address space of this process
+    pub fn thread(self: &Arc<Self>, entry: VirtAddr, arg: usize) -> Arc<Self> {
+        let mut inner = self.inner.write();
+        let new_inner = inner.thread(Arc::downgrade(self), entry, arg);
+        let child = Arc::new(Process {
+            pid: ProcessId::new(),
+            inner: Arc::new(RwLock::new(new_inner)),
+        });
         debug!(
-            "Killing process {}#{} with ret code: {}",
+            "Process {}#{} created thread #{}",
             inner.name(),
             self.pid,
-            ret
+            child.pid
         );
-
-        inner.kill(self.pid(), ret);
+        inner.children.push(child.clone());
+        child
     }
 
-    pub fn vfork(self: &Arc<Self>) -> Arc<Self> {
+    fn fork_with(
+        self: &Arc<Self>,
+        fork: impl FnOnce(&mut ProcessInner, Weak<Process>) -> ProcessInner,
+    ) -> Arc<Self> {
         let mut inner = self.inner.write();
-        let new_inner = inner.vfork(Arc::downgrade(self));
+        let new_inner = fork(&mut inner, Arc::downgrade(self));
         let pid = ProcessId::new();
         let child = Arc::new(Process {
             pid,
             inner: Arc::new(RwLock::new(new_inner)),
         });
         debug!(
-            "Process {}#{} vforked to {}#{}",
+            "Process {}#{} forked to {}#{}",
             inner.name(),
             self.pid,
             child.inner.read().name(),
@@ -133,8 +202,13 @@ impl ProcessInner {
         self.parent.as_ref().and_then(|p| p.upgrade())
     }
     
-    pub fn tick(&mut self) {
-        self.ticks_passed += 1;
+    /// Account a timer tick, `user` if it interrupted user mode
+    pub fn tick(&mut self, user: bool) {
+        if user {
+            self.user_ticks += 1;
+        } else {
+            self.kernel_ticks += 1;
+        }
     }
 
     pub fn status(&self) -> ProgramStatus {
@@ -153,8 +227,32 @@ impl ProcessInner {
         self.status = ProgramStatus::Blocked;
     }
 
-    pub fn exit_code(&self) -> Option<isize> {
-        self.exit_code
+    pub fn is_traced(&self) -> bool {
+        self.traced
+    }
+
+    pub fn set_traced(&mut self, traced: bool) {
+        self.traced = traced;
+    }
+
+    pub fn signals(&self) -> &SignalState {
+        &self.signals
+    }
+
+    pub fn signals_mut(&mut self) -> &mut SignalState {
+        &mut self.signals
+    }
+
+    pub fn sched(&self) -> &SchedInfo {
+        &self.sched
+    }
+
+    pub fn sched_mut(&mut self) -> &mut SchedInfo {
+        &mut self.sched
+    }
+
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        self.exit_status
     }
 
     pub fn clone_page_table(&self) -> PageTableContext {
@@ -165,6 +263,16 @@ impl ProcessInner {
         self.status == ProgramStatus::Ready
     }
 
+    /// Mapped user memory, empty for a killed process
+    pub fn vm_areas(&self) -> Vec<VmArea> {
+        self.proc_vm.as_ref().map_or_else(Vec::new, |vm| vm.areas())
+    }
+
+    /// Open file descriptors, empty for a killed process
+    pub fn fds(&self) -> Vec<(u8, String)> {
+        self.proc_data.as_ref().map_or_else(Vec::new, |data| data.fds())
+    }
+
     pub fn vm(&self) -> &ProcessVm {
         self.proc_vm.as_ref().unwrap()
     }
@@ -173,10 +281,22 @@ impl ProcessInner {
         self.proc_vm.as_mut().unwrap()
     }
 
+    /// Whether both processes run in the same address space
+    pub fn shares_vm_with(&self, other: &ProcessInner) -> bool {
+        match (self.proc_vm.as_ref(), other.proc_vm.as_ref()) {
+            (Some(vm), Some(other)) => vm.shares_with(other),
+            _ => false,
+        }
+    }
+
     pub fn handle_page_fault(&mut self, addr: VirtAddr) -> bool {
         self.vm_mut().handle_page_fault(addr)
     }
 
+    pub fn handle_cow_fault(&mut self, addr: VirtAddr) -> bool {
+        self.vm_mut().handle_cow_fault(addr)
+    }
+
     pub fn load_elf(&mut self, elf: &ElfFile) {
         self.vm_mut().load_elf(elf);
     }
@@ -205,54 +325,96 @@ impl ProcessInner {
         self.status = ProgramStatus::Running;
     }
 
-    pub fn init_stack_frame(&mut self, entry: VirtAddr, stack_top: VirtAddr) {
-        self.context.init_stack_frame(entry, stack_top)
+    pub fn init_stack_frame(&mut self, entry: VirtAddr, args: &ProcessArgs) {
+        self.context.init_stack_frame(entry, args.stack_top);
+        self.context.init_args(args);
     }
 
-    pub fn kill(&mut self, pid: ProcessId, ret: isize) {
-        let children = self.children();
-
-        // remove self from parent, and set parent to children
-        if let Some(parent) = self.parent() {
-            if parent.read().exit_code().is_none() {
-                parent.write().remove_child(pid);
-                let weak = Arc::downgrade(&parent);
-                for child in children {
-                    child.write().set_parent(weak.clone());
-                }
-            } else {
-                // parent already exited, set parent to None
-                for child in children {
-                    child.write().set_parent(Weak::new());
-                }
-            }
+    /// Release everything but the exit status
+    ///
+    /// The process stays as a zombie until its parent waits for it,
+    /// the children are returned to be adopted by the init process.
+    pub fn kill(&mut self, pid: ProcessId, status: ExitStatus) -> Vec<Arc<Process>> {
+        // resources are released once the last process sharing them exits
+        if let Some(proc_data) = self.proc_data.take() {
+            proc_data.sem_cancel(pid);
         }
-
         self.proc_vm.take();
-        self.proc_data.take();
-        self.exit_code = Some(ret);
+        self.exit_status = Some(status);
         self.status = ProgramStatus::Dead;
+
+        core::mem::take(&mut self.children)
     }
-    
-    pub fn vfork(&mut self, parent: Weak<Process>) -> ProcessInner{
-        let proc_vm = self.vm().fork(self.children.len() as u64 + 1);
+
+    pub fn vfork(&mut self, parent: Weak<Process>) -> ProcessInner {
+        let proc_vm = self.vm().vfork(self.children.len() as u64 + 1);
         let offset = proc_vm.stack.stack_offset(&self.vm().stack);
         let mut child_context = self.context;
         child_context.set_stack_offset(offset);
-        child_context.set_rax(0);
+        self.fork_child(parent, proc_vm, self.proc_data.clone(), child_context)
+    }
+
+    pub fn fork(&mut self, parent: Weak<Process>) -> ProcessInner {
+        let proc_vm = self.vm().fork();
+        let proc_data = self.proc_data.as_ref().map(ProcessData::fork);
+        self.fork_child(parent, proc_vm, proc_data, self.context)
+    }
+
+    pub fn thread(&mut self, parent: Weak<Process>, entry: VirtAddr, arg: usize) -> ProcessInner {
+        let proc_vm = self.vm().thread(self.children.len() as u64 + 1);
+        let mut context = ProcessContext::default();
+        context.init_thread(entry, proc_vm.stack.init_top(), arg);
+        self.fork_child(parent, proc_vm, self.proc_data.clone(), context)
+    }
+
+    fn fork_child(
+        &self,
+        parent: Weak<Process>,
+        proc_vm: ProcessVm,
+        proc_data: Option<ProcessData>,
+        mut context: ProcessContext,
+    ) -> ProcessInner {
+        context.set_rax(0);
         Self {
             name: self.name.clone(),
             parent: Some(parent),
             status: ProgramStatus::Ready,
-            context: child_context,
-            ticks_passed: 0,
-            exit_code: None,
+            context,
+            user_ticks: 0,
+            kernel_ticks: 0,
+            start_time: clock::now(),
+            exit_status: None,
             children: Vec::new(),
             proc_vm: Some(proc_vm),
-            proc_data: self.proc_data.clone(),
+            proc_data,
+            traced: self.traced,
+            signals: self.signals.fork(),
+            sched: self.sched.fork(),
         }
     }
 
+    /// Replace the address space with a new one loaded from `elf`
+    pub fn exec(
+        &mut self,
+        elf: &ElfFile,
+        name: String,
+        page_table: PageTableContext,
+        args: &[String],
+        envs: &[String],
+    ) {
+        let mut proc_vm = ProcessVm::new(page_table);
+        proc_vm.load_elf(elf);
+        let args = proc_vm.init_args(args, envs);
+
+        // the old page table is freed with the old vm, leave it first
+        proc_vm.page_table.load();
+        self.proc_vm.replace(proc_vm);
+
+        self.name = name;
+        self.signals.exec();
+        self.init_stack_frame(VirtAddr::new_truncate(elf.header.pt2.entry_point()), &args);
+    }
+
     pub fn brk(&mut self, addr: Option<VirtAddr>) -> Option<VirtAddr> {
         self.vm_mut().brk(addr)
     }
@@ -293,7 +455,9 @@ impl core::fmt::Debug for Process {
             .field("name", &inner.name)
             .field("parent", &inner.parent().map(|p| p.pid))
             .field("status", &inner.status)
-            .field("ticks_passed", &inner.ticks_passed)
+            .field("sched", &inner.sched)
+            .field("user_ticks", &inner.user_ticks)
+            .field("kernel_ticks", &inner.kernel_ticks)
             .field("children", &inner.children.iter().map(|c| c.pid.0))
             .field("status", &inner.status)
             .field("context", &inner.context)
@@ -302,25 +466,3 @@ impl core::fmt::Debug for Process {
     }
 }
 
-impl core::fmt::Display for Process {
-    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        let inner = self.inner.read();
-        let (size, unit) = inner.proc_vm.as_ref().map_or((0.0, "B"), |vm| {
-            let usage = vm.memory_usage();
-            humanized_size(usage)
-        });
-
-        write!(
-            f,
-            // 在 Process Name 和 Ticks 之间添加了内存占用的列
-            " #{:<3} | #{:<3} | {:<12} | {:>5.1} {:<2} | {:<7} | {:?}",
-            self.pid.0, // PID
-            inner.parent().map(|p| p.pid.0).unwrap_or(0), // PPID
-            inner.name,                                   // 进程名
-            size,                                         // 内存大小
-            unit,                                         // 内存单位
-            inner.ticks_passed,                           // Ticks
-            inner.status                                  // 状态
-        )
-    }
-}
@@ -1,37 +1,35 @@
-use core::sync::atomic::{AtomicU16, Ordering};
+use core::sync::atomic::{AtomicU16, AtomicU64, AtomicUsize, Ordering};
 
 use crate::proc::ProcessId;
-use alloc::{string::String, vec::Vec};
 use x86::cpuid::CpuId;
+use x86_64::VirtAddr;
 
-const MAX_CPU_COUNT: usize = 4;
+pub const MAX_CPU_COUNT: usize = 4;
 
 #[allow(clippy::declare_interior_mutable_const)]
 const EMPTY: Processor = Processor::new(); // means no process
 
 static PROCESSORS: [Processor; MAX_CPU_COUNT] = [EMPTY; MAX_CPU_COUNT];
 
-/// Returns the current processor based on the current APIC ID
-fn current() -> &'static Processor {
-    let cpuid = CpuId::new()
+/// Top of the stack each CPU idles on, see `idle_context`
+static IDLE_STACKS: [AtomicU64; MAX_CPU_COUNT] = [const { AtomicU64::new(0) }; MAX_CPU_COUNT];
+
+/// CPU holding the process lock, see `exclusive`
+static LOCK_OWNER: AtomicUsize = AtomicUsize::new(NO_OWNER);
+const NO_OWNER: usize = usize::MAX;
+
+/// APIC ID of the current CPU
+#[inline]
+pub fn cpu_id() -> usize {
+    CpuId::new()
         .get_feature_info()
         .unwrap()
-        .initial_local_apic_id() as usize;
-
-    &PROCESSORS[cpuid]
+        .initial_local_apic_id() as usize
 }
 
-pub fn print_processors() -> String {
-    alloc::format!(
-        "CPUs   : {}\n",
-        PROCESSORS
-            .iter()
-            .enumerate()
-            .filter(|(_, p)| !p.is_free())
-            .map(|(i, p)| alloc::format!("[{}: {}]", i, p.get_pid().unwrap()))
-            .collect::<Vec<_>>()
-            .join(", ")
-    )
+/// Returns the current processor based on the current APIC ID
+fn current() -> &'static Processor {
+    &PROCESSORS[cpu_id()]
 }
 
 /// Processor holds the current process id
@@ -43,6 +41,12 @@ impl Processor {
     }
 }
 
+impl Default for Processor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[inline]
 pub fn set_pid(pid: ProcessId) {
     current().set_pid(pid)
@@ -53,6 +57,101 @@ pub fn get_pid() -> ProcessId {
     current().get_pid().expect("No current process")
 }
 
+/// The process running on this CPU, `None` if it is idle
+#[inline]
+pub fn current_pid() -> Option<ProcessId> {
+    current().get_pid()
+}
+
+/// Whether this is the bootstrap processor
+pub fn is_bsp() -> bool {
+    const IA32_APIC_BASE: u32 = 0x1b;
+    const BSP: u64 = 1 << 8;
+    unsafe { x86_64::registers::model_specific::Msr::new(IA32_APIC_BASE).read() & BSP != 0 }
+}
+
+/// Whether `pid` is running on any CPU
+pub fn is_running(pid: ProcessId) -> bool {
+    cpu_of(pid).is_some()
+}
+
+/// The CPU running `pid`
+pub fn cpu_of(pid: ProcessId) -> Option<usize> {
+    PROCESSORS.iter().position(|p| p.get_pid() == Some(pid))
+}
+
+/// Processes running on the other CPUs
+pub fn running_elsewhere() -> impl Iterator<Item = ProcessId> {
+    let cpu = cpu_id();
+    PROCESSORS
+        .iter()
+        .enumerate()
+        .filter(move |&(i, _)| i != cpu)
+        .filter_map(|(_, p)| p.get_pid())
+}
+
+/// Whether CPU `cpu` has been started
+#[inline]
+pub fn is_online(cpu: usize) -> bool {
+    IDLE_STACKS[cpu].load(Ordering::Relaxed) != 0
+}
+
+/// Number of CPUs started
+pub fn online_count() -> usize {
+    (0..MAX_CPU_COUNT).filter(|&cpu| is_online(cpu)).count()
+}
+
+/// Set the stack this CPU idles on, which also marks it online
+pub fn set_idle_stack(stack_top: VirtAddr) {
+    IDLE_STACKS[cpu_id()].store(stack_top.as_u64(), Ordering::Relaxed);
+}
+
+/// Leave the current process and idle until something is ready
+///
+/// The CPU halts in ring 0 on its own stack with interrupts enabled,
+/// the timer interrupt picks up the next ready process. The caller
+/// switches to a page table that stays valid.
+pub fn idle_context(context: &mut super::ProcessContext) {
+    let stack_top = IDLE_STACKS[cpu_id()].load(Ordering::Relaxed);
+    assert!(stack_top != 0, "No idle stack for CPU {}", cpu_id());
+
+    // leave space for the return address, just like `STACK_INIT_TOP`
+    context.init_kernel(VirtAddr::new(idle as usize as u64), VirtAddr::new(stack_top - 8));
+    current().set_pid(ProcessId(0));
+}
+
+extern "C" fn idle() -> ! {
+    loop {
+        x86_64::instructions::interrupts::enable_and_hlt();
+    }
+}
+
+/// Run `f` with interrupts disabled, while holding the process lock
+///
+/// The lock serializes scheduling across CPUs, it is reentrant so that
+/// process management functions can call each other.
+pub fn exclusive<T>(f: impl FnOnce() -> T) -> T {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let cpu = cpu_id();
+        let nested = LOCK_OWNER.load(Ordering::Acquire) == cpu;
+        if !nested {
+            while LOCK_OWNER
+                .compare_exchange_weak(NO_OWNER, cpu, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                core::hint::spin_loop();
+            }
+        }
+
+        let ret = f();
+
+        if !nested {
+            LOCK_OWNER.store(NO_OWNER, Ordering::Release);
+        }
+        ret
+    })
+}
+
 impl Processor {
     #[inline]
     pub fn is_free(&self) -> bool {
@@ -0,0 +1,195 @@
+//! Scheduling policies, selected by `sched_policy` in the boot config
+//!
+//! - `rr`: round-robin, switch on every timer tick
+//! - `priority`: always run the ready process with the lowest nice value,
+//!   round-robin among equal priorities
+//! - `mlfq`: multi-level feedback queue, a process using up its time slice
+//!   moves one level down, all levels are boosted back to the top periodically
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use core::fmt::Debug;
+use syscall_def::sched::*;
+
+use super::ProcessId;
+
+/// Per-process scheduling state, kept in `ProcessInner`
+#[derive(Clone, Copy, Debug)]
+pub struct SchedInfo {
+    /// nice value in `[NICE_MIN, NICE_MAX]`
+    pub nice: isize,
+    /// queue level of `mlfq`, 0 is the highest
+    pub level: usize,
+    /// ticks used in the current time slice
+    pub used: usize,
+    /// `mlfq` boost period this state belongs to
+    epoch: usize,
+}
+
+impl Default for SchedInfo {
+    fn default() -> Self {
+        Self {
+            nice: NICE_DEFAULT,
+            level: 0,
+            used: 0,
+            epoch: 0,
+        }
+    }
+}
+
+impl SchedInfo {
+    /// State of a new child, the priority is inherited
+    pub fn fork(&self) -> Self {
+        Self {
+            nice: self.nice,
+            ..Self::default()
+        }
+    }
+}
+
+pub trait Scheduler: Debug + Send {
+    fn name(&self) -> &'static str;
+
+    /// Queue a process that is ready to run
+    fn push(&mut self, pid: ProcessId, info: &mut SchedInfo);
+
+    /// Take the next process to run
+    fn pop(&mut self) -> Option<ProcessId>;
+
+    /// Account a timer tick to the running process,
+    /// returns whether it should be preempted
+    fn tick(&mut self, info: &mut SchedInfo) -> bool;
+}
+
+/// Create the scheduler named `name`, round-robin if unknown
+pub fn new_scheduler(name: &str) -> Box<dyn Scheduler> {
+    match name {
+        "rr" | "" => Box::new(RoundRobin::default()),
+        "priority" => Box::new(Priority::default()),
+        "mlfq" => Box::new(Mlfq::default()),
+        _ => {
+            warn!("Unknown scheduler \"{}\", fall back to rr", name);
+            Box::new(RoundRobin::default())
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    queue: VecDeque<ProcessId>,
+}
+
+impl Scheduler for RoundRobin {
+    fn name(&self) -> &'static str {
+        "rr"
+    }
+
+    fn push(&mut self, pid: ProcessId, _info: &mut SchedInfo) {
+        self.queue.push_back(pid);
+    }
+
+    fn pop(&mut self) -> Option<ProcessId> {
+        self.queue.pop_front()
+    }
+
+    fn tick(&mut self, _info: &mut SchedInfo) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Priority {
+    queues: BTreeMap<isize, VecDeque<ProcessId>>,
+}
+
+impl Scheduler for Priority {
+    fn name(&self) -> &'static str {
+        "priority"
+    }
+
+    fn push(&mut self, pid: ProcessId, info: &mut SchedInfo) {
+        self.queues.entry(info.nice).or_default().push_back(pid);
+    }
+
+    fn pop(&mut self) -> Option<ProcessId> {
+        let mut entry = self.queues.first_entry()?;
+        let pid = entry.get_mut().pop_front();
+        if entry.get().is_empty() {
+            entry.remove();
+        }
+        pid
+    }
+
+    fn tick(&mut self, _info: &mut SchedInfo) -> bool {
+        true
+    }
+}
+
+/// Number of levels of `Mlfq`
+const MLFQ_LEVELS: usize = 3;
+/// Ticks between two boosts of `Mlfq`
+const MLFQ_BOOST_TICKS: usize = 100;
+
+#[derive(Debug, Default)]
+pub struct Mlfq {
+    queues: [VecDeque<ProcessId>; MLFQ_LEVELS],
+    ticks: usize,
+    epoch: usize,
+}
+
+impl Mlfq {
+    /// Time slice of `level`, doubled on each level down
+    fn slice(level: usize) -> usize {
+        1 << level
+    }
+
+    /// Move every process back to the top level
+    fn boost(&mut self) {
+        self.epoch += 1;
+        for level in 1..MLFQ_LEVELS {
+            let queue = core::mem::take(&mut self.queues[level]);
+            self.queues[0].extend(queue);
+        }
+    }
+
+    fn refresh(&self, info: &mut SchedInfo) {
+        if info.epoch != self.epoch {
+            info.epoch = self.epoch;
+            info.level = 0;
+            info.used = 0;
+        }
+    }
+}
+
+impl Scheduler for Mlfq {
+    fn name(&self) -> &'static str {
+        "mlfq"
+    }
+
+    fn push(&mut self, pid: ProcessId, info: &mut SchedInfo) {
+        self.refresh(info);
+        self.queues[info.level].push_back(pid);
+    }
+
+    fn pop(&mut self) -> Option<ProcessId> {
+        self.queues.iter_mut().find_map(|q| q.pop_front())
+    }
+
+    fn tick(&mut self, info: &mut SchedInfo) -> bool {
+        self.ticks += 1;
+        if self.ticks % MLFQ_BOOST_TICKS == 0 {
+            self.boost();
+        }
+        self.refresh(info);
+
+        info.used += 1;
+        if info.used < Self::slice(info.level) {
+            // keep running, unless a higher level has something to run
+            return self.queues[..info.level].iter().any(|q| !q.is_empty());
+        }
+
+        info.used = 0;
+        info.level = (info.level + 1).min(MLFQ_LEVELS - 1);
+        true
+    }
+}
@@ -0,0 +1,277 @@
+//! POSIX-style signals
+//!
+//! Signals are raised by setting a bit in the pending set of the target,
+//! and delivered when the current process returns to user mode: either the
+//! default action is taken, or a `SignalFrame` is pushed to the user stack
+//! and the process continues in its handler. The handler returns through
+//! the restorer registered with `sigaction`, which calls `sigreturn`.
+
+use core::mem::size_of;
+use syscall_def::signal::*;
+use syscall_def::{SyscallError, SyscallResult};
+use x86_64::VirtAddr;
+
+use super::*;
+use crate::memory::gdt::get_user_selector;
+use crate::memory::uaccess::*;
+
+/// Bytes below the user stack pointer that must be left untouched
+const RED_ZONE: u64 = 128;
+
+/// Handler registered with `sigaction`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SigAction {
+    /// `SIG_DFL`, `SIG_IGN` or the address of `fn(signo: usize)`
+    pub handler: usize,
+    /// signals blocked while the handler runs
+    pub mask: SigSet,
+    /// where the handler returns to, it should call `sigreturn`
+    pub restorer: usize,
+}
+
+/// Saved state pushed to the user stack before entering a handler
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SignalFrame {
+    /// return address of the handler
+    restorer: usize,
+    signo: usize,
+    blocked: SigSet,
+    context: ProcessContext,
+}
+
+// SAFETY: only integers, `VirtAddr`s and bitflags, any bit pattern is valid
+unsafe impl Pod for SignalFrame {}
+
+#[derive(Clone, Debug, Default)]
+pub struct SignalState {
+    pending: SigSet,
+    blocked: SigSet,
+    actions: [SigAction; NSIG],
+}
+
+impl SignalState {
+    /// State of a forked child: handlers and mask are inherited,
+    /// pending signals are not
+    pub fn fork(&self) -> Self {
+        Self {
+            pending: SigSet::empty(),
+            blocked: self.blocked,
+            actions: self.actions,
+        }
+    }
+
+    /// Handlers point into the old program, reset them on `exec`
+    pub fn exec(&mut self) {
+        for action in self.actions.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SigAction::default();
+            }
+        }
+    }
+
+    pub fn action(&self, sig: Signal) -> SigAction {
+        self.actions[sig.number()]
+    }
+
+    pub fn set_action(&mut self, sig: Signal, action: SigAction) -> SyscallResult<SigAction> {
+        if !sig.can_catch() {
+            return Err(SyscallError::InvalidArgument);
+        }
+
+        Ok(core::mem::replace(&mut self.actions[sig.number()], action))
+    }
+
+    pub fn set_blocked(&mut self, how: usize, set: SigSet) -> SyscallResult<SigSet> {
+        let old = self.blocked;
+        let mut blocked = match how {
+            SIG_BLOCK => old.union(set),
+            SIG_UNBLOCK => old.difference(set),
+            SIG_SETMASK => set,
+            _ => return Err(SyscallError::InvalidArgument),
+        };
+        blocked.remove(Signal::Kill);
+        self.blocked = blocked;
+
+        Ok(old)
+    }
+
+    pub fn raise(&mut self, sig: Signal) {
+        self.pending.insert(sig);
+    }
+
+    /// Raise a signal caused by the process itself (e.g. `SIGSEGV`),
+    /// which can not be blocked or ignored
+    pub fn force(&mut self, sig: Signal) {
+        let action = &mut self.actions[sig.number()];
+        if self.blocked.contains(sig) || action.handler == SIG_IGN {
+            *action = SigAction::default();
+            self.blocked.remove(sig);
+        }
+        self.pending.insert(sig);
+    }
+
+    /// Whether `sig` terminates the process without running any user code
+    pub fn is_fatal(&self, sig: Signal) -> bool {
+        sig == Signal::Kill
+            || (!self.blocked.contains(sig)
+                && self.action(sig).handler == SIG_DFL
+                && sig.default_action() == DefaultAction::Terminate)
+    }
+
+    /// Take the next pending signal that is not blocked
+    fn take(&mut self) -> Option<(Signal, SigAction)> {
+        let sig = self.pending.difference(self.blocked).first()?;
+        self.pending.remove(sig);
+
+        let action = match sig {
+            Signal::Kill => SigAction::default(),
+            _ => self.action(sig),
+        };
+        Some((sig, action))
+    }
+}
+
+/// Send `sig` to `proc`
+///
+/// A signal that terminates a process with the default action is handled
+/// at once, so processes blocked in the kernel can be killed. Others stay
+/// pending until the target returns to user mode, as do all signals to
+/// a process running on some CPU.
+pub(super) fn send(manager: &ProcessManager, proc: &Process, sig: Signal) {
+    let pid = proc.pid();
+    let fatal = proc.read().signals().is_fatal(sig);
+
+    if fatal && !processor::is_running(pid) {
+        info!("Process #{} terminated by {}", pid, sig);
+        manager.kill(pid, ExitStatus::Signaled(sig));
+    } else {
+        proc.write().signals_mut().raise(sig);
+    }
+}
+
+/// Deliver pending signals of the current process before it returns to user mode
+///
+/// This may terminate the current process and switch to another one,
+/// whose pending signals are handled too.
+pub(super) fn handle(manager: &ProcessManager, context: &mut ProcessContext) {
+    loop {
+        // the kernel, or an idle CPU
+        if !context.is_user_mode() {
+            return;
+        }
+
+        let proc = manager.current();
+
+        let Some((sig, action)) = proc.write().signals_mut().take() else {
+            return;
+        };
+
+        match action.handler {
+            SIG_IGN => continue,
+            SIG_DFL if sig.default_action() == DefaultAction::Ignore => continue,
+            SIG_DFL => terminate(manager, sig, context),
+            handler => {
+                if let Err(err) = enter_handler(&proc, sig, handler, action, context) {
+                    warn!("Failed to deliver {} to #{}: {}", sig, proc.pid(), err);
+                    terminate(manager, Signal::Segv, context);
+                    continue;
+                }
+                return;
+            }
+        }
+    }
+}
+
+fn terminate(manager: &ProcessManager, sig: Signal, context: &mut ProcessContext) {
+    info!("Process #{} terminated by {}", processor::get_pid(), sig);
+    manager.kill_self(ExitStatus::Signaled(sig));
+    manager.switch_next(context);
+}
+
+fn enter_handler(
+    proc: &Process,
+    sig: Signal,
+    handler: usize,
+    action: SigAction,
+    context: &mut ProcessContext,
+) -> SyscallResult<()> {
+    let mut inner = proc.write();
+    let signals = inner.signals_mut();
+
+    let frame = SignalFrame {
+        restorer: action.restorer,
+        signo: sig.number(),
+        blocked: signals.blocked,
+        context: *context,
+    };
+
+    // the handler starts as if `frame.restorer` was pushed by `call`
+    let sp = context.stack_frame.stack_pointer.as_u64();
+    let addr = sp
+        .checked_sub(RED_ZONE + size_of::<SignalFrame>() as u64)
+        .and_then(|addr| (addr & !0xf).checked_sub(8))
+        .ok_or(SyscallError::BadAddress)?;
+    let handler = user_addr(handler as u64)?;
+    let stack = user_addr(addr)?;
+
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            &frame as *const SignalFrame as *const u8,
+            size_of::<SignalFrame>(),
+        )
+    };
+    // `copy_to_user` may need the process to grow its stack
+    drop(inner);
+    copy_to_user(addr as usize, bytes)?;
+
+    let mut inner = proc.write();
+    let signals = inner.signals_mut();
+    signals.blocked = signals.blocked.union(action.mask).union(sig.into());
+    signals.blocked.remove(Signal::Kill);
+
+    context.enter_handler(handler, stack, sig.number());
+
+    Ok(())
+}
+
+/// `addr` as a canonical address in user space
+fn user_addr(addr: u64) -> SyscallResult<VirtAddr> {
+    if addr >= USER_SPACE_END {
+        return Err(SyscallError::BadAddress);
+    }
+    VirtAddr::try_new(addr).map_err(|_| SyscallError::BadAddress)
+}
+
+/// Restore the context saved by `enter_handler`
+///
+/// The handler returned to the restorer, which popped the return address,
+/// so the frame starts right below the current stack pointer.
+pub(super) fn sigreturn(proc: &Process, context: &mut ProcessContext) -> SyscallResult<()> {
+    let addr = context
+        .stack_frame
+        .stack_pointer
+        .as_u64()
+        .checked_sub(8)
+        .ok_or(SyscallError::BadAddress)?;
+    let frame: SignalFrame = read_user(addr as usize)?;
+
+    let mut saved = frame.context;
+    if saved.stack_frame.instruction_pointer.as_u64() >= USER_SPACE_END
+        || saved.stack_frame.stack_pointer.as_u64() >= USER_SPACE_END
+    {
+        return Err(SyscallError::BadAddress);
+    }
+    // never let a forged frame return to kernel mode
+    let selector = get_user_selector();
+    saved.set_user_segments(selector.user_code_selector, selector.user_data_selector);
+    saved.set_user_flags(context.stack_frame.cpu_flags);
+
+    let mut inner = proc.write();
+    let signals = inner.signals_mut();
+    signals.blocked = frame.blocked;
+    signals.blocked.remove(Signal::Kill);
+
+    *context = saved;
+    Ok(())
+}
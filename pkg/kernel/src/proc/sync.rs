@@ -70,6 +70,11 @@ impl Semaphore {
             return SemaphoreResult::WakeUp(pid);
         }
     }
+
+    /// Stop waiting for the semaphore, e.g. when `pid` exits
+    pub fn cancel(&mut self, pid: ProcessId) {
+        self.wait_queue.retain(|&p| p != pid);
+    }
 }
 
 #[derive(Debug, Default)]
@@ -122,6 +127,13 @@ impl SemaphoreSet {
             SemaphoreResult::NotExist
         }
     }
+
+    /// Remove `pid` from the wait queues of all semaphores
+    pub fn cancel(&self, pid: ProcessId) {
+        for sem in self.sems.values() {
+            sem.lock().cancel(pid);
+        }
+    }
 }
 
 impl core::fmt::Display for Semaphore {
@@ -2,7 +2,7 @@ use core::sync::atomic::{AtomicU64, Ordering};
 
 use alloc::sync::Arc;
 use x86_64::{
-    structures::paging::{mapper::UnmapError, Page},
+    structures::paging::{mapper::UnmapError, page::PageRangeInclusive, Page},
     VirtAddr,
 };
 
@@ -42,13 +42,35 @@ impl Heap {
         }
     }
 
-    pub fn fork(&self) -> Self {
+    /// Share the heap with a vforked child
+    pub fn vfork(&self) -> Self {
         Self {
             base: self.base,
             end: self.end.clone(),
         }
     }
 
+    /// Copy the heap range for a forked child, the pages are copied on write
+    pub fn fork(&self) -> Self {
+        Self {
+            base: self.base,
+            end: Arc::new(AtomicU64::new(self.end.load(Ordering::Acquire))),
+        }
+    }
+
+    /// Mapped pages of the heap, `None` if the heap is empty
+    pub fn range(&self) -> Option<PageRangeInclusive> {
+        let end = self.end.load(Ordering::Relaxed);
+        if end == self.base.as_u64() {
+            return None;
+        }
+
+        Some(Page::range_inclusive(
+            Page::containing_address(self.base),
+            Page::containing_address(VirtAddr::new(end)),
+        ))
+    }
+
     pub fn brk(
         &self,
         new_end: Option<VirtAddr>,
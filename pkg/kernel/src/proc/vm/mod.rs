@@ -1,15 +1,16 @@
-use alloc::{format, vec::Vec};
+use alloc::{format, string::String, vec::Vec};
+use core::ptr::copy_nonoverlapping;
 use boot::KernelPages;
 use x86_64::{
     structures::paging::{
-        mapper::{CleanUp, UnmapError},
+        mapper::{CleanUp, MapToError, MappedFrame, TranslateResult, UnmapError},
         page::*,
         *,
     },
     VirtAddr,
 };
 use xmas_elf::ElfFile;
-use crate::{humanized_size, memory::*};
+use crate::{humanized_size, memory::{user::UserHeap, *}};
 
 pub mod heap;
 pub mod stack;
@@ -24,8 +25,38 @@ use super::PageTableContext;
 // use boot::KernelPages;
 
 type MapperRef<'a> = &'a mut OffsetPageTable<'static>;
+
+/// Software bit of a read-only page shared by `fork`, copied on write
+pub const COW_FLAG: PageTableFlags = PageTableFlags::BIT_9;
+
+/// Flags of page tables created for user pages
+const TABLE_FLAGS: PageTableFlags = PageTableFlags::PRESENT
+    .union(PageTableFlags::WRITABLE)
+    .union(PageTableFlags::USER_ACCESSIBLE);
 type FrameAllocatorRef<'a> = &'a mut BootInfoFrameAllocator;
 
+/// argv and envp laid out on the init stack of a new process
+///
+/// Both vectors are arrays of `[ptr, len]` pairs pointing to UTF-8 strings.
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessArgs {
+    pub stack_top: VirtAddr,
+    pub argc: usize,
+    pub argv: VirtAddr,
+    pub envc: usize,
+    pub envp: VirtAddr,
+}
+
+/// A mapped range of user pages, listed in `/proc/<pid>/maps`
+#[derive(Debug, Clone, Copy)]
+pub struct VmArea {
+    pub start: VirtAddr,
+    pub end: VirtAddr,
+    /// flags of the first page, ignoring copy-on-write
+    pub flags: PageTableFlags,
+    pub name: &'static str,
+}
+
 pub struct ProcessVm {
     // page table is shared by parent and child
     pub(super) page_table: PageTableContext,
@@ -36,8 +67,11 @@ pub struct ProcessVm {
     // heap is allocated by brk syscall
     pub(super) heap: Heap,
 
-    // code is hold by the first process
-    // these fields will be empty for other processes
+    // heap of the allocate syscall, mapped on the first allocation
+    pub(super) user_heap: UserHeap,
+
+    // code is shared by every process using this page table,
+    // and freed by the last one
     pub(super) code: Vec<PageRangeInclusive>,
     pub(super) code_usage: u64,
 }
@@ -48,6 +82,7 @@ impl ProcessVm {
             page_table,
             stack: Stack::empty(),
             heap: Heap::empty(),
+            user_heap: UserHeap::empty(),
             code: Vec::new(),
             code_usage: 0,
         }
@@ -101,6 +136,15 @@ impl ProcessVm {
         )
     }
 
+    /// The heap of the allocate syscall, mapped if this is its first use
+    pub fn user_heap(&self) -> Result<UserHeap, MapToError<Size4KiB>> {
+        self.user_heap.init(
+            &mut self.page_table.mapper(),
+            &mut *get_frame_alloc_for_sure(),
+        )?;
+        Ok(self.user_heap.vfork())
+    }
+
     pub fn load_elf(&mut self, elf: &ElfFile) {
         let mapper = &mut self.page_table.mapper();
 
@@ -110,6 +154,67 @@ impl ProcessVm {
         self.stack.init(mapper, alloc);
     }
 
+    /// Copy argv and envp to the top of the init stack
+    ///
+    /// NOTE: the caller should check the size with `stack_args_size`
+    pub fn init_args(&self, args: &[String], envs: &[String]) -> ProcessArgs {
+        debug_assert!(stack::stack_args_size(args, envs) <= stack::STACK_ARGS_MAX);
+
+        let mut sp = stack::STACK_MAX;
+        let mut pairs = Vec::with_capacity(args.len() + envs.len());
+
+        for s in args.iter().chain(envs) {
+            sp -= s.len() as u64;
+            self.write_bytes(VirtAddr::new(sp), s.as_bytes());
+            pairs.push([sp, s.len() as u64]);
+        }
+
+        sp &= !0xf;
+        sp -= pairs.len() as u64 * 16;
+
+        let bytes: Vec<u8> = pairs
+            .iter()
+            .flat_map(|pair| pair.iter().flat_map(|v| v.to_ne_bytes()))
+            .collect();
+        self.write_bytes(VirtAddr::new(sp), &bytes);
+
+        let argv = VirtAddr::new(sp);
+        let envp = argv + args.len() as u64 * 16;
+
+        ProcessArgs {
+            // leave space for the return address, just like `STACK_INIT_TOP`
+            stack_top: argv - 8u64,
+            argc: args.len(),
+            argv,
+            envc: envs.len(),
+            envp,
+        }
+    }
+
+    /// Write to memory of this vm, which may not be the active one
+    fn write_bytes(&self, addr: VirtAddr, data: &[u8]) {
+        let mapper = self.page_table.mapper();
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let virt = addr + offset as u64;
+            let phys = mapper
+                .translate_addr(virt)
+                .expect("Writing to unmapped user memory");
+            let len = (data.len() - offset).min((PAGE_SIZE - u64::from(virt.page_offset())) as usize);
+
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    data[offset..].as_ptr(),
+                    physical_to_virtual(phys.as_u64()) as *mut u8,
+                    len,
+                );
+            }
+
+            offset += len;
+        }
+    }
+
     fn load_elf_code(&mut self, elf: &ElfFile, mapper: MapperRef, alloc: FrameAllocatorRef) {
         // DONE: make the `load_elf` function return the code pages
         // DONE: calculate code usage
@@ -126,7 +231,7 @@ impl ProcessVm {
         }
     }
 
-    pub fn fork(&self, stack_offset_count: u64) -> Self {
+    pub fn vfork(&self, stack_offset_count: u64) -> Self {
         let owned_page_table = self.page_table.fork();
         let mapper = &mut owned_page_table.mapper();
 
@@ -135,11 +240,112 @@ impl ProcessVm {
         Self {
             page_table: owned_page_table,
             stack: self.stack.vfork(mapper, alloc, stack_offset_count),
-            heap: self.heap.fork(),
+            heap: self.heap.vfork(),
+            user_heap: self.user_heap.vfork(),
 
-            // do not share code info
-            code: Vec::new(),
-            code_usage: 0,
+            // keep the code ranges, so that a fork of the child can copy them
+            code: self.code.clone(),
+            code_usage: self.code_usage,
+        }
+    }
+
+    /// Share the address space with a new thread, which gets its own stack
+    pub fn thread(&self, slot: u64) -> Self {
+        let page_table = self.page_table.fork();
+        let stack = Stack::thread(
+            &mut page_table.mapper(),
+            &mut get_frame_alloc_for_sure(),
+            slot,
+        );
+
+        Self {
+            page_table,
+            stack,
+            heap: self.heap.vfork(),
+            user_heap: self.user_heap.vfork(),
+            code: self.code.clone(),
+            code_usage: self.code_usage,
+        }
+    }
+
+    /// Whether `other` uses the same page table, e.g. a thread or a vforked child
+    pub fn shares_with(&self, other: &ProcessVm) -> bool {
+        self.page_table.shares_with(&other.page_table)
+    }
+
+    /// Clone the address space, sharing code, stack and both heaps copy-on-write
+    ///
+    /// NOTE: this vm must be the active one, as the TLB is flushed after
+    ///       the writable pages are made read-only
+    pub fn fork(&self) -> Self {
+        let page_table = self.page_table.clone_level_4();
+        let stack = self.stack.fork();
+        let heap = self.heap.fork();
+        let user_heap = self.user_heap.fork();
+
+        let stack_range = stack.range();
+        let mut ranges = self.code.clone();
+        if !stack_range.is_empty() {
+            ranges.push(Page::range_inclusive(stack_range.start, stack_range.end - 1));
+        }
+        ranges.extend(heap.range());
+        ranges.extend(user_heap.range());
+
+        // the copied level 4 table still points to the private tables of
+        // the parent, drop them and map the pages again one by one
+        let p4 = unsafe {
+            &mut *(physical_to_virtual(page_table.reg.addr.start_address().as_u64())
+                as *mut PageTable)
+        };
+        for range in ranges.iter() {
+            for index in u16::from(range.start.p4_index())..=u16::from(range.end.p4_index()) {
+                p4[index as usize].set_unused();
+            }
+        }
+
+        let parent = &mut self.page_table.mapper();
+        let child = &mut page_table.mapper();
+        let alloc = &mut *get_frame_alloc_for_sure();
+
+        for page in ranges.iter().flat_map(|range| range.into_iter()) {
+            let (frame, mut flags) = match parent.translate(page.start_address()) {
+                TranslateResult::Mapped {
+                    frame: MappedFrame::Size4KiB(frame),
+                    flags,
+                    ..
+                } => (frame, flags),
+                _ => continue,
+            };
+
+            if flags.contains(PageTableFlags::WRITABLE) {
+                flags.remove(PageTableFlags::WRITABLE);
+                flags.insert(COW_FLAG);
+                unsafe {
+                    parent
+                        .update_flags(page, flags)
+                        .expect("Failed to share page with child")
+                        .ignore();
+                }
+            }
+
+            alloc.share_frame(frame);
+            unsafe {
+                child
+                    .map_to_with_table_flags(page, frame, flags, TABLE_FLAGS, alloc)
+                    .expect("Failed to map page for child")
+                    .ignore();
+            }
+        }
+
+        x86_64::instructions::tlb::flush_all();
+
+        Self {
+            page_table,
+            stack,
+            heap,
+            user_heap,
+            code: self.code.clone(),
+            code_usage: self.code_usage,
         }
     }
 
@@ -150,8 +356,117 @@ impl ProcessVm {
         self.stack.handle_page_fault(addr, mapper, alloc)
     }
 
+    /// Handle a write to a copy-on-write page
+    ///
+    /// The last owner of the frame takes it over, others get a private copy.
+    pub fn handle_cow_fault(&mut self, addr: VirtAddr) -> bool {
+        let mapper = &mut self.page_table.mapper();
+        let alloc = &mut *get_frame_alloc_for_sure();
+
+        let page = Page::<Size4KiB>::containing_address(addr);
+        let (frame, flags) = match mapper.translate(addr) {
+            TranslateResult::Mapped {
+                frame: MappedFrame::Size4KiB(frame),
+                flags,
+                ..
+            } if flags.contains(COW_FLAG) => (frame, flags),
+            _ => return false,
+        };
+        let flags = (flags | PageTableFlags::WRITABLE) - COW_FLAG;
+
+        if alloc.frame_ref_count(frame) == 1 {
+            trace!("COW: reuse {:?} for {:#x}", frame, addr);
+            return match unsafe { mapper.update_flags(page, flags) } {
+                Ok(flush) => {
+                    flush.flush();
+                    true
+                }
+                Err(err) => {
+                    error!("COW: failed to update flags: {:?}", err);
+                    false
+                }
+            };
+        }
+
+        let Some(new_frame) = alloc.allocate_frame() else {
+            error!("COW: out of memory at {:#x}", addr);
+            return false;
+        };
+        trace!("COW: copy {:?} to {:?} for {:#x}", frame, new_frame, addr);
+
+        unsafe {
+            copy_nonoverlapping(
+                physical_to_virtual(frame.start_address().as_u64()) as *const u8,
+                physical_to_virtual(new_frame.start_address().as_u64()) as *mut u8,
+                Size4KiB::SIZE as usize,
+            );
+
+            match mapper.unmap(page) {
+                Ok((_, flush)) => flush.ignore(),
+                Err(err) => {
+                    error!("COW: failed to unmap {:#x}: {:?}", addr, err);
+                    alloc.deallocate_frame(new_frame);
+                    return false;
+                }
+            }
+
+            match mapper.map_to_with_table_flags(page, new_frame, flags, TABLE_FLAGS, alloc) {
+                Ok(flush) => flush.flush(),
+                Err(err) => {
+                    error!("COW: failed to map {:#x}: {:?}", addr, err);
+                    return false;
+                }
+            }
+
+            // drop the reference of this page
+            alloc.deallocate_frame(frame);
+        }
+
+        true
+    }
+
     pub(super) fn memory_usage(&self) -> u64 {
-        self.stack.memory_usage() + self.heap.memory_usage() + self.code_usage
+        self.stack.memory_usage() + self.heap_usage() + self.code_usage
+    }
+
+    fn heap_usage(&self) -> u64 {
+        self.heap.memory_usage() + self.user_heap.memory_usage()
+    }
+
+    /// Memory used by code, stack and heap, in bytes
+    pub fn usage(&self) -> (u64, u64, u64) {
+        (self.code_usage, self.stack.memory_usage(), self.heap_usage())
+    }
+
+    /// Mapped ranges of code, heap and stack, ordered by address
+    pub fn areas(&self) -> Vec<VmArea> {
+        let mapper = self.page_table.mapper();
+        let flags_of = |page: Page| match mapper.translate(page.start_address()) {
+            TranslateResult::Mapped { flags, .. } if flags.contains(COW_FLAG) => {
+                (flags | PageTableFlags::WRITABLE) - COW_FLAG
+            }
+            TranslateResult::Mapped { flags, .. } => flags,
+            _ => PageTableFlags::empty(),
+        };
+
+        let mut ranges: Vec<(PageRangeInclusive, &'static str)> =
+            self.code.iter().map(|range| (*range, "code")).collect();
+        ranges.extend(self.heap.range().map(|range| (range, "heap")));
+        let stack = self.stack.range();
+        if !stack.is_empty() {
+            ranges.push((Page::range_inclusive(stack.start, stack.end - 1), "stack"));
+        }
+        ranges.sort_by_key(|(range, _)| range.start);
+
+        ranges
+            .into_iter()
+            .map(|(range, name)| VmArea {
+                start: range.start.start_address(),
+                end: range.end.start_address() + range.end.size(),
+                flags: flags_of(range.start),
+                name,
+            })
+            .collect()
     }
 
     pub(super) fn clean_up(&mut self) -> Result<(), UnmapError> {
@@ -165,6 +480,7 @@ impl ProcessVm {
             // free heap
             // FIXME: implement the `clean_up` function for `Heap`
             self.heap.clean_up(mapper, dealloc)?;
+            self.user_heap.clean_up(mapper, dealloc)?;
 
             // free code
             for page_range in self.code.iter() {
@@ -196,6 +512,7 @@ impl core::fmt::Debug for ProcessVm {
         f.debug_struct("ProcessVm")
             .field("stack", &self.stack)
             .field("heap", &self.heap)
+            .field("user_heap", &self.user_heap)
             .field("memory_usage", &format!("{} {}", size, unit))
             .field("page_table", &self.page_table)
             .finish()
@@ -30,6 +30,10 @@ pub const STACK_DEF_SIZE: u64 = STACK_DEF_PAGE * crate::memory::PAGE_SIZE;
 pub const STACK_INIT_BOT: u64 = STACK_MAX - STACK_DEF_SIZE;
 pub const STACK_INIT_TOP: u64 = STACK_MAX - 8;
 
+// argv and envp are copied to the top of the init stack,
+// leave at least half of the init stack to the process
+pub const STACK_ARGS_MAX: u64 = STACK_DEF_SIZE / 2;
+
 const STACK_INIT_TOP_PAGE: Page<Size4KiB> = Page::containing_address(VirtAddr::new(STACK_INIT_TOP));
 
 // [bot..0xffffff0100000000..top..0xffffff01ffffffff]
@@ -45,6 +49,13 @@ pub const KSTACK_INIT_TOP: u64 = KSTACK_MAX - 8;
 const KSTACK_INIT_PAGE: Page<Size4KiB> = Page::containing_address(VirtAddr::new(KSTACK_INIT_BOT));
 const KSTACK_INIT_TOP_PAGE: Page<Size4KiB> = Page::containing_address(VirtAddr::new(KSTACK_INIT_TOP));
 
+/// Bytes taken by argv and envp on the init stack, see `ProcessVm::init_args`
+pub fn stack_args_size(args: &[String], envs: &[String]) -> u64 {
+    let strings: usize = args.iter().chain(envs).map(|s| s.len()).sum();
+    // strings, alignment, (ptr, len) pairs and the fake return address
+    strings as u64 + 15 + (args.len() + envs.len()) as u64 * 16 + 8
+}
+
 pub struct Stack {
     range: PageRange<Size4KiB>,
     usage: u64,
@@ -114,18 +125,25 @@ impl Stack {
         offset
     }
 
+    /// Keep the same stack range in a forked child, the pages are copied on write
+    pub fn fork(&self) -> Self {
+        Self {
+            range: self.range,
+            usage: self.usage,
+        }
+    }
+
+    pub fn range(&self) -> PageRange<Size4KiB> {
+        self.range
+    }
+
     pub fn vfork(&self, mapper: MapperRef, alloc: FrameAllocatorRef, stack_offset: u64) -> Self {
-        let mut new_stack_base = self.range.start.start_address().as_u64() - stack_offset * STACK_MAX_SIZE;
-        while elf::map_pages(
-            new_stack_base,
+        let new_stack_base = map_free_slot(
+            self.range.start.start_address().as_u64() - stack_offset * STACK_MAX_SIZE,
             self.usage,
             mapper,
             alloc,
-            true,
-        )
-        .is_err() {
-            new_stack_base -= STACK_MAX_SIZE;
-        }
+        );
         debug!("Map new stack: {:#x}", new_stack_base);
         unsafe {
             copy_nonoverlapping(
@@ -147,6 +165,23 @@ impl Stack {
         }
     }
 
+    /// Map the init stack of a new thread in a free slot, starting from `slot`
+    pub fn thread(mapper: MapperRef, alloc: FrameAllocatorRef, slot: u64) -> Self {
+        let base = map_free_slot(STACK_INIT_BOT - slot * STACK_MAX_SIZE, STACK_DEF_PAGE, mapper, alloc);
+        debug!("Map thread stack: {:#x}", base);
+
+        let start = Page::containing_address(VirtAddr::new(base));
+        Self {
+            range: Page::range(start, start + STACK_DEF_PAGE),
+            usage: STACK_DEF_PAGE,
+        }
+    }
+
+    /// Initial stack pointer, just like `STACK_INIT_TOP` in its own slot
+    pub fn init_top(&self) -> VirtAddr {
+        self.range.end.start_address() - 8u64
+    }
+
     pub fn handle_page_fault(
         &mut self,
         addr: VirtAddr,
@@ -222,3 +257,11 @@ impl core::fmt::Debug for Stack {
             .finish()
     }
 }
+
+/// Map `pages` at `base`, moving down one slot at a time until the pages are free
+fn map_free_slot(mut base: u64, pages: u64, mapper: MapperRef, alloc: FrameAllocatorRef) -> u64 {
+    while elf::map_pages(base, pages, mapper, alloc, true).is_err() {
+        base -= STACK_MAX_SIZE;
+    }
+    base
+}
@@ -0,0 +1,198 @@
+//! Symmetric multiprocessing
+//!
+//! The BSP copies the trampoline to low memory and wakes every other
+//! processor with INIT-SIPI-SIPI. Each AP switches to long mode on a
+//! temporary page table, loads the kernel page table, its own GDT, TSS
+//! and LAPIC timer, then idles until the scheduler gives it a process.
+
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use x86_64::registers::control::Cr3;
+use x86_64::structures::paging::mapper::CleanUp;
+use x86_64::structures::paging::*;
+use x86_64::{PhysAddr, VirtAddr};
+
+use crate::interrupt;
+use crate::memory::{PAGE_SIZE, gdt, get_frame_alloc_for_sure, physical_to_virtual};
+use crate::proc::PageTableContext;
+use crate::proc::processor::{self, MAX_CPU_COUNT};
+use crate::utils::clock;
+use core::time::Duration;
+
+/// Physical address the trampoline is copied to, below 1 MiB
+pub const TRAMPOLINE_ADDR: u64 = 0x8000;
+
+/// Stack of an AP, it idles on the same stack
+const AP_STACK_SIZE: usize = 0x4000;
+
+/// How long the BSP waits for the APs to start
+const STARTUP_TIMEOUT: Duration = Duration::from_millis(100);
+
+core::arch::global_asm!(
+    include_str!("trampoline.S"),
+    base = const TRAMPOLINE_ADDR,
+    count = const MAX_CPU_COUNT - 1,
+    options(att_syntax)
+);
+
+unsafe extern "C" {
+    static ap_trampoline_start: u8;
+    static ap_trampoline_end: u8;
+    static ap_trampoline_cr3: u8;
+    static ap_trampoline_entry: u8;
+    static ap_trampoline_started: u8;
+    static ap_trampoline_stacks: u8;
+}
+
+/// Page table the APs switch to once they run the kernel
+static KERNEL_CR3: AtomicU64 = AtomicU64::new(0);
+
+/// APs that have finished `ap_main`
+static AP_READY: AtomicUsize = AtomicUsize::new(0);
+
+/// Start the application processors
+///
+/// Called on the BSP with interrupts enabled, the timer is used to
+/// wait between the startup IPIs.
+pub fn init(boot_info: &'static boot::BootInfo) {
+    processor::set_idle_stack(gdt::alloc_stack(AP_STACK_SIZE));
+
+    let page = boot_info.memory_map.iter().find(|r| {
+        r.phys_start <= TRAMPOLINE_ADDR
+            && TRAMPOLINE_ADDR < r.phys_start + r.page_count * PAGE_SIZE
+    });
+    if page.is_none_or(|r| r.ty != boot::MemoryType::CONVENTIONAL) {
+        warn!("SMP: {:#x} is not free, APs are not started", TRAMPOLINE_ADDR);
+        return;
+    }
+
+    let started = unsafe { start_aps() };
+    info!("SMP: {} CPUs online ({} APs started)", processor::online_count(), started);
+}
+
+/// Offset of `symbol` in the trampoline
+fn offset_of(symbol: *const u8) -> usize {
+    symbol as usize - (&raw const ap_trampoline_start) as usize
+}
+
+/// Write `value` to the slot `symbol` of the copied trampoline
+unsafe fn set_slot(symbol: *const u8, index: usize, value: u64) {
+    let slot = physical_to_virtual(TRAMPOLINE_ADDR) as usize + offset_of(symbol) + index * 8;
+    unsafe { core::ptr::write_volatile(slot as *mut u64, value) };
+}
+
+unsafe fn read_slot(symbol: *const u8) -> u64 {
+    let slot = physical_to_virtual(TRAMPOLINE_ADDR) as usize + offset_of(symbol);
+    unsafe { core::ptr::read_volatile(slot as *const u64) }
+}
+
+/// Copy the trampoline, wake every AP and wait for them
+///
+/// Returns how many APs have started.
+unsafe fn start_aps() -> usize {
+    let kernel = PageTableContext::new();
+    KERNEL_CR3.store(kernel.reg.addr.start_address().as_u64(), Ordering::Relaxed);
+
+    // the low half of the kernel page table is left by UEFI,
+    // map the trampoline in a copy to make sure it is executable
+    let boot_table = kernel.clone_level_4();
+    let mut mapper = boot_table.mapper();
+    mapper.level_4_table_mut()[0].set_unused();
+
+    let page = Page::<Size4KiB>::containing_address(VirtAddr::new(TRAMPOLINE_ADDR));
+    let frame = PhysFrame::containing_address(PhysAddr::new(TRAMPOLINE_ADDR));
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+    unsafe {
+        mapper
+            .map_to(page, frame, flags, &mut *get_frame_alloc_for_sure())
+            .expect("Failed to map the AP trampoline")
+            .ignore();
+    }
+
+    let cr3 = boot_table.reg.addr.start_address().as_u64();
+    assert!(cr3 < 0x1_0000_0000, "AP page table must be below 4 GiB");
+
+    unsafe {
+        let start = &raw const ap_trampoline_start;
+        let len = (&raw const ap_trampoline_end) as usize - start as usize;
+        assert!(len as u64 <= PAGE_SIZE, "AP trampoline is too large");
+        core::ptr::copy_nonoverlapping(start, physical_to_virtual(TRAMPOLINE_ADDR) as *mut u8, len);
+
+        set_slot(&raw const ap_trampoline_cr3, 0, cr3);
+        set_slot(&raw const ap_trampoline_entry, 0, ap_main as usize as u64);
+        set_slot(&raw const ap_trampoline_started, 0, 0);
+        for i in 0..MAX_CPU_COUNT - 1 {
+            // the stack top is aligned, `call` pushes the return address
+            set_slot(&raw const ap_trampoline_stacks, i, gdt::alloc_stack(AP_STACK_SIZE).as_u64());
+        }
+    }
+
+    // INIT, then two startup IPIs to every other processor
+    const ALL_BUT_SELF: u64 = 0b11 << 18;
+    const LEVEL_ASSERT: u64 = 1 << 14;
+    const INIT: u64 = 0b101 << 8;
+    const STARTUP: u64 = 0b110 << 8;
+
+    interrupt::send_ipi(ALL_BUT_SELF | LEVEL_ASSERT | INIT);
+    wait(Duration::from_millis(10));
+    for _ in 0..2 {
+        interrupt::send_ipi(ALL_BUT_SELF | LEVEL_ASSERT | STARTUP | (TRAMPOLINE_ADDR >> 12));
+        wait(Duration::from_millis(1));
+    }
+    wait(STARTUP_TIMEOUT);
+
+    // every AP that took a stack must leave the boot page table first
+    let started = (unsafe { read_slot(&raw const ap_trampoline_started) } as usize)
+        .min(MAX_CPU_COUNT - 1);
+    while AP_READY.load(Ordering::Acquire) < started {
+        core::hint::spin_loop();
+    }
+
+    unsafe {
+        let mut frame_alloc = get_frame_alloc_for_sure();
+        mapper.unmap(page).expect("Failed to unmap the AP trampoline").1.ignore();
+        mapper.clean_up_addr_range(Page::range_inclusive(page, page), &mut *frame_alloc);
+        frame_alloc.deallocate_frame(boot_table.reg.addr);
+    }
+
+    started
+}
+
+/// Wait for `duration` with the timer
+fn wait(duration: Duration) {
+    // round up, the current tick is partly over
+    let deadline = clock::ticks() + clock::ticks_for(duration) + 1;
+    while clock::ticks() < deadline {
+        x86_64::instructions::hlt();
+    }
+}
+
+/// Entry of an AP, called by the trampoline on the stack `stack_top`
+extern "C" fn ap_main(stack_top: u64) -> ! {
+    unsafe {
+        let frame = PhysFrame::containing_address(PhysAddr::new(KERNEL_CR3.load(Ordering::Relaxed)));
+        Cr3::write(frame, Cr3::read().1);
+    }
+
+    let cpu = processor::cpu_id();
+    if cpu >= MAX_CPU_COUNT {
+        warn!("SMP: CPU {} is not supported, halt", cpu);
+        AP_READY.fetch_add(1, Ordering::Release);
+        loop {
+            x86_64::instructions::interrupts::disable();
+            x86_64::instructions::hlt();
+        }
+    }
+
+    gdt::init_ap();
+    interrupt::init_ap();
+
+    // the stack of `ap_main` is reused by the idle loop
+    processor::set_idle_stack(VirtAddr::new(stack_top));
+
+    info!("SMP: CPU {} started", cpu);
+    AP_READY.fetch_add(1, Ordering::Release);
+
+    loop {
+        x86_64::instructions::interrupts::enable_and_hlt();
+    }
+}
@@ -0,0 +1,68 @@
+//! Monotonic time counted by the timer interrupt
+
+use chrono::{DateTime, NaiveDate, Utc};
+use core::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use core::time::Duration;
+
+/// Used until the LAPIC timer is calibrated
+const DEFAULT_TICK_NS: u64 = 10_000_000;
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+static TICK_NS: AtomicU64 = AtomicU64::new(DEFAULT_TICK_NS);
+
+/// Unix time when the ticks started, read from the real time clock
+static BOOT_TIME: AtomicI64 = AtomicI64::new(0);
+
+/// Set the period of the timer interrupt
+pub fn set_tick_ns(ns: u64) {
+    info!("Timer tick: {}.{:03} ms", ns / 1_000_000, ns / 1_000 % 1_000);
+    TICK_NS.store(ns.max(1), Ordering::Relaxed);
+}
+
+/// Count a timer interrupt, returns the ticks since boot
+pub fn tick() -> u64 {
+    TICKS.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// Timer interrupts since boot
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Time since boot, in the resolution of a tick
+pub fn now() -> Duration {
+    Duration::from_nanos(ticks() * TICK_NS.load(Ordering::Relaxed))
+}
+
+/// Ticks needed for `duration` to pass, rounded up
+pub fn ticks_for(duration: Duration) -> u64 {
+    let tick_ns = TICK_NS.load(Ordering::Relaxed) as u128;
+    duration.as_nanos().div_ceil(tick_ns) as u64
+}
+
+/// Read the real time clock through UEFI runtime services
+///
+/// The clock is taken as UTC, `wall_time` stays at the epoch on failure.
+pub fn init_wall_clock() {
+    let Ok(time) = uefi::runtime::get_time() else {
+        warn!("Failed to read the real time clock");
+        return;
+    };
+
+    let unix = NaiveDate::from_ymd_opt(time.year() as i32, time.month() as u32, time.day() as u32)
+        .and_then(|date| {
+            date.and_hms_opt(time.hour() as u32, time.minute() as u32, time.second() as u32)
+        })
+        .map(|datetime| datetime.and_utc().timestamp());
+
+    if let Some(unix) = unix {
+        BOOT_TIME.store(unix - now().as_secs() as i64, Ordering::Relaxed);
+        info!("Wall clock: {}", wall_time());
+    }
+}
+
+/// The current date and time
+pub fn wall_time() -> DateTime<Utc> {
+    let boot = DateTime::from_timestamp(BOOT_TIME.load(Ordering::Relaxed), 0).unwrap_or_default();
+    boot + now()
+}
@@ -3,7 +3,7 @@ mod macros;
 #[macro_use]
 mod regs;
 
-// pub mod clock;
+pub mod clock;
 pub mod func;
 pub mod logger;
 pub mod resource;
@@ -1,7 +1,8 @@
 use crate::drivers::input::*;
-use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use alloc::{collections::BTreeMap, string::String, sync::Arc};
 use spin::Mutex;
 use storage::common::FileHandle;
+use syscall_def::{SyscallError, SyscallResult};
 
 #[derive(Debug, Clone)]
 pub enum StdIO {
@@ -10,59 +11,51 @@ pub enum StdIO {
     Stderr,
 }
 
-#[derive(Debug)]
+/// File descriptor table
+///
+/// Cloning it gives a new table whose descriptors refer to the same
+/// open resources, like the table a forked process inherits.
+#[derive(Debug, Clone)]
 pub struct ResourceSet {
-    pub handles: BTreeMap<u8, Mutex<Resource>>,
-    recycled: Vec<u8>,
+    pub handles: BTreeMap<u8, Arc<Mutex<Resource>>>,
 }
 
 impl Default for ResourceSet {
     fn default() -> Self {
         let mut res = Self {
             handles: BTreeMap::new(),
-            recycled: Vec::new(),
         };
 
-        res.open(Resource::Console(StdIO::Stdin));
-        res.open(Resource::Console(StdIO::Stdout));
-        res.open(Resource::Console(StdIO::Stderr));
+        for stdio in [StdIO::Stdin, StdIO::Stdout, StdIO::Stderr] {
+            let _ = res.open(Resource::Console(stdio));
+        }
 
         res
     }
 }
 
 impl ResourceSet {
-    pub fn open(&mut self, res: Resource) -> u8 {
-        let fd = match self.recycled.pop() {
-            Some(fd) => fd,
-            None => self.handles.len() as u8,
-        };
-        self.handles.insert(fd, Mutex::new(res));
-        fd
+    /// Add `res` at the lowest free descriptor
+    pub fn open(&mut self, res: Resource) -> SyscallResult<u8> {
+        let fd = (0..=u8::MAX)
+            .find(|fd| !self.handles.contains_key(fd))
+            .ok_or(SyscallError::TooManyFiles)?;
+        self.handles.insert(fd, Arc::new(Mutex::new(res)));
+        Ok(fd)
     }
 
     pub fn close(&mut self, fd: u8) -> bool {
-        match self.handles.remove(&fd) {
-            Some(_) => {
-                self.recycled.push(fd);
-                true
-            }
-            None => false,
-        }
+        self.handles.remove(&fd).is_some()
     }
 
-    pub fn read(&self, fd: u8, buf: &mut [u8]) -> isize {
-        match self.handles.get(&fd).and_then(|h| h.lock().read(buf)) {
-            Some(count) => count as isize,
-            None => -1,
-        }
+    pub fn read(&self, fd: u8, buf: &mut [u8]) -> SyscallResult {
+        let handle = self.handles.get(&fd).ok_or(SyscallError::BadFd)?;
+        handle.lock().read(buf).ok_or(SyscallError::IoError)
     }
 
-    pub fn write(&self, fd: u8, buf: &[u8]) -> isize {
-        match self.handles.get(&fd).and_then(|h| h.lock().write(buf)) {
-            Some(count) => count as isize,
-            None => -1,
-        }
+    pub fn write(&self, fd: u8, buf: &[u8]) -> SyscallResult {
+        let handle = self.handles.get(&fd).ok_or(SyscallError::BadFd)?;
+        handle.lock().write(buf).ok_or(SyscallError::IoError)
     }
 }
 
@@ -114,16 +107,30 @@ impl Resource {
                     Some(buf.len())
                 }
             },
-            Resource::File(_file_handle) => {
-                // 文件写入暂不实现，直接忽略
-                warn!("File write not implemented");
-                None
+            Resource::File(file_handle) => match file_handle.write(buf) {
+                Ok(bytes_written) => Some(bytes_written),
+                Err(e) => {
+                    warn!("File write error: {:?}", e);
+                    None
+                }
             },
             Resource::Null => Some(buf.len()),
         }
     }
 }
 
+impl core::fmt::Display for Resource {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Resource::Console(StdIO::Stdin) => write!(f, "stdin"),
+            Resource::Console(StdIO::Stdout) => write!(f, "stdout"),
+            Resource::Console(StdIO::Stderr) => write!(f, "stderr"),
+            Resource::File(file_handle) => write!(f, "file:{}", file_handle.meta.name),
+            Resource::Null => write!(f, "null"),
+        }
+    }
+}
+
 impl core::fmt::Debug for Resource {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
@@ -0,0 +1,57 @@
+use alloc::vec::Vec;
+use spin::Once;
+
+static ARGS: Once<Vec<&'static str>> = Once::new();
+static ENVS: Once<Vec<(&'static str, &'static str)>> = Once::new();
+
+/// Collect `count` strings laid out by the kernel as `[ptr, len]` pairs
+unsafe fn collect(ptr: *const [usize; 2], count: usize) -> impl Iterator<Item = &'static str> {
+    (0..count).map(move |i| unsafe {
+        let [ptr, len] = *ptr.add(i);
+        core::str::from_utf8_unchecked(core::slice::from_raw_parts(ptr as *const u8, len))
+    })
+}
+
+/// Save argv and envp passed to `_start` on the init stack
+#[doc(hidden)]
+pub unsafe fn init(argc: usize, argv: *const [usize; 2], envc: usize, envp: *const [usize; 2]) {
+    ARGS.call_once(|| unsafe { collect(argv, argc) }.collect());
+    ENVS.call_once(|| {
+        unsafe { collect(envp, envc) }
+            .filter_map(|env| env.split_once('='))
+            .collect()
+    });
+}
+
+/// Arguments of the current process, `args()[0]` is the program name
+pub fn args() -> &'static [&'static str] {
+    ARGS.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// Get the value of the environment variable `key`
+pub fn env(key: &str) -> Option<&'static str> {
+    envs().find(|(k, _)| *k == key).map(|(_, v)| v)
+}
+
+/// All environment variables of the current process
+pub fn envs() -> impl Iterator<Item = (&'static str, &'static str)> {
+    ENVS.get().into_iter().flatten().copied()
+}
+
+/// `main` functions accepted by `entry!`, with or without `args`
+#[doc(hidden)]
+pub trait EntryMain<Marker> {
+    fn call_main(self) -> isize;
+}
+
+impl<F: FnOnce() -> isize> EntryMain<()> for F {
+    fn call_main(self) -> isize {
+        self()
+    }
+}
+
+impl<F: FnOnce(&'static [&'static str]) -> isize> EntryMain<&'static [&'static str]> for F {
+    fn call_main(self) -> isize {
+        self(args())
+    }
+}
@@ -42,7 +42,7 @@ impl Stdin {
     }
 
     fn try_read_key_with_buf(&self, buf: &mut [u8]) -> Option<u8> {
-        if let Some(bytes) = sys_read(0, buf) {
+        if let Ok(bytes) = sys_read(0, buf) {
             if bytes == 1 {
                 return Some(buf[0]);
             }
@@ -100,6 +100,17 @@ impl Stdin {
         string
     }
 
+    /// Read a key if one has been typed, without waiting
+    pub fn try_read_key(&self) -> Option<char> {
+        let mut buf = [0];
+        let ch = self.try_read_key_with_buf(&mut buf)?;
+        if Self::is_utf8(ch) {
+            char::from_u32(self.to_utf8(ch))
+        } else {
+            Some(ch as char)
+        }
+    }
+
     pub fn read_key(&self) -> Option<char> {
         let ch = self.pop_key();
         if Self::is_utf8(ch) {
@@ -120,7 +131,7 @@ impl Stdout {
 
     pub fn flush(&mut self) {
         if !self.buffer.is_empty() {
-            sys_write(1, self.buffer.as_bytes());
+            let _ = sys_write(1, self.buffer.as_bytes());
             self.buffer.clear();
         }
     }
@@ -149,7 +160,7 @@ impl Stderr {
     }
 
     pub fn write(&self, s: &str) {
-        sys_write(2, s.as_bytes());
+        let _ = sys_write(2, s.as_bytes());
     }
 }
 
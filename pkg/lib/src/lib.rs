@@ -1,5 +1,6 @@
 #![allow(dead_code, unused_imports)]
 #![feature(alloc_error_handler)]
+#![feature(naked_functions)]
 #![cfg_attr(not(test), no_std)]
 
 #[macro_use]
@@ -11,7 +12,10 @@ extern crate syscall_def;
 #[macro_use]
 pub mod io;
 pub mod allocator;
+pub mod env;
+pub mod signal;
 pub mod sync;
+pub mod thread;
 pub extern crate alloc;
 
 mod syscall;
@@ -20,9 +24,14 @@ use core::fmt::*;
 
 pub use alloc::*;
 pub use chrono::*;
+pub use env::{args, env, envs};
 pub use io::*;
 pub use sync::*;
 pub use syscall::*;
+pub use syscall_def::{SyscallError, SyscallResult};
+pub use syscall_def::wait::{ExitStatus, WAIT_ANY, WNOHANG};
+pub use syscall_def::proc::{ProcInfo, ProcStatus};
+pub use syscall_def::fs::{FsType, MOUNT_READ_ONLY, O_APPEND, O_CREATE, O_TRUNC};
 
 pub fn init() {
     #[cfg(feature = "brk_alloc")]
@@ -51,6 +60,21 @@ macro_rules! errln {
     ($($arg:tt)*) => ($crate::err!("{}\n", format_args!($($arg)*)));
 }
 
+/// Print every process, from `sys_proc_info`
+pub fn print_processes() {
+    println!("{:>5} {:>5} {:<16} {:>2} {:>8}", "PID", "PPID", "NAME", "ST", "MEM");
+    for info in sys_proc_info() {
+        println!(
+            "{:>5} {:>5} {:<16} {:>2} {:>5} kB",
+            info.pid,
+            info.ppid,
+            info.name(),
+            info.status,
+            info.memory() / 1024
+        );
+    }
+}
+
 #[doc(hidden)]
 pub fn _print(args: Arguments) {
     use core::fmt::Write;
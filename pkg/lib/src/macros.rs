@@ -4,10 +4,17 @@ use alloc::string::ToString;
 #[macro_export]
 macro_rules! entry {
     ($fn:ident) => {
+        // not `pub`, only the loader calls it with pointers it has set up
         #[unsafe(export_name = "_start")]
-        pub extern "C" fn __impl_start() {
+        extern "C" fn __impl_start(
+            argc: usize,
+            argv: *const [usize; 2],
+            envc: usize,
+            envp: *const [usize; 2],
+        ) {
             lib::init(); // THIS LINE IS NEW IN LAB 7
-            let ret = $fn();
+            unsafe { lib::env::init(argc, argv, envc, envp) };
+            let ret = lib::env::EntryMain::call_main($fn);
             lib::sys_exit(ret);
         }
     };
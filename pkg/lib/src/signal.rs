@@ -0,0 +1,79 @@
+//! Signal handlers, the numbers are defined in `syscall_def::signal`
+
+use syscall_def::Syscall;
+
+use crate::*;
+pub use syscall_def::signal::*;
+
+/// What to do when a signal arrives
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SigHandler {
+    /// take the default action, e.g. terminate on `SIGTERM`
+    Default,
+    Ignore,
+    /// call the function with the signal number
+    Handler(extern "C" fn(usize)),
+}
+
+impl SigHandler {
+    fn from_raw(handler: usize) -> Self {
+        match handler {
+            SIG_DFL => SigHandler::Default,
+            SIG_IGN => SigHandler::Ignore,
+            f => SigHandler::Handler(unsafe { core::mem::transmute::<usize, extern "C" fn(usize)>(f) }),
+        }
+    }
+
+    fn as_raw(self) -> usize {
+        match self {
+            SigHandler::Default => SIG_DFL,
+            SigHandler::Ignore => SIG_IGN,
+            SigHandler::Handler(f) => f as usize,
+        }
+    }
+}
+
+/// Set the handler of `sig`, blocking `mask` while the handler runs
+///
+/// Returns the old handler.
+pub fn sigaction(sig: Signal, handler: SigHandler, mask: SigSet) -> SyscallResult<SigHandler> {
+    sys_sigaction(sig.number(), handler.as_raw(), mask, restorer as usize).map(SigHandler::from_raw)
+}
+
+/// Set the handler of `sig`, returns the old handler
+pub fn signal(sig: Signal, handler: SigHandler) -> SyscallResult<SigHandler> {
+    sigaction(sig, handler, SigSet::empty())
+}
+
+/// Send `sig` to process `pid`
+pub fn kill(pid: u16, sig: Signal) -> SyscallResult<()> {
+    sys_kill(pid, sig.number())
+}
+
+/// Handlers return here, the signal frame starts right below `rsp`
+#[cfg(feature = "fast_syscall")]
+#[naked]
+unsafe extern "C" fn restorer() {
+    unsafe {
+        core::arch::naked_asm!(
+            "mov rax, {sigreturn}",
+            "syscall",
+            "ud2",
+            sigreturn = const Syscall::SigReturn as usize,
+        );
+    }
+}
+
+/// Handlers return here, the signal frame starts right below `rsp`
+#[cfg(not(feature = "fast_syscall"))]
+#[naked]
+unsafe extern "C" fn restorer() {
+    unsafe {
+        core::arch::naked_asm!(
+            "mov rax, {sigreturn}",
+            "int 0x80",
+            "ud2",
+            sigreturn = const Syscall::SigReturn as usize,
+        );
+    }
+}
@@ -45,21 +45,21 @@ impl Semaphore {
 
     #[inline(always)]
     pub fn init(&self, value: usize) -> bool {
-        sys_new_sem(self.key, value)
+        sys_new_sem(self.key, value).is_ok()
     }
 
     /* DONE: other functions with syscall... */
     #[inline(always)]
     pub fn wait(&self) -> bool {
-        sys_sem_wait(self.key)
+        sys_sem_wait(self.key).is_ok()
     }
     #[inline(always)]
     pub fn signal(&self) -> bool {
-        sys_sem_signal(self.key)
+        sys_sem_signal(self.key).is_ok()
     }
     #[inline(always)]
     pub fn free(&self) -> bool {
-        sys_sem_free(self.key)
+        sys_sem_free(self.key).is_ok()
     }
 }
 
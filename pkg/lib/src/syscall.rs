@@ -1,63 +1,162 @@
-use syscall_def::Syscall;
+use alloc::vec::Vec;
+use chrono::Duration;
+use syscall_def::fs::FsType;
+use syscall_def::proc::ProcInfo;
+use syscall_def::sched::decode_nice;
+use syscall_def::signal::SigSet;
+use syscall_def::wait::{ExitStatus, decode_wait};
+use syscall_def::{Syscall, SyscallError, SyscallResult, decode_result};
 
 #[inline(always)]
-pub fn sys_write(fd: u8, buf: &[u8]) -> Option<usize> {
-    let ret = syscall!(
+pub fn sys_write(fd: u8, buf: &[u8]) -> SyscallResult {
+    decode_result(syscall!(
         Syscall::Write,
         fd as u64,
         buf.as_ptr() as u64,
         buf.len() as u64
-    ) as isize;
-    if ret.is_negative() {
-        None
-    } else {
-        Some(ret as usize)
-    }
+    ))
 }
 
 #[inline(always)]
-pub fn sys_read(fd: u8, buf: &mut [u8]) -> Option<usize> {
-    let ret = syscall!(
+pub fn sys_read(fd: u8, buf: &mut [u8]) -> SyscallResult {
+    decode_result(syscall!(
         Syscall::Read,
         fd as u64,
         buf.as_ptr() as u64,
         buf.len() as u64
-    ) as isize;
-    if ret.is_negative() {
-        None
-    } else {
-        Some(ret as usize)
-    }
+    ))
+}
+
+/// Wait for the process to exit and return how it exited
+#[inline(always)]
+pub fn sys_wait_pid(pid: u16) -> SyscallResult<ExitStatus> {
+    decode_result(syscall!(Syscall::WaitPid, pid as u64, 0)).map(|ret| decode_wait(ret).1)
+}
+
+/// Wait for `pid`, or any child with `WAIT_ANY`, and reap it
+///
+/// Returns `None` if `WNOHANG` is set in `options` and nothing has exited.
+#[inline(always)]
+pub fn sys_waitpid(pid: isize, options: usize) -> SyscallResult<Option<(u16, ExitStatus)>> {
+    decode_result(syscall!(Syscall::WaitPid, pid as u64, options as u64))
+        .map(|ret| (ret != 0).then(|| decode_wait(ret)))
+}
+
+/// Start a thread running `entry(arg)`, see `thread::spawn`
+#[inline(always)]
+pub fn sys_thread_create(entry: extern "C" fn(usize) -> !, arg: usize) -> SyscallResult<u16> {
+    decode_result(syscall!(Syscall::ThreadCreate, entry as usize, arg)).map(|tid| tid as u16)
 }
 
+/// Wait for a thread of this process to exit and get how it exited
 #[inline(always)]
-pub fn sys_wait_pid(pid: u16) -> isize {
-    syscall!(Syscall::WaitPid, pid as u64) as isize
+pub fn sys_thread_join(tid: u16) -> SyscallResult<ExitStatus> {
+    decode_result(syscall!(Syscall::ThreadJoin, tid as u64)).map(|ret| decode_wait(ret).1)
 }
 
+/// Set the nice value of `pid` (0 = current process), returns the value
+/// actually set after clamping into `[NICE_MIN, NICE_MAX]`
 #[inline(always)]
-pub fn sys_list_app() {
-    syscall!(Syscall::ListApp);
+pub fn sys_set_priority(pid: u16, nice: isize) -> SyscallResult<isize> {
+    decode_result(syscall!(Syscall::SetPriority, pid as u64, nice as u64)).map(decode_nice)
 }
 
+/// Add `inc` to the nice value of the current process and return the new one
 #[inline(always)]
-pub fn sys_stat() {
-    syscall!(Syscall::Stat);
+pub fn sys_nice(inc: isize) -> SyscallResult<isize> {
+    decode_result(syscall!(Syscall::Nice, inc as u64)).map(decode_nice)
 }
 
+/// Give up the CPU to other ready processes
 #[inline(always)]
-pub fn sys_allocate(layout: &core::alloc::Layout) -> *mut u8 {
-    syscall!(Syscall::Allocate, layout as *const _) as *mut u8
+pub fn sys_yield() {
+    syscall!(Syscall::Yield);
 }
 
+/// Block the current process for at least `duration`
 #[inline(always)]
-pub fn sys_deallocate(ptr: *mut u8, layout: &core::alloc::Layout) -> usize {
-    syscall!(Syscall::Deallocate, ptr, layout as *const _)
+pub fn sys_sleep(duration: Duration) {
+    let ns = duration.num_nanoseconds().unwrap_or(i64::MAX).max(0);
+    syscall!(Syscall::Sleep, ns as u64);
+}
+
+/// Monotonic time since boot, in the resolution of a timer tick
+#[inline(always)]
+pub fn sys_time() -> Duration {
+    Duration::nanoseconds(syscall!(Syscall::Time) as i64)
+}
+
+/// Records of every process, ordered by pid
+pub fn sys_proc_info() -> Vec<ProcInfo> {
+    let mut infos = Vec::new();
+    loop {
+        // leave room for processes created in between
+        let len = infos.len().max(16);
+        infos.resize(len, ProcInfo::default());
+
+        let count = syscall!(Syscall::ProcInfo, infos.as_mut_ptr(), len);
+        let count = decode_result(count).unwrap_or(0);
+        if count <= len {
+            infos.truncate(count);
+            return infos;
+        }
+        infos.resize(count + 4, ProcInfo::default());
+    }
 }
 
 #[inline(always)]
-pub fn sys_spawn(path: &str) -> u16 {
-    syscall!(Syscall::Spawn, path.as_ptr() as u64, path.len() as u64) as u16
+pub fn sys_allocate(layout: &core::alloc::Layout) -> SyscallResult<*mut u8> {
+    // `Layout` has no stable memory layout, pass it as `[size, align]`
+    let layout = [layout.size(), layout.align()];
+    decode_result(syscall!(Syscall::Allocate, layout.as_ptr())).map(|ptr| ptr as *mut u8)
+}
+
+#[inline(always)]
+pub fn sys_deallocate(ptr: *mut u8, layout: &core::alloc::Layout) -> SyscallResult<()> {
+    let layout = [layout.size(), layout.align()];
+    decode_result(syscall!(Syscall::Deallocate, ptr, layout.as_ptr())).map(|_| ())
+}
+
+/// Spawn `path` with arguments and `KEY=VALUE` environment variables
+///
+/// `args` defaults to `[path]` if empty.
+#[inline(always)]
+pub fn sys_spawn(path: &str, args: &[&str], envs: &[&str]) -> SyscallResult<u16> {
+    let argv: Vec<[usize; 2]> = args.iter().map(|s| [s.as_ptr() as usize, s.len()]).collect();
+    let envp: Vec<[usize; 2]> = envs.iter().map(|s| [s.as_ptr() as usize, s.len()]).collect();
+
+    decode_result(syscall!(
+        Syscall::Spawn,
+        path.as_ptr() as u64,
+        path.len() as u64,
+        argv.as_ptr() as u64,
+        argv.len() as u64,
+        envp.as_ptr() as u64,
+        envp.len() as u64
+    ))
+    .map(|pid| pid as u16)
+}
+
+/// Replace the current program with `path`, keeping the pid and open files
+///
+/// Only returns if the program can not be executed.
+#[inline(always)]
+pub fn sys_exec(path: &str, args: &[&str], envs: &[&str]) -> SyscallError {
+    let argv: Vec<[usize; 2]> = args.iter().map(|s| [s.as_ptr() as usize, s.len()]).collect();
+    let envp: Vec<[usize; 2]> = envs.iter().map(|s| [s.as_ptr() as usize, s.len()]).collect();
+
+    match decode_result(syscall!(
+        Syscall::Exec,
+        path.as_ptr() as u64,
+        path.len() as u64,
+        argv.as_ptr() as u64,
+        argv.len() as u64,
+        envp.as_ptr() as u64,
+        envp.len() as u64
+    )) {
+        Ok(_) => unreachable!("This process should be replaced by now."),
+        Err(err) => err,
+    }
 }
 
 #[inline(always)]
@@ -71,99 +170,176 @@ pub fn sys_exit(code: isize) -> ! {
     unreachable!("This process should be terminated by now.");
 }
 
+/// Send signal `sig` to `pid`, signal `0` only checks that `pid` exists
+#[inline(always)]
+pub fn sys_kill(pid: u16, sig: usize) -> SyscallResult<()> {
+    decode_result(syscall!(Syscall::Kill, pid as u64, sig)).map(|_| ())
+}
+
+/// Set the handler of `sig`, returns the old handler
+///
+/// `restorer` is where the handler returns to, see `signal::sigaction`.
 #[inline(always)]
-pub fn sys_kill(pid: u16) {
-    syscall!(Syscall::Kill, pid as u64);
+pub fn sys_sigaction(sig: usize, handler: usize, mask: SigSet, restorer: usize) -> SyscallResult {
+    decode_result(syscall!(Syscall::SigAction, sig, handler, mask.0, restorer))
 }
 
+/// Change the blocked signals, `how` is one of `SIG_BLOCK`, `SIG_UNBLOCK`
+/// and `SIG_SETMASK`, returns the old set
 #[inline(always)]
-pub fn sys_fork() -> u16 {
-    syscall!(Syscall::VFork) as u16
+pub fn sys_sigprocmask(how: usize, set: SigSet) -> SyscallResult<SigSet> {
+    decode_result(syscall!(Syscall::SigProcMask, how, set.0)).map(|old| SigSet(old as u64))
 }
 
+/// Fork the current process, the address space is copied on write
+///
+/// The heap of either allocator is copied too, the child gets its own fds
+/// and environment, but open files are shared with the parent.
 #[inline(always)]
-pub fn sys_new_sem(key: u32, value: usize) -> bool {
-    syscall!(Syscall::Sem, 0, key as u64, value) == 0
+pub fn sys_fork() -> SyscallResult<u16> {
+    decode_result(syscall!(Syscall::Fork)).map(|pid| pid as u16)
 }
 
+/// Fork the current process, sharing the address space with the child
+///
+/// The child gets its own stack, globals and the heap are shared.
 #[inline(always)]
-pub fn sys_sem_wait(key: u32) -> bool {
-    syscall!(Syscall::Sem, 1, key as u64) == 0
+pub fn sys_vfork() -> SyscallResult<u16> {
+    decode_result(syscall!(Syscall::VFork)).map(|pid| pid as u16)
 }
 
 #[inline(always)]
-pub fn sys_sem_signal(key: u32) -> bool {
-    syscall!(Syscall::Sem, 2, key as u64) == 0
+pub fn sys_new_sem(key: u32, value: usize) -> SyscallResult<()> {
+    decode_result(syscall!(Syscall::Sem, 0, key as u64, value)).map(|_| ())
 }
 
 #[inline(always)]
-pub fn sys_sem_free(key: u32) -> bool {
-    syscall!(Syscall::Sem, 3, key as u64) == 0
+pub fn sys_sem_wait(key: u32) -> SyscallResult<()> {
+    decode_result(syscall!(Syscall::Sem, 1, key as u64)).map(|_| ())
 }
 
 #[inline(always)]
-pub fn list_dir(path: &str) -> Result<(), &'static str> {
-    let ret = syscall!(
+pub fn sys_sem_signal(key: u32) -> SyscallResult<()> {
+    decode_result(syscall!(Syscall::Sem, 2, key as u64)).map(|_| ())
+}
+
+#[inline(always)]
+pub fn sys_sem_free(key: u32) -> SyscallResult<()> {
+    decode_result(syscall!(Syscall::Sem, 3, key as u64)).map(|_| ())
+}
+
+#[inline(always)]
+pub fn list_dir(path: &str) -> SyscallResult<()> {
+    decode_result(syscall!(
         Syscall::ListDir,
         path.as_ptr() as u64,
         path.len() as u64
-    ) as usize;
-    
-    match ret {
-        0 => Ok(()),
-        1 => Err("Null path pointer"),
-        2 => Err("Empty path"),
-        3 => Err("Path too long"),
-        4 => Err("Invalid UTF-8 in path"),
-        5 => Err("Empty path string"),
-        _ => Err("Unknown error"),
-    }
+    ))
+    .map(|_| ())
 }
 
-/// 打开文件
+/// 打开文件，`flags` 为 `O_CREATE`、`O_TRUNC` 和 `O_APPEND` 的组合
 #[inline(always)]
-pub fn open(path: &str) -> Result<u8, &'static str> {
-    let ret = syscall!(
+pub fn sys_open(path: &str, flags: usize) -> SyscallResult<u8> {
+    decode_result(syscall!(
         Syscall::Open,
         path.as_ptr() as u64,
-        path.len() as u64
-    ) as usize;
-    
-    if ret == usize::MAX {
-        Err("Failed to open file")
-    } else {
-        Ok(ret as u8)
-    }
+        path.len() as u64,
+        flags
+    ))
+    .map(|fd| fd as u8)
+}
+
+/// 以只读方式打开文件
+#[inline(always)]
+pub fn open(path: &str) -> SyscallResult<u8> {
+    sys_open(path, 0)
 }
 
 /// 关闭文件描述符
 #[inline(always)]
-pub fn close(fd: u8) -> Result<(), &'static str> {
-    let ret = syscall!(Syscall::Close, fd as u64) as usize;
-    
-    if ret == 0 {
-        Ok(())
-    } else {
-        Err("Failed to close file")
-    }
+pub fn close(fd: u8) -> SyscallResult<()> {
+    decode_result(syscall!(Syscall::Close, fd as u64)).map(|_| ())
+}
+
+/// 创建目录
+#[inline(always)]
+pub fn sys_mkdir(path: &str) -> SyscallResult<()> {
+    decode_result(syscall!(Syscall::Mkdir, path.as_ptr() as u64, path.len() as u64)).map(|_| ())
+}
+
+/// 删除空目录
+#[inline(always)]
+pub fn sys_rmdir(path: &str) -> SyscallResult<()> {
+    decode_result(syscall!(Syscall::Rmdir, path.as_ptr() as u64, path.len() as u64)).map(|_| ())
+}
+
+/// 删除文件
+#[inline(always)]
+pub fn sys_unlink(path: &str) -> SyscallResult<()> {
+    decode_result(syscall!(Syscall::Unlink, path.as_ptr() as u64, path.len() as u64)).map(|_| ())
+}
+
+/// 移动或重命名文件和目录，`dst` 必须不存在且与 `src` 在同一文件系统中
+#[inline(always)]
+pub fn sys_rename(src: &str, dst: &str) -> SyscallResult<()> {
+    decode_result(syscall!(
+        Syscall::Rename,
+        src.as_ptr() as u64,
+        src.len() as u64,
+        dst.as_ptr() as u64,
+        dst.len() as u64
+    ))
+    .map(|_| ())
+}
+
+/// 挂载文件系统，`flags` 可以包含 `MOUNT_READ_ONLY`
+#[inline(always)]
+pub fn sys_mount(source: &str, target: &str, fs_type: FsType, flags: usize) -> SyscallResult<()> {
+    decode_result(syscall!(
+        Syscall::Mount,
+        source.as_ptr() as u64,
+        source.len() as u64,
+        target.as_ptr() as u64,
+        target.len() as u64,
+        fs_type as usize,
+        flags
+    ))
+    .map(|_| ())
+}
+
+/// 将缓存中尚未写回的块写入磁盘
+#[inline(always)]
+pub fn sys_sync() -> SyscallResult<()> {
+    decode_result(syscall!(Syscall::Sync)).map(|_| ())
+}
+
+/// 卸载挂载在 `target` 的文件系统
+#[inline(always)]
+pub fn sys_umount(target: &str) -> SyscallResult<()> {
+    decode_result(syscall!(
+        Syscall::Umount,
+        target.as_ptr() as u64,
+        target.len() as u64
+    ))
+    .map(|_| ())
 }
 
 /// 从文件描述符读取数据
 #[inline(always)]
-pub fn read(fd: u8, buf: &mut [u8]) -> isize {
-    syscall!(
-        Syscall::Read,
-        fd as u64,
-        buf.as_mut_ptr() as u64,
-        buf.len() as u64
-    ) as isize
+pub fn read(fd: u8, buf: &mut [u8]) -> SyscallResult {
+    sys_read(fd, buf)
 }
 
+/// Enable or disable syscall tracing for `pid` (0 = current process)
+///
+/// The flag is inherited by processes spawned or forked afterwards.
 #[inline(always)]
-pub fn sys_brk(addr: Option<usize>) -> Option<usize> {
-    const BRK_FAILED: usize = !0;
-    match syscall!(Syscall::Brk, addr.unwrap_or(0)) {
-        BRK_FAILED => None,
-        ret => Some(ret),
-    }
-}
\ No newline at end of file
+pub fn sys_trace(pid: u16, enable: bool) -> SyscallResult<()> {
+    decode_result(syscall!(Syscall::Trace, pid as u64, enable as u64)).map(|_| ())
+}
+
+#[inline(always)]
+pub fn sys_brk(addr: Option<usize>) -> SyscallResult {
+    decode_result(syscall!(Syscall::Brk, addr.unwrap_or(0)))
+}
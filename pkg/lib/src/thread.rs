@@ -0,0 +1,45 @@
+use alloc::boxed::Box;
+
+use crate::*;
+
+/// Handle of a thread created by `spawn`
+#[derive(Debug)]
+pub struct JoinHandle {
+    tid: u16,
+}
+
+impl JoinHandle {
+    pub fn tid(&self) -> u16 {
+        self.tid
+    }
+
+    /// Wait for the thread to exit, `Exited` holds the value returned by it
+    pub fn join(self) -> SyscallResult<ExitStatus> {
+        sys_thread_join(self.tid)
+    }
+}
+
+/// Run `f` in a new thread sharing the address space of this process
+pub fn spawn<F>(f: F) -> SyscallResult<JoinHandle>
+where
+    F: FnOnce() -> isize + Send + 'static,
+{
+    let f = Box::into_raw(Box::new(f));
+
+    match sys_thread_create(thread_start::<F>, f as usize) {
+        Ok(tid) => Ok(JoinHandle { tid }),
+        Err(err) => {
+            // the thread is not started, take the closure back
+            drop(unsafe { Box::from_raw(f) });
+            Err(err)
+        }
+    }
+}
+
+extern "C" fn thread_start<F>(f: usize) -> !
+where
+    F: FnOnce() -> isize + Send + 'static,
+{
+    let f = unsafe { Box::from_raw(f as *mut F) };
+    sys_exit(f())
+}
@@ -0,0 +1,242 @@
+//! LRU Block Cache
+//!
+//! Keeps recently used blocks of a device in memory. Writes are only
+//! applied to the cached copy and reach the device when the block is
+//! evicted or on `sync`.
+
+use super::*;
+use core::num::NonZeroUsize;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lru::LruCache;
+
+/// A cached block, `dirty` if it has not been written back yet
+struct CachedBlock<B> {
+    block: B,
+    dirty: bool,
+}
+
+/// Counters of a [`BlockCache`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Reads served from the cache
+    pub hits: usize,
+    /// Reads that went to the device
+    pub misses: usize,
+    /// Dirty blocks written to the device
+    pub writebacks: usize,
+    /// Blocks currently cached
+    pub cached: usize,
+    /// Cached blocks not written back yet
+    pub dirty: usize,
+    /// Maximum number of cached blocks
+    pub capacity: usize,
+}
+
+/// A write-back LRU cache in front of another block device
+///
+/// Dirty blocks are also written back when the cache is dropped.
+pub struct BlockCache<B: BlockTrait, D: BlockDevice<B>> {
+    inner: D,
+    blocks: spin::Mutex<LruCache<usize, CachedBlock<B>>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    writebacks: AtomicUsize,
+}
+
+impl<B: BlockTrait, D: BlockDevice<B>> BlockCache<B, D> {
+    /// Cache up to `capacity` blocks of `inner`, at least one
+    pub fn new(inner: D, capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            inner,
+            blocks: spin::Mutex::new(LruCache::new(capacity)),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            writebacks: AtomicUsize::new(0),
+        }
+    }
+
+    /// The device behind the cache, writing to it bypasses the cache
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// Write every dirty block back to the device
+    pub fn sync(&self) -> FsResult {
+        let mut blocks = self.blocks.lock();
+        for (offset, cached) in blocks.iter_mut().filter(|(_, cached)| cached.dirty) {
+            self.inner.write_block(*offset, &cached.block)?;
+            cached.dirty = false;
+            self.writebacks.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let blocks = self.blocks.lock();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            writebacks: self.writebacks.load(Ordering::Relaxed),
+            cached: blocks.len(),
+            dirty: blocks.iter().filter(|(_, cached)| cached.dirty).count(),
+            capacity: blocks.cap().get(),
+        }
+    }
+
+    /// Cache a block, writing back the least recently used one if it has to
+    /// be evicted and is dirty
+    fn insert(
+        &self,
+        blocks: &mut LruCache<usize, CachedBlock<B>>,
+        offset: usize,
+        cached: CachedBlock<B>,
+    ) -> FsResult {
+        if !blocks.contains(&offset) && blocks.len() == blocks.cap().get() {
+            if let Some((evicted, lru)) = blocks.peek_lru() {
+                if lru.dirty {
+                    // keep the block if the write-back fails, so no data is lost
+                    self.inner.write_block(*evicted, &lru.block)?;
+                    self.writebacks.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        blocks.push(offset, cached);
+        Ok(())
+    }
+}
+
+impl<B: BlockTrait, D: BlockDevice<B>> BlockDevice<B> for BlockCache<B, D> {
+    fn block_count(&self) -> FsResult<usize> {
+        self.inner.block_count()
+    }
+
+    fn read_block(&self, offset: usize, block: &mut B) -> FsResult {
+        let mut blocks = self.blocks.lock();
+        if let Some(cached) = blocks.get(&offset) {
+            block.clone_from(&cached.block);
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        self.inner.read_block(offset, block)?;
+        let cached = CachedBlock {
+            block: block.clone(),
+            dirty: false,
+        };
+        self.insert(&mut blocks, offset, cached)
+    }
+
+    fn write_block(&self, offset: usize, block: &B) -> FsResult {
+        let mut blocks = self.blocks.lock();
+        let cached = CachedBlock {
+            block: block.clone(),
+            dirty: true,
+        };
+        self.insert(&mut blocks, offset, cached)
+    }
+}
+
+impl<B: BlockTrait, D: BlockDevice<B>> Drop for BlockCache<B, D> {
+    fn drop(&mut self) {
+        if let Err(err) = self.sync() {
+            warn!("Failed to write back cached blocks: {:?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A disk kept in memory, counting the blocks read and written
+    #[derive(Default)]
+    struct Disk {
+        blocks: spin::Mutex<Vec<Block512>>,
+        reads: AtomicUsize,
+        writes: AtomicUsize,
+    }
+
+    impl Disk {
+        fn new(count: usize) -> Arc<Self> {
+            let blocks = (0..count)
+                .map(|i| Block512::new(&[i as u8; 512]))
+                .collect();
+            Arc::new(Self {
+                blocks: spin::Mutex::new(blocks),
+                ..Default::default()
+            })
+        }
+
+        fn counts(&self) -> (usize, usize) {
+            (self.reads.load(Ordering::Relaxed), self.writes.load(Ordering::Relaxed))
+        }
+    }
+
+    impl BlockDevice<Block512> for Disk {
+        fn block_count(&self) -> FsResult<usize> {
+            Ok(self.blocks.lock().len())
+        }
+
+        fn read_block(&self, offset: usize, block: &mut Block512) -> FsResult {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+            *block = self.blocks.lock()[offset].clone();
+            Ok(())
+        }
+
+        fn write_block(&self, offset: usize, block: &Block512) -> FsResult {
+            self.writes.fetch_add(1, Ordering::Relaxed);
+            self.blocks.lock()[offset] = block.clone();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn least_recently_used_is_evicted() {
+        let disk = Disk::new(8);
+        let cache = BlockCache::new(disk.clone(), 2);
+        let mut block = Block512::default();
+
+        for offset in [0, 1, 0, 2, 0, 1] {
+            cache.read_block(offset, &mut block).unwrap();
+            assert_eq!(block[0], offset as u8);
+        }
+
+        // 1 was evicted by 2, while 0 stayed cached
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (2, 4));
+        assert_eq!((stats.cached, stats.capacity), (2, 2));
+        assert_eq!(disk.counts(), (4, 0));
+    }
+
+    #[test]
+    fn dirty_blocks_are_written_back() {
+        let disk = Disk::new(8);
+        let cache = BlockCache::new(disk.clone(), 2);
+        let mut block = Block512::default();
+
+        cache.write_block(0, &Block512::new(&[0xAA; 512])).unwrap();
+        cache.write_block(1, &Block512::new(&[0xBB; 512])).unwrap();
+        cache.read_block(0, &mut block).unwrap();
+        assert_eq!(block[0], 0xAA);
+        assert_eq!(disk.counts(), (0, 0));
+        assert_eq!(cache.stats().dirty, 2);
+
+        // reading 2 evicts 1, which is written back first
+        cache.read_block(2, &mut block).unwrap();
+        assert_eq!(disk.counts(), (1, 1));
+        assert_eq!(disk.blocks.lock()[1][0], 0xBB);
+
+        cache.sync().unwrap();
+        cache.sync().unwrap();
+        assert_eq!(disk.counts(), (1, 2));
+        assert_eq!(disk.blocks.lock()[0][0], 0xAA);
+        assert_eq!(cache.stats().writebacks, 2);
+        assert_eq!(cache.stats().dirty, 0);
+
+        cache.write_block(3, &Block512::new(&[0xCC; 512])).unwrap();
+        drop(cache);
+        assert_eq!(disk.blocks.lock()[3][0], 0xCC);
+    }
+}
@@ -19,3 +19,31 @@ where
         B::size()
     }
 }
+
+impl<B: BlockTrait> BlockDevice<B> for Box<dyn BlockDevice<B>> {
+    fn block_count(&self) -> FsResult<usize> {
+        self.as_ref().block_count()
+    }
+
+    fn read_block(&self, offset: usize, block: &mut B) -> FsResult {
+        self.as_ref().read_block(offset, block)
+    }
+
+    fn write_block(&self, offset: usize, block: &B) -> FsResult {
+        self.as_ref().write_block(offset, block)
+    }
+}
+
+impl<B: BlockTrait, T: BlockDevice<B>> BlockDevice<B> for Arc<T> {
+    fn block_count(&self) -> FsResult<usize> {
+        self.as_ref().block_count()
+    }
+
+    fn read_block(&self, offset: usize, block: &mut B) -> FsResult {
+        self.as_ref().read_block(offset, block)
+    }
+
+    fn write_block(&self, offset: usize, block: &B) -> FsResult {
+        self.as_ref().write_block(offset, block)
+    }
+}
@@ -32,6 +32,12 @@ pub enum FsError {
     DeviceError(DeviceError),
     /// Invalid path.
     InvalidPath(String),
+    /// The file already exists.
+    AlreadyExists,
+    /// The file or mount point is in use.
+    Busy,
+    /// The directory is not empty.
+    NotEmpty,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
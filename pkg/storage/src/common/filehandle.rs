@@ -11,6 +11,10 @@ impl FileHandle {
     pub fn new(meta: Metadata, file: Box<dyn FileIO + Send>) -> Self {
         Self { meta, file }
     }
+
+    pub fn into_parts(self) -> (Metadata, Box<dyn FileIO + Send>) {
+        (self.meta, self.file)
+    }
 }
 
 impl Deref for FileHandle {
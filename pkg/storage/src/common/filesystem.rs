@@ -31,13 +31,18 @@ pub trait FileSystem: Debug + Sync + Send {
         Err(FsError::NotSupported)
     }
 
+    /// Creates a directory at this path
+    fn create_dir(&self, _path: &str) -> FsResult {
+        Err(FsError::NotSupported)
+    }
+
     /// Removes the file at this path
-    fn remove_file(&self, _path: &str) -> FsResult<FileHandle> {
+    fn remove_file(&self, _path: &str) -> FsResult {
         Err(FsError::NotSupported)
     }
 
-    /// Removes the directory at this path
-    fn remove_dir(&self, _path: &str) -> FsResult<FileHandle> {
+    /// Removes the empty directory at this path
+    fn remove_dir(&self, _path: &str) -> FsResult {
         Err(FsError::NotSupported)
     }
 
@@ -56,3 +61,54 @@ pub trait FileSystem: Debug + Sync + Send {
         Err(FsError::NotSupported)
     }
 }
+
+/// A filesystem shared with the kernel, e.g. to look up devices
+impl<T: FileSystem> FileSystem for Arc<T> {
+    fn read_dir(&self, path: &str) -> FsResult<Box<dyn Iterator<Item = Metadata> + Send>> {
+        self.as_ref().read_dir(path)
+    }
+
+    fn open_file(&self, path: &str) -> FsResult<FileHandle> {
+        self.as_ref().open_file(path)
+    }
+
+    fn metadata(&self, path: &str) -> FsResult<Metadata> {
+        self.as_ref().metadata(path)
+    }
+
+    fn exists(&self, path: &str) -> FsResult<bool> {
+        self.as_ref().exists(path)
+    }
+
+    fn create_file(&self, path: &str) -> FsResult<FileHandle> {
+        self.as_ref().create_file(path)
+    }
+
+    fn append_file(&self, path: &str) -> FsResult<FileHandle> {
+        self.as_ref().append_file(path)
+    }
+
+    fn create_dir(&self, path: &str) -> FsResult {
+        self.as_ref().create_dir(path)
+    }
+
+    fn remove_file(&self, path: &str) -> FsResult {
+        self.as_ref().remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &str) -> FsResult {
+        self.as_ref().remove_dir(path)
+    }
+
+    fn copy_file(&self, src: &str, dst: &str) -> FsResult {
+        self.as_ref().copy_file(src, dst)
+    }
+
+    fn move_file(&self, src: &str, dst: &str) -> FsResult {
+        self.as_ref().move_file(src, dst)
+    }
+
+    fn move_dir(&self, src: &str, dst: &str) -> FsResult {
+        self.as_ref().move_dir(src, dst)
+    }
+}
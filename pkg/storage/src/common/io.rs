@@ -51,8 +51,13 @@ pub trait Write {
 
     /// Attempts to write an entire buffer into this writer.
     fn write_all(&mut self, mut buf: &[u8]) -> FsResult {
-        // not required for lab
-        todo!()
+        while !buf.is_empty() {
+            match self.write(buf)? {
+                0 => return Err(FsError::WriteZero),
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
     }
 }
 
@@ -3,6 +3,21 @@ use chrono::{DateTime, Utc};
 
 pub type FsTime = DateTime<Utc>;
 
+static CLOCK: spin::Once<fn() -> FsTime> = spin::Once::new();
+
+/// Set the clock used to stamp created and modified files
+pub fn set_clock(clock: fn() -> FsTime) {
+    CLOCK.call_once(|| clock);
+}
+
+/// The time of the clock, or the Unix epoch if no clock is set
+pub fn current_time() -> FsTime {
+    match CLOCK.get() {
+        Some(clock) => clock(),
+        None => DateTime::from_timestamp_millis(0).unwrap(),
+    }
+}
+
 /// Type of file entry
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum FileType {
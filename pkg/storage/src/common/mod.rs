@@ -2,6 +2,7 @@
 mod macros;
 
 mod block;
+mod cache;
 mod device;
 mod error;
 mod filehandle;
@@ -9,10 +10,13 @@ mod filesystem;
 mod io;
 mod metadata;
 mod mount;
+mod path;
+mod vfs;
 
 use super::*;
 
 pub use block::*;
+pub use cache::*;
 pub use device::*;
 pub use error::*;
 pub use filehandle::*;
@@ -20,5 +24,7 @@ pub use filesystem::*;
 pub use io::*;
 pub use metadata::*;
 pub use mount::*;
+pub use path::*;
+pub use vfs::*;
 
 pub const PATH_SEPARATOR: char = '/';
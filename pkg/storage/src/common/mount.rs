@@ -6,17 +6,49 @@ use super::*;
 pub struct Mount {
     pub fs: Box<dyn FileSystem>,
     pub mount_point: Box<str>,
+    /// name of the filesystem and what it is mounted from, e.g. `/dev/hda1`
+    pub source: Box<str>,
+    pub read_only: bool,
 }
 
 impl Mount {
     #[inline]
     pub fn new(fs: Box<dyn FileSystem>, mount_point: Box<str>) -> Self {
-        Self { fs, mount_point }
+        Self {
+            fs,
+            mount_point,
+            source: "none".into(),
+            read_only: false,
+        }
     }
 
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = source.into();
+        self
+    }
+
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Path inside the filesystem, `path` must be normalized and under the mount point
     #[inline]
     fn trim_mount_point<'a>(&self, path: &'a str) -> &'a str {
-        path.trim_start_matches(self.mount_point.as_ref())
+        if self.mount_point.as_ref() == "/" {
+            return path;
+        }
+        match path.strip_prefix(self.mount_point.as_ref()) {
+            Some("") | None => "/",
+            Some(rest) => rest,
+        }
+    }
+
+    fn check_writable(&self) -> FsResult {
+        match self.read_only {
+            true => Err(FsError::ReadOnly),
+            false => Ok(()),
+        }
     }
 }
 
@@ -26,9 +58,14 @@ impl FileSystem for Mount {
         self.fs.read_dir(self.trim_mount_point(path))
     }
 
-    #[inline]
     fn open_file(&self, path: &str) -> FsResult<FileHandle> {
-        self.fs.open_file(self.trim_mount_point(path))
+        let handle = self.fs.open_file(self.trim_mount_point(path))?;
+        if !self.read_only {
+            return Ok(handle);
+        }
+
+        let (meta, file) = handle.into_parts();
+        Ok(FileHandle::new(meta, Box::new(ReadOnlyFile(file))))
     }
 
     #[inline]
@@ -40,13 +77,83 @@ impl FileSystem for Mount {
     fn exists(&self, path: &str) -> FsResult<bool> {
         self.fs.exists(self.trim_mount_point(path))
     }
+
+    fn create_file(&self, path: &str) -> FsResult<FileHandle> {
+        self.check_writable()?;
+        self.fs.create_file(self.trim_mount_point(path))
+    }
+
+    fn append_file(&self, path: &str) -> FsResult<FileHandle> {
+        self.check_writable()?;
+        self.fs.append_file(self.trim_mount_point(path))
+    }
+
+    fn create_dir(&self, path: &str) -> FsResult {
+        self.check_writable()?;
+        self.fs.create_dir(self.trim_mount_point(path))
+    }
+
+    fn remove_file(&self, path: &str) -> FsResult {
+        self.check_writable()?;
+        self.fs.remove_file(self.trim_mount_point(path))
+    }
+
+    fn remove_dir(&self, path: &str) -> FsResult {
+        self.check_writable()?;
+        self.fs.remove_dir(self.trim_mount_point(path))
+    }
+
+    fn copy_file(&self, src: &str, dst: &str) -> FsResult {
+        self.check_writable()?;
+        self.fs
+            .copy_file(self.trim_mount_point(src), self.trim_mount_point(dst))
+    }
+
+    fn move_file(&self, src: &str, dst: &str) -> FsResult {
+        self.check_writable()?;
+        self.fs
+            .move_file(self.trim_mount_point(src), self.trim_mount_point(dst))
+    }
+
+    fn move_dir(&self, src: &str, dst: &str) -> FsResult {
+        self.check_writable()?;
+        self.fs
+            .move_dir(self.trim_mount_point(src), self.trim_mount_point(dst))
+    }
 }
 
 impl core::fmt::Debug for Mount {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("Mount")
             .field("mount_point", &self.mount_point)
+            .field("source", &self.source)
+            .field("read_only", &self.read_only)
             .field("fs", &self.fs)
             .finish()
     }
 }
+
+/// A file opened on a read-only mount
+struct ReadOnlyFile(Box<dyn FileIO + Send>);
+
+impl Read for ReadOnlyFile {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for ReadOnlyFile {
+    fn write(&mut self, _buf: &[u8]) -> FsResult<usize> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn flush(&mut self) -> FsResult {
+        Ok(())
+    }
+}
+
+impl Seek for ReadOnlyFile {
+    fn seek(&mut self, pos: SeekFrom) -> FsResult<usize> {
+        self.0.seek(pos)
+    }
+}
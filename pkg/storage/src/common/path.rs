@@ -0,0 +1,73 @@
+//! Canonical absolute paths
+
+use crate::*;
+
+/// Normalize `path` to an absolute path without `.`, `..` or repeated `/`
+///
+/// A relative path is taken relative to `/`, `..` of the root is the root.
+pub fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split(PATH_SEPARATOR) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+
+    let mut normalized = String::with_capacity(path.len() + 1);
+    for part in parts {
+        normalized.push(PATH_SEPARATOR);
+        normalized.push_str(part);
+    }
+    if normalized.is_empty() {
+        normalized.push(PATH_SEPARATOR);
+    }
+    normalized
+}
+
+/// Split a normalized path into its parent and the last component
+///
+/// The root has no parent.
+pub fn split_parent(path: &str) -> Option<(&str, &str)> {
+    let (parent, name) = path.rsplit_once(PATH_SEPARATOR)?;
+    if name.is_empty() {
+        return None;
+    }
+    Some((if parent.is_empty() { "/" } else { parent }, name))
+}
+
+/// Whether the normalized `path` is `dir` or inside it
+pub fn is_under(path: &str, dir: &str) -> bool {
+    if dir == "/" {
+        return true;
+    }
+    path.strip_prefix(dir)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(PATH_SEPARATOR))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(normalize(""), "/");
+        assert_eq!(normalize("/"), "/");
+        assert_eq!(normalize("//APP///hello"), "/APP/hello");
+        assert_eq!(normalize("/proc/./1/../self/"), "/proc/self");
+        assert_eq!(normalize("/../.."), "/");
+        assert_eq!(normalize("dev/hda"), "/dev/hda");
+
+        assert_eq!(split_parent("/"), None);
+        assert_eq!(split_parent("/dev"), Some(("/", "dev")));
+        assert_eq!(split_parent("/dev/hda1"), Some(("/dev", "hda1")));
+
+        assert!(is_under("/proc/1", "/proc"));
+        assert!(is_under("/proc", "/proc"));
+        assert!(!is_under("/procfs", "/proc"));
+        assert!(is_under("/APP", "/"));
+    }
+}
@@ -0,0 +1,255 @@
+//! The virtual filesystem, a table of mounted filesystems
+//!
+//! Paths are normalized and resolved to the mount with the longest
+//! matching mount point, e.g. `/proc/1/status` goes to the mount at
+//! `/proc` rather than the one at `/`.
+
+use super::*;
+use spin::RwLock;
+
+#[derive(Debug, Default)]
+pub struct Vfs {
+    mounts: RwLock<Vec<Arc<Mount>>>,
+}
+
+impl Vfs {
+    pub const fn new() -> Self {
+        Self {
+            mounts: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Add `mount` to the table, its mount point is normalized
+    ///
+    /// A device, i.e. a source given as a path, can only be mounted once,
+    /// two filesystems on the same blocks would overwrite each other.
+    pub fn mount(&self, mut mount: Mount) -> FsResult {
+        mount.mount_point = normalize(&mount.mount_point).into();
+
+        let mut mounts = self.mounts.write();
+        if mounts.iter().any(|m| m.mount_point == mount.mount_point) {
+            return Err(FsError::AlreadyExists);
+        }
+        if mount.source.starts_with('/') && mounts.iter().any(|m| m.source == mount.source) {
+            return Err(FsError::Busy);
+        }
+
+        info!("Mounted {} at {}", mount.source, mount.mount_point);
+        mounts.push(Arc::new(mount));
+        Ok(())
+    }
+
+    /// Remove the mount at `mount_point`
+    ///
+    /// Fails with `Busy` if another filesystem is mounted inside it.
+    pub fn umount(&self, mount_point: &str) -> FsResult<Arc<Mount>> {
+        let mount_point = normalize(mount_point);
+
+        let mut mounts = self.mounts.write();
+        let index = mounts
+            .iter()
+            .position(|m| *m.mount_point == mount_point)
+            .ok_or(FsError::FileNotFound)?;
+
+        let nested = mounts
+            .iter()
+            .any(|m| *m.mount_point != mount_point && is_under(&m.mount_point, &mount_point));
+        if nested {
+            return Err(FsError::Busy);
+        }
+
+        info!("Unmounted {}", mount_point);
+        Ok(mounts.remove(index))
+    }
+
+    /// Every mount, in the order they were mounted
+    pub fn mounts(&self) -> Vec<Arc<Mount>> {
+        self.mounts.read().clone()
+    }
+
+    /// The mount `path` belongs to, and the normalized path
+    pub fn resolve(&self, path: &str) -> FsResult<(Arc<Mount>, String)> {
+        let path = normalize(path);
+        let mount = self
+            .mounts
+            .read()
+            .iter()
+            .filter(|m| is_under(&path, &m.mount_point))
+            .max_by_key(|m| m.mount_point.len())
+            .cloned()
+            .ok_or(FsError::FileNotFound)?;
+        Ok((mount, path))
+    }
+
+    /// Names of the mount points directly inside `dir`
+    fn mount_points_in(&self, dir: &str) -> Vec<String> {
+        self.mounts
+            .read()
+            .iter()
+            .filter_map(|m| split_parent(&m.mount_point))
+            .filter(|(parent, _)| *parent == dir)
+            .map(|(_, name)| name.into())
+            .collect()
+    }
+
+    fn is_mount_point(&self, path: &str) -> bool {
+        self.mounts.read().iter().any(|m| *m.mount_point == *path)
+    }
+
+    fn mount_point_meta(path: &str) -> Metadata {
+        let name = split_parent(path).map_or("/", |(_, name)| name);
+        Metadata::new(name.into(), FileType::Directory, 0, None, None, None)
+    }
+
+    /// Resolve two paths, which must be on the same mount
+    fn resolve_pair(&self, src: &str, dst: &str) -> FsResult<(Arc<Mount>, String, String)> {
+        let (mount, src) = self.resolve(src)?;
+        let (other, dst) = self.resolve(dst)?;
+        if !Arc::ptr_eq(&mount, &other) {
+            return Err(FsError::InvalidOperation);
+        }
+        Ok((mount, src, dst))
+    }
+}
+
+impl FileSystem for Vfs {
+    fn read_dir(&self, path: &str) -> FsResult<Box<dyn Iterator<Item = Metadata> + Send>> {
+        let (mount, path) = self.resolve(path)?;
+        let mut entries: Vec<Metadata> = match mount.read_dir(&path) {
+            Ok(iter) => iter.collect(),
+            // a mount point not present in the parent filesystem
+            Err(FsError::FileNotFound) if self.is_mount_point(&path) => Vec::new(),
+            Err(err) => return Err(err),
+        };
+
+        for name in self.mount_points_in(&path) {
+            if !entries.iter().any(|e| e.name == name) {
+                entries.push(Metadata::new(name, FileType::Directory, 0, None, None, None));
+            }
+        }
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn open_file(&self, path: &str) -> FsResult<FileHandle> {
+        let (mount, path) = self.resolve(path)?;
+        mount.open_file(&path)
+    }
+
+    fn metadata(&self, path: &str) -> FsResult<Metadata> {
+        let (mount, path) = self.resolve(path)?;
+        if self.is_mount_point(&path) {
+            return Ok(Self::mount_point_meta(&path));
+        }
+        mount.metadata(&path)
+    }
+
+    fn exists(&self, path: &str) -> FsResult<bool> {
+        let (mount, path) = self.resolve(path)?;
+        Ok(self.is_mount_point(&path) || mount.exists(&path)?)
+    }
+
+    fn create_file(&self, path: &str) -> FsResult<FileHandle> {
+        let (mount, path) = self.resolve(path)?;
+        mount.create_file(&path)
+    }
+
+    fn append_file(&self, path: &str) -> FsResult<FileHandle> {
+        let (mount, path) = self.resolve(path)?;
+        mount.append_file(&path)
+    }
+
+    fn create_dir(&self, path: &str) -> FsResult {
+        let (mount, path) = self.resolve(path)?;
+        if self.is_mount_point(&path) {
+            return Err(FsError::AlreadyExists);
+        }
+        mount.create_dir(&path)
+    }
+
+    fn remove_file(&self, path: &str) -> FsResult {
+        let (mount, path) = self.resolve(path)?;
+        mount.remove_file(&path)
+    }
+
+    fn remove_dir(&self, path: &str) -> FsResult {
+        let (mount, path) = self.resolve(path)?;
+        if self.is_mount_point(&path) {
+            return Err(FsError::Busy);
+        }
+        mount.remove_dir(&path)
+    }
+
+    fn copy_file(&self, src: &str, dst: &str) -> FsResult {
+        let (mount, src, dst) = self.resolve_pair(src, dst)?;
+        mount.copy_file(&src, &dst)
+    }
+
+    fn move_file(&self, src: &str, dst: &str) -> FsResult {
+        let (mount, src, dst) = self.resolve_pair(src, dst)?;
+        mount.move_file(&src, &dst)
+    }
+
+    fn move_dir(&self, src: &str, dst: &str) -> FsResult {
+        let (mount, src, dst) = self.resolve_pair(src, dst)?;
+        if self.is_mount_point(&src) {
+            return Err(FsError::Busy);
+        }
+        mount.move_dir(&src, &dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmpfs::Tmpfs;
+
+    fn read_to_string(fs: &dyn FileSystem, path: &str) -> String {
+        let mut buf = Vec::new();
+        fs.open_file(path).unwrap().read_all(&mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn resolve_longest_mount_point() {
+        let vfs = Vfs::new();
+        vfs.mount(Mount::new(Box::new(Tmpfs::new()), "/".into())).unwrap();
+        vfs.mount(Mount::new(Box::new(Tmpfs::new()), "/tmp/".into())).unwrap();
+        assert_eq!(
+            vfs.mount(Mount::new(Box::new(Tmpfs::new()), "/tmp".into())),
+            Err(FsError::AlreadyExists)
+        );
+
+        vfs.create_file("/tmp//a").unwrap().write(b"in tmp").unwrap();
+        vfs.create_file("/tmpa").unwrap().write(b"in root").unwrap();
+
+        assert_eq!(read_to_string(&vfs, "/tmp/./a"), "in tmp");
+        assert_eq!(read_to_string(&vfs, "/tmp/../tmpa"), "in root");
+        assert!(!vfs.exists("/a").unwrap());
+
+        let names: Vec<String> = vfs.read_dir("/").unwrap().map(|m| m.name).collect();
+        assert_eq!(names, ["tmpa", "tmp"]);
+
+        assert_eq!(vfs.umount("/").err(), Some(FsError::Busy));
+        vfs.umount("/tmp").unwrap();
+        assert!(vfs.open_file("/tmp/a").is_err());
+
+        // a device is only mounted once
+        let dev = |path: &str| Mount::new(Box::new(Tmpfs::new()), path.into()).with_source("/dev/hda1");
+        vfs.mount(dev("/a")).unwrap();
+        assert_eq!(vfs.mount(dev("/b")), Err(FsError::Busy));
+    }
+
+    #[test]
+    fn read_only_mount() {
+        let vfs = Vfs::new();
+        let fs = Tmpfs::new();
+        fs.create_file("/file").unwrap().write(b"data").unwrap();
+        vfs.mount(Mount::new(Box::new(fs), "/".into()).with_read_only(true))
+            .unwrap();
+
+        assert_eq!(vfs.create_file("/new").err(), Some(FsError::ReadOnly));
+        let mut file = vfs.open_file("/file").unwrap();
+        assert_eq!(file.write(b"x"), Err(FsError::ReadOnly));
+        assert_eq!(read_to_string(&vfs, "/file"), "data");
+    }
+}
@@ -18,11 +18,34 @@ pub struct DirEntry {
     pub cluster: Cluster,
     pub attributes: Attributes,
     pub size: u32,
+    /// VFAT long name, stored in the entries before this one
+    pub long_name: Option<String>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Cluster(pub u32);
 
+/// Where a directory entry is stored on the disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryPos {
+    /// The sector holding the entry
+    pub sector: usize,
+    /// Byte offset of the entry in the sector
+    pub offset: usize,
+    /// `(sector, offset)` of the long name entries before it
+    pub long_name: Vec<(usize, usize)>,
+}
+
+impl EntryPos {
+    pub fn new(sector: usize, offset: usize) -> Self {
+        Self {
+            sector,
+            offset,
+            long_name: Vec::new(),
+        }
+    }
+}
+
 bitflags! {
     /// File Attributes
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -40,6 +63,21 @@ bitflags! {
 impl DirEntry {
     pub const LEN: usize = 0x20;
 
+    /// A new empty entry, stamped with the current time
+    pub fn new(filename: ShortFileName, attributes: Attributes) -> Self {
+        let now = current_time();
+        Self {
+            filename,
+            modified_time: now,
+            created_time: now,
+            accessed_time: now,
+            cluster: Cluster::EMPTY,
+            attributes,
+            size: 0,
+            long_name: None,
+        }
+    }
+
     pub fn is_valid(&self) -> bool {
         !self.filename.is_unused() && !self.filename.is_eod()
     }
@@ -57,11 +95,12 @@ impl DirEntry {
     }
 
     pub fn filename(&self) -> String {
-        // NOTE: ignore the long file name in FAT16 for lab
-        if self.is_valid() && !self.is_long_name() {
-            format!("{}", self.filename)
-        } else {
+        if !self.is_valid() || self.is_long_name() {
             String::from("unknown")
+        } else if let Some(long_name) = &self.long_name {
+            long_name.clone()
+        } else {
+            format!("{}", self.filename)
         }
     }
 
@@ -107,12 +146,51 @@ impl DirEntry {
             cluster: Cluster(cluster),
             attributes,
             size,
+            long_name: None,
         })
     }
 
     pub fn as_meta(&self) -> Metadata {
         self.into()
     }
+
+    /// Encode the entry in the on-disk format, the inverse of `parse`
+    pub fn as_bytes(&self) -> [u8; DirEntry::LEN] {
+        let mut data = [0u8; DirEntry::LEN];
+        let (created_date, created_time) = format_datetime(&self.created_time);
+        let (accessed_date, _) = format_datetime(&self.accessed_time);
+        let (modified_date, modified_time) = format_datetime(&self.modified_time);
+
+        data[..8].copy_from_slice(&self.filename.name);
+        data[8..11].copy_from_slice(&self.filename.ext);
+        data[11] = self.attributes.bits();
+        data[14..16].copy_from_slice(&created_time.to_le_bytes());
+        data[16..18].copy_from_slice(&created_date.to_le_bytes());
+        data[18..20].copy_from_slice(&accessed_date.to_le_bytes());
+        data[20..22].copy_from_slice(&((self.cluster.0 >> 16) as u16).to_le_bytes());
+        data[22..24].copy_from_slice(&modified_time.to_le_bytes());
+        data[24..26].copy_from_slice(&modified_date.to_le_bytes());
+        data[26..28].copy_from_slice(&(self.cluster.0 as u16).to_le_bytes());
+        data[28..32].copy_from_slice(&self.size.to_le_bytes());
+        data
+    }
+}
+
+/// Encode a time as FAT `(date, time)`, times before 1980 are clamped
+fn format_datetime(datetime: &FsTime) -> (u16, u16) {
+    use chrono::{Datelike, Timelike};
+
+    if datetime.year() < 1980 {
+        return ((1 << 5) | 1, 0);
+    }
+
+    let date = ((datetime.year() - 1980).min(127) as u16) << 9
+        | (datetime.month() as u16) << 5
+        | datetime.day() as u16;
+    let time = (datetime.hour() as u16) << 11
+        | (datetime.minute() as u16) << 5
+        | (datetime.second() / 2) as u16;
+    (date, time)
 }
 
 fn parse_datetime(date: u16, time: u16) -> FsTime {
@@ -146,6 +224,17 @@ pub struct ShortFileName {
 }
 
 impl ShortFileName {
+    /// Name of the entry of a directory pointing to itself
+    pub const DOT: ShortFileName = ShortFileName {
+        name: *b".       ",
+        ext: *b"   ",
+    };
+    /// Name of the entry of a directory pointing to its parent
+    pub const DOT_DOT: ShortFileName = ShortFileName {
+        name: *b"..      ",
+        ext: *b"   ",
+    };
+
     pub fn new(buf: &[u8]) -> Self {
         Self {
             name: buf[..8].try_into().unwrap(),
@@ -236,9 +325,12 @@ impl ShortFileName {
             }
 
             // 检查扩展名长度
-            // if extension.len() > 3 {
-            //     return Err(FilenameError::ExtensionTooLong.into());
-            // }
+            if extension.len() > 3 {
+                return Err(FilenameError::NameTooLong.into());
+            }
+            if extension.contains('.') {
+                return Err(FilenameError::MisplacedPeriod.into());
+            }
 
             // 检查点号位置 (不能在第9个字符之后)
             if dot_pos > 8 {
@@ -408,4 +500,15 @@ mod tests {
 
         println!("{:#?}", res);
     }
+
+    #[test]
+    fn test_dir_entry_round_trip() {
+        let data = hex_literal::hex!(
+            "4b 45 52 4e 45 4c 20 20 45 4c 46 20 00 00 0f be
+             d0 50 d0 50 00 00 0f be d0 50 02 00 f0 e4 0e 00"
+        );
+
+        let res = DirEntry::parse(&data).unwrap();
+        assert_eq!(res.as_bytes(), data);
+    }
 }
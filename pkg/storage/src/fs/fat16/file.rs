@@ -5,23 +5,26 @@
 use super::*;
 
 #[derive(Debug, Clone)]
-pub struct File {
+pub struct File<V = Fat16Impl> {
     /// The current offset in the file
     offset: usize,
     /// The current cluster of this file
     current_cluster: Cluster,
     /// DirEntry of this file
     entry: DirEntry,
+    /// Where the DirEntry is stored, updated after writes
+    pos: EntryPos,
     /// The file system handle that contains this file
-    handle: Fat16Handle,
+    handle: Arc<V>,
 }
 
-impl File {
-    pub fn new(handle: Fat16Handle, entry: DirEntry) -> Self {
+impl<V: FatVolume> File<V> {
+    pub fn new(handle: Arc<V>, entry: DirEntry, pos: EntryPos) -> Self {
         Self {
             offset: 0,
             current_cluster: entry.cluster,
             entry,
+            pos,
             handle,
         }
     }
@@ -32,8 +35,7 @@ impl File {
     
     /// 确保当前簇指向正确的位置（基于当前偏移量）
     fn ensure_correct_cluster(&mut self) -> FsResult<()> {
-        let cluster_size = self.handle.bpb.sectors_per_cluster() as usize 
-                          * self.handle.bpb.bytes_per_sector() as usize;
+        let cluster_size = self.handle.cluster_size();
         
         let target_cluster_index = self.offset / cluster_size;
         
@@ -53,9 +55,9 @@ impl File {
                     return Err(FsError::InvalidOperation);
                 }
                 
-                match self.handle.get_next_cluster(c as u16)? {
+                match self.handle.get_next_cluster(c)? {
                     Some(next_cluster) => {
-                        current_cluster = Cluster(next_cluster as u32);
+                        current_cluster = Cluster(next_cluster);
                     }
                     None => {
                         // 簇链提前结束
@@ -78,9 +80,9 @@ impl File {
                 return Ok(false);
             }
             
-            match self.handle.get_next_cluster(c as u16)? {
+            match self.handle.get_next_cluster(c)? {
                 Some(next_cluster) => {
-                    self.current_cluster = Cluster(next_cluster as u32);
+                    self.current_cluster = Cluster(next_cluster);
                     Ok(true)
                 }
                 None => {
@@ -95,8 +97,8 @@ impl File {
 
     /// 从当前簇读取数据
     fn read_from_current_cluster(&self, offset_in_cluster: usize, buf: &mut [u8]) -> FsResult<usize> {
-        let sector_size = self.handle.bpb.bytes_per_sector() as usize;
-        let sectors_per_cluster = self.handle.bpb.sectors_per_cluster() as usize;
+        let sector_size = self.handle.bytes_per_sector();
+        let sectors_per_cluster = self.handle.sectors_per_cluster();
         
         // 获取簇的起始扇区
         let start_sector = self.handle.cluster_to_sector(&self.current_cluster);
@@ -113,7 +115,7 @@ impl File {
             
             // 读取扇区数据
             let mut block = Block::default();
-            self.handle.inner.read_block(current_sector, &mut block)?;
+            self.handle.device().read_block(current_sector, &mut block)?;
             let sector_data = block.as_ref();
             
             // 计算在当前扇区中的起始位置
@@ -142,7 +144,7 @@ impl File {
     }
 }
 
-impl Read for File {
+impl<V: FatVolume> Read for File<V> {
     fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
         // DONE: read file content from disk
         //      CAUTION: file length / buffer size / offset
@@ -166,8 +168,7 @@ impl Read for File {
             return Ok(0);
         }
 
-        let cluster_size = self.handle.bpb.sectors_per_cluster() as usize 
-                          * self.handle.bpb.bytes_per_sector() as usize;
+        let cluster_size = self.handle.cluster_size();
         
         let mut bytes_read = 0;
 
@@ -212,20 +213,99 @@ impl Read for File {
     }
 }
 
-// NOTE: `Seek` trait is not required for this lab
-impl Seek for File {
+impl<V: FatVolume> Seek for File<V> {
     fn seek(&mut self, pos: SeekFrom) -> FsResult<usize> {
-        unimplemented!()
+        let offset = match pos {
+            SeekFrom::Start(offset) => offset as isize,
+            SeekFrom::End(offset) => self.entry.size as isize + offset,
+            SeekFrom::Current(offset) => self.offset as isize + offset,
+        };
+        if offset < 0 {
+            return Err(FsError::InvalidOffset);
+        }
+        self.offset = offset as usize;
+        Ok(self.offset)
     }
 }
 
-// NOTE: `Write` trait is not required for this lab
-impl Write for File {
-    fn write(&mut self, _buf: &[u8]) -> FsResult<usize> {
-        unimplemented!()
+impl<V: FatVolume> File<V> {
+    /// 重新读取磁盘上的目录条目，其他句柄可能已经修改了它
+    ///
+    /// 条目已被删除或移走时返回 `FileNotFound`，
+    /// 调用者需持有 `self.handle.lock()`
+    fn reload_entry(&mut self) -> FsResult {
+        let entry = self
+            .handle
+            .read_entry(&self.pos)?
+            .filter(|entry| entry.is_file() && entry.filename == self.entry.filename)
+            .ok_or(FsError::FileNotFound)?;
+        self.entry = DirEntry {
+            long_name: self.entry.long_name.take(),
+            ..entry
+        };
+        Ok(())
+    }
+
+    /// 从当前偏移量开始写入数据，按需为文件分配新簇
+    fn write_data(&mut self, buf: &[u8]) -> FsResult {
+        let cluster_size = self.handle.cluster_size();
+
+        // 空文件还没有分配簇
+        if self.entry.cluster == Cluster::EMPTY {
+            self.entry.cluster = Cluster(self.handle.alloc_cluster(None)?);
+        }
+
+        let start = self.entry.cluster.0;
+        let mut cluster = self.handle.nth_cluster(start, self.offset / cluster_size, true)?;
+        let mut written = 0;
+
+        while written < buf.len() {
+            let offset_in_cluster = self.offset % cluster_size;
+            let len = (cluster_size - offset_in_cluster).min(buf.len() - written);
+            self.handle
+                .write_cluster(cluster, offset_in_cluster, &buf[written..written + len])?;
+            written += len;
+            self.offset += len;
+
+            if written < buf.len() {
+                cluster = self.handle.nth_cluster(cluster, 1, true)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<V: FatVolume> Write for File<V> {
+    fn write(&mut self, buf: &[u8]) -> FsResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // 文件大小在目录条目中只有 32 位
+        if self.offset + buf.len() > u32::MAX as usize {
+            return Err(FsError::WriteZero);
+        }
+
+        let handle = self.handle.clone();
+        let _guard = handle.lock().lock();
+        self.reload_entry()?;
+        let start = self.offset;
+        let result = self.write_data(buf);
+        let written = self.offset - start;
+
+        // 即使只写入了一部分，也要更新目录条目
+        self.entry.size = self.entry.size.max(self.offset as u32);
+        self.entry.modified_time = current_time();
+        self.handle.write_entry(&self.pos, &self.entry)?;
+
+        match result {
+            Err(err) if written == 0 => Err(err),
+            _ => Ok(written),
+        }
     }
 
     fn flush(&mut self) -> FsResult {
-        unimplemented!()
+        // 每次写入后目录条目都已写回
+        Ok(())
     }
 }
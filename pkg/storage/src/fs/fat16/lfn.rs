@@ -0,0 +1,238 @@
+//! VFAT Long File Name
+//!
+//! A long name is stored in up to 20 entries of 13 UTF-16 characters,
+//! placed before the 8.3 entry it belongs to, the last part first.
+//!
+//! reference: <https://wiki.osdev.org/FAT#Long_File_Names>
+
+use super::*;
+
+/// Offsets of the 13 characters in a long name entry
+const CHAR_OFFSETS: [usize; 13] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+/// Characters in a part of the long name
+const CHARS_PER_ENTRY: usize = CHAR_OFFSETS.len();
+
+/// Flag of the order byte marking the last part of the name
+const LAST_ENTRY: u8 = 0x40;
+
+/// A long name is at most 255 UTF-16 characters
+const MAX_LEN: usize = 255;
+
+/// Characters allowed in a short name besides letters and digits
+const SHORT_NAME_SPECIAL: &str = "!#$%&'()-@^_`{}~";
+
+/// Checksum of the short name, stored in every entry of its long name
+pub fn checksum(sfn: &ShortFileName) -> u8 {
+    sfn.name
+        .iter()
+        .chain(sfn.ext.iter())
+        .fold(0u8, |sum, &byte| sum.rotate_right(1).wrapping_add(byte))
+}
+
+/// Check that `name` can be stored as a long name
+pub fn check_name(name: &str) -> FsResult {
+    if name.is_empty() {
+        return Err(FilenameError::FilenameEmpty.into());
+    }
+    if name == "." || name == ".." {
+        return Err(FilenameError::MisplacedPeriod.into());
+    }
+    if name.encode_utf16().count() > MAX_LEN {
+        return Err(FilenameError::NameTooLong.into());
+    }
+    if name.chars().any(|c| c < ' ' || "\"*/:<>?\\|".contains(c)) {
+        return Err(FilenameError::InvalidCharacter.into());
+    }
+    Ok(())
+}
+
+/// Whether two names are equal, ignoring case
+pub fn eq_ignore_case(a: &str, b: &str) -> bool {
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .eq(b.chars().flat_map(char::to_lowercase))
+}
+
+/// Encode `name` as long name entries, in the order they are stored
+pub fn encode(name: &str, checksum: u8) -> Vec<[u8; DirEntry::LEN]> {
+    let units: Vec<u16> = name.encode_utf16().collect();
+    let count = units.len().div_ceil(CHARS_PER_ENTRY);
+
+    (0..count)
+        .rev()
+        .map(|i| {
+            let mut data = [0u8; DirEntry::LEN];
+            data[0] = (i + 1) as u8 | if i + 1 == count { LAST_ENTRY } else { 0 };
+            data[11] = Attributes::LFN.bits();
+            data[13] = checksum;
+
+            for (j, offset) in CHAR_OFFSETS.iter().enumerate() {
+                // the name ends with 0x0000, the rest is padded with 0xFFFF
+                let index = i * CHARS_PER_ENTRY + j;
+                let unit = match units.get(index) {
+                    Some(unit) => *unit,
+                    None if index == units.len() => 0x0000,
+                    None => 0xFFFF,
+                };
+                data[*offset..*offset + 2].copy_from_slice(&unit.to_le_bytes());
+            }
+            data
+        })
+        .collect()
+}
+
+/// Collects the long name entries before a short entry
+#[derive(Debug, Default)]
+pub struct LongName {
+    units: Vec<u16>,
+    /// Order of the next expected entry, 0 when the name is complete
+    next: u8,
+    checksum: u8,
+}
+
+impl LongName {
+    pub fn reset(&mut self) {
+        self.units.clear();
+        self.next = 0;
+    }
+
+    /// Add a long name entry, returns false if it does not continue the name
+    pub fn push(&mut self, data: &[u8]) -> bool {
+        let order = data[0] & 0x1F;
+        if data[0] & LAST_ENTRY != 0 {
+            self.units.clear();
+            self.next = order;
+            self.checksum = data[13];
+        }
+
+        if order == 0 || order != self.next || data[13] != self.checksum {
+            self.reset();
+            return false;
+        }
+
+        let part = CHAR_OFFSETS
+            .iter()
+            .map(|offset| u16::from_le_bytes([data[*offset], data[offset + 1]]))
+            .take_while(|unit| *unit != 0x0000 && *unit != 0xFFFF);
+        self.units.splice(0..0, part);
+        self.next -= 1;
+        true
+    }
+
+    /// The name, if every entry was seen and it belongs to `sfn`
+    pub fn finish(&mut self, sfn: &ShortFileName) -> Option<String> {
+        let complete = self.next == 0 && !self.units.is_empty() && self.checksum == checksum(sfn);
+        let name = complete.then(|| String::from_utf16_lossy(&self.units));
+        self.reset();
+        name
+    }
+}
+
+/// A unique 8.3 alias of `name`
+///
+/// A name that only differs from its alias in case keeps it as is,
+/// others get a numeric tail, e.g. `Hello World.txt` -> `HELLOW~1.TXT`.
+pub fn short_alias(name: &str, exists: impl Fn(&ShortFileName) -> bool) -> ShortFileName {
+    let trimmed = name.trim_start_matches('.');
+    let (base, ext) = match trimmed.rfind('.') {
+        Some(i) => (&trimmed[..i], &trimmed[i + 1..]),
+        None => (trimmed, ""),
+    };
+
+    let (mut base, base_lossy) = basis(base, 8);
+    let (ext, ext_lossy) = basis(ext, 3);
+    let lossy = base_lossy || ext_lossy || base.is_empty() || trimmed.len() != name.len();
+    if base.is_empty() {
+        base.push(b'_');
+    }
+
+    let alias = |tail: &str| {
+        let mut sfn = ShortFileName {
+            name: [b' '; 8],
+            ext: [b' '; 3],
+        };
+        let keep = base.len().min(8 - tail.len());
+        sfn.name[..keep].copy_from_slice(&base[..keep]);
+        sfn.name[keep..keep + tail.len()].copy_from_slice(tail.as_bytes());
+        sfn.ext[..ext.len()].copy_from_slice(&ext);
+        sfn
+    };
+
+    if !lossy {
+        let sfn = alias("");
+        if !exists(&sfn) {
+            return sfn;
+        }
+    }
+
+    (1..)
+        .map(|n| alias(&format!("~{}", n)))
+        .find(|sfn| !exists(sfn))
+        .unwrap()
+}
+
+/// Uppercase `part` and replace characters not allowed in a short name,
+/// returns whether anything but case was changed
+fn basis(part: &str, max: usize) -> (Vec<u8>, bool) {
+    let mut lossy = false;
+    let mut basis = Vec::new();
+
+    for c in part.chars() {
+        match c {
+            ' ' | '.' => lossy = true,
+            c if c.is_ascii_alphanumeric() || SHORT_NAME_SPECIAL.contains(c) => {
+                basis.push(c.to_ascii_uppercase() as u8)
+            }
+            _ => {
+                lossy = true;
+                basis.push(b'_');
+            }
+        }
+    }
+
+    if basis.len() > max {
+        basis.truncate(max);
+        lossy = true;
+    }
+    (basis, lossy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_name_round_trip() {
+        let name = "A file with a long name, 中文.txt";
+        let sfn = ShortFileName::parse("AFILEW~1.TXT").unwrap();
+        let entries = encode(name, checksum(&sfn));
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0][0], LAST_ENTRY | 3);
+
+        let mut long_name = LongName::default();
+        assert!(entries.iter().all(|data| long_name.push(data)));
+        assert_eq!(long_name.finish(&sfn).as_deref(), Some(name));
+
+        // a short entry with another checksum, e.g. renamed by an old driver
+        assert!(entries.iter().all(|data| long_name.push(data)));
+        assert_eq!(long_name.finish(&ShortFileName::parse("OTHER").unwrap()), None);
+
+        // missing the first part
+        assert!(!long_name.push(&entries[1]));
+        assert_eq!(long_name.finish(&sfn), None);
+    }
+
+    #[test]
+    fn short_aliases() {
+        let none = |_: &ShortFileName| false;
+        assert_eq!(short_alias("readme.md", none).to_string(), "README.MD");
+        assert_eq!(short_alias("Hello World.txt", none).to_string(), "HELLOW~1.TXT");
+        assert_eq!(short_alias(".bashrc", none).to_string(), "BASHRC~1");
+        assert_eq!(short_alias("a+b.tar.gz", none).to_string(), "A_BTAR~1.GZ");
+
+        let taken = [ShortFileName::parse("HELLOW~1.TXT").unwrap()];
+        let exists = |sfn: &ShortFileName| taken.contains(sfn);
+        assert_eq!(short_alias("Hello Wide.txt", exists).to_string(), "HELLOW~2.TXT");
+    }
+}
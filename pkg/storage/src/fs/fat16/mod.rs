@@ -3,20 +3,21 @@ pub mod directory;
 pub mod direntry;
 pub mod file;
 pub mod impls;
+pub mod lfn;
+pub mod volume;
 
 use crate::*;
 use directory::Directory;
 use direntry::*;
 use file::File;
+use volume::*;
 
 use bpb::Fat16Bpb;
 
 const BLOCK_SIZE: usize = 512;
 
 /// Identifies a Fat16 filesystem on the disk.
-pub struct Fat16 {
-    handle: Fat16Handle,
-}
+pub type Fat16 = Fat<Fat16Impl>;
 
 impl Fat16 {
     pub fn new(inner: impl BlockDevice<Block512>) -> Self {
@@ -24,9 +25,13 @@ impl Fat16 {
             handle: Arc::new(Fat16Impl::new(inner)),
         }
     }
-}
 
-type Fat16Handle = Arc<Fat16Impl>;
+    pub fn try_new(inner: impl BlockDevice<Block512>) -> FsResult<Self> {
+        Ok(Self {
+            handle: Arc::new(Fat16Impl::try_new(inner)?),
+        })
+    }
+}
 
 /// The Fat16 filesystem.
 ///
@@ -41,18 +46,200 @@ pub struct Fat16Impl {
     pub fat_start: usize,
     pub first_data_sector: usize,
     pub first_root_dir_sector: usize,
+    /// Held while the FAT or a directory is modified
+    pub(crate) lock: spin::Mutex<()>,
 }
 
-impl core::fmt::Debug for Fat16 {
+impl core::fmt::Debug for Fat16Impl {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        f.debug_struct("Fat16")
-            .field("bpb", &self.handle.bpb)
-            .finish()
+        f.debug_struct("Fat16Impl").field("bpb", &self.bpb).finish()
     }
 }
 
-impl core::fmt::Debug for Fat16Impl {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        f.debug_struct("Fat16Impl").field("bpb", &self.bpb).finish()
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A disk kept in memory
+    pub(crate) struct RamDisk(pub(crate) spin::Mutex<Vec<Block512>>);
+
+    impl BlockDevice<Block512> for RamDisk {
+        fn block_count(&self) -> FsResult<usize> {
+            Ok(self.0.lock().len())
+        }
+
+        fn read_block(&self, offset: usize, block: &mut Block512) -> FsResult {
+            *block = self.0.lock()[offset].clone();
+            Ok(())
+        }
+
+        fn write_block(&self, offset: usize, block: &Block512) -> FsResult {
+            self.0.lock()[offset] = block.clone();
+            Ok(())
+        }
+    }
+
+    /// 2048 sectors, one sector per cluster, two FATs of 8 sectors
+    fn format() -> Fat16 {
+        let mut disk = vec![Block512::default(); 2048];
+
+        let bpb = disk[0].as_mut();
+        bpb[0x0B..0x0D].copy_from_slice(&512u16.to_le_bytes());
+        bpb[0x0D] = 1;
+        bpb[0x0E..0x10].copy_from_slice(&1u16.to_le_bytes());
+        bpb[0x10] = 2;
+        bpb[0x11..0x13].copy_from_slice(&64u16.to_le_bytes());
+        bpb[0x13..0x15].copy_from_slice(&2048u16.to_le_bytes());
+        bpb[0x16..0x18].copy_from_slice(&8u16.to_le_bytes());
+        bpb[0x1FE..0x200].copy_from_slice(&0xAA55u16.to_le_bytes());
+
+        for fat in [1, 9] {
+            disk[fat].as_mut()[..4].copy_from_slice(&[0xF8, 0xFF, 0xFF, 0xFF]);
+        }
+
+        Fat16::new(RamDisk(spin::Mutex::new(disk)))
+    }
+
+    pub(crate) fn read_to_end(fs: &dyn FileSystem, path: &str) -> Vec<u8> {
+        let mut buf = Vec::new();
+        fs.open_file(path).unwrap().read_all(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn write_across_clusters() {
+        let fs = format();
+        let data: Vec<u8> = (0..1300u32).map(|i| i as u8).collect();
+
+        let mut file = fs.create_file("/data.bin").unwrap();
+        file.write_all(&data[..700]).unwrap();
+        file.write_all(&data[700..]).unwrap();
+
+        assert_eq!(fs.metadata("/DATA.BIN").unwrap().len, data.len());
+        assert_eq!(read_to_end(&fs, "/data.bin"), data);
+
+        // both FATs hold the chain of three clusters
+        let handle = &fs.handle;
+        assert_eq!(handle.fat_entry(2).unwrap(), 3);
+        assert_eq!(handle.fat_entry(3).unwrap(), 4);
+        assert_eq!(handle.fat_entry(4).unwrap(), 0xFFFF);
+        let (mut fat1, mut fat2) = (Block512::default(), Block512::default());
+        handle.inner.read_block(1, &mut fat1).unwrap();
+        handle.inner.read_block(9, &mut fat2).unwrap();
+        assert_eq!(fat1.as_ref(), fat2.as_ref());
+    }
+
+    #[test]
+    fn append_and_truncate() {
+        let fs = format();
+        fs.create_file("/log.txt").unwrap().write_all(b"hello").unwrap();
+        fs.append_file("/log.txt").unwrap().write_all(b", world").unwrap();
+        assert_eq!(read_to_end(&fs, "/log.txt"), b"hello, world");
+
+        let mut file = fs.open_file("/log.txt").unwrap();
+        file.seek(SeekFrom::Start(7)).unwrap();
+        file.write_all(b"FAT16").unwrap();
+        assert_eq!(read_to_end(&fs, "/log.txt"), b"hello, FAT16");
+
+        fs.create_file("/log.txt").unwrap();
+        assert_eq!(fs.metadata("/log.txt").unwrap().len, 0);
+        assert_eq!(fs.handle.fat_entry(2).unwrap(), 0);
+
+        assert_eq!(fs.append_file("/none.txt").err(), Some(FsError::FileNotFound));
+        assert!(fs.create_file("/no/file.txt").is_err());
+    }
+
+    #[test]
+    fn shared_handles() {
+        let fs = format();
+        fs.create_file("/a.txt").unwrap();
+        let mut first = fs.open_file("/a.txt").unwrap();
+        let mut second = fs.open_file("/a.txt").unwrap();
+
+        // both handles start with an empty file, only one cluster is allocated
+        first.write_all(b"first").unwrap();
+        second.seek(SeekFrom::Start(5)).unwrap();
+        second.write_all(b"+second").unwrap();
+        first.write_all(b"!").unwrap();
+        assert_eq!(read_to_end(&fs, "/a.txt"), b"first!second");
+        assert_eq!(fs.handle.fat_entry(3).unwrap(), 0);
+
+        // an unlinked file can no longer be written
+        fs.remove_file("/a.txt").unwrap();
+        assert_eq!(first.write(b"late"), Err(FsError::FileNotFound));
+        assert_eq!(fs.handle.fat_entry(2).unwrap(), 0);
+    }
+
+    #[test]
+    fn directories() {
+        let fs = format();
+        fs.create_dir("/docs").unwrap();
+        fs.create_dir("/docs/old").unwrap();
+        assert_eq!(fs.create_dir("/DOCS"), Err(FsError::AlreadyExists));
+        fs.create_file("/docs/a.txt").unwrap().write_all(b"text").unwrap();
+
+        let names: Vec<String> = fs.read_dir("/docs").unwrap().map(|m| m.name).collect();
+        assert_eq!(names, [".", "..", "old", "a.txt"]);
+
+        assert_eq!(fs.remove_dir("/docs"), Err(FsError::NotEmpty));
+        assert_eq!(fs.remove_file("/docs/old"), Err(FsError::NotAFile));
+
+        fs.copy_file("/docs/a.txt", "/b.txt").unwrap();
+        fs.move_file("/docs/a.txt", "/docs/old/c.txt").unwrap();
+        assert_eq!(read_to_end(&fs, "/docs/old/c.txt"), b"text");
+        assert_eq!(read_to_end(&fs, "/b.txt"), b"text");
+        assert!(!fs.exists("/docs/a.txt").unwrap());
+
+        // `..` of a moved directory points to its new parent
+        assert_eq!(fs.move_dir("/docs", "/docs/old/docs"), Err(FsError::InvalidOperation));
+        fs.move_dir("/docs/old", "/old").unwrap();
+        assert_eq!(read_to_end(&fs, "/old/c.txt"), b"text");
+        let old = fs.handle.find_path("/old").unwrap().unwrap();
+        let entries = fs.handle.read_dir_entries(&old.cluster).unwrap();
+        assert_eq!(entries[1].filename, ShortFileName::DOT_DOT);
+        assert_eq!(entries[1].cluster, Cluster::EMPTY);
+
+        fs.remove_file("/old/c.txt").unwrap();
+        fs.remove_dir("/old").unwrap();
+        fs.remove_dir("/docs").unwrap();
+        assert!(!fs.exists("/old").unwrap());
+
+        // only the clusters of `/b.txt` are still in use
+        let used = (2..fs.handle.cluster_count() as u32 + 2)
+            .filter(|c| fs.handle.fat_entry(*c).unwrap() != 0)
+            .count();
+        assert_eq!(used, 1);
+    }
+
+    #[test]
+    fn long_names() {
+        let fs = format();
+        fs.create_file("/Long File Name.txt").unwrap().write_all(b"long").unwrap();
+        fs.create_file("/Long File Name 2.txt").unwrap();
+        fs.create_dir("/A Directory").unwrap();
+        fs.create_file("/A Directory/.hidden").unwrap();
+
+        let names: Vec<String> = fs.read_dir("/").unwrap().map(|m| m.name).collect();
+        assert_eq!(names, ["Long File Name.txt", "Long File Name 2.txt", "A Directory"]);
+        assert_eq!(fs.metadata("/a directory/.HIDDEN").unwrap().name, ".hidden");
+
+        // found by its long name in any case, or by its short alias
+        assert_eq!(read_to_end(&fs, "/long file name.TXT"), b"long");
+        assert_eq!(read_to_end(&fs, "/LONGFI~1.TXT"), b"long");
+        let entry = fs.handle.find_path("/Long File Name 2.txt").unwrap().unwrap();
+        assert_eq!(entry.filename, ShortFileName::parse("LONGFI~2.TXT").unwrap());
+
+        fs.move_file("/Long File Name.txt", "/A Directory/renamed file.txt").unwrap();
+        assert_eq!(read_to_end(&fs, "/a directory/Renamed File.txt"), b"long");
+        assert!(!fs.exists("/Long File Name.txt").unwrap());
+
+        // removing an entry frees the slots of its long name
+        fs.remove_file("/Long File Name 2.txt").unwrap();
+        let mut root = Block512::default();
+        fs.handle.inner.read_block(fs.handle.first_root_dir_sector, &mut root).unwrap();
+        let free = root.chunks(DirEntry::LEN).filter(|slot| slot[0] == 0xE5).count();
+        assert_eq!(free, 6);
+
+        assert!(fs.create_file("/bad:name").is_err());
     }
 }
@@ -0,0 +1,731 @@
+//! Volume
+//!
+//! Cluster chains, directories and the `FileSystem` implementation shared
+//! by FAT16 and FAT32. A volume only has to describe its FAT and where its
+//! root directory is stored.
+//!
+//! reference: <https://wiki.osdev.org/FAT#FAT_32>
+
+use super::*;
+use crate::alloc::string::ToString;
+use core::ops::Range;
+
+/// A FAT filesystem on a volume `V`
+pub struct Fat<V> {
+    pub(crate) handle: Arc<V>,
+}
+
+impl<V: FatVolume> core::fmt::Debug for Fat<V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.handle.fmt(f)
+    }
+}
+
+/// Where the root directory is stored
+pub enum RootDir {
+    /// A fixed region of sectors after the FATs, as in FAT16
+    Region(Range<usize>),
+    /// A cluster chain like any other directory, as in FAT32
+    Chain(u32),
+}
+
+pub trait FatVolume: core::fmt::Debug + Send + Sync + 'static {
+    /// The FAT entry marking the end of a cluster chain
+    const END_OF_CHAIN: u32;
+
+    fn device(&self) -> &dyn BlockDevice<Block512>;
+
+    /// Held while the FAT or a directory is modified
+    fn lock(&self) -> &spin::Mutex<()>;
+
+    fn bytes_per_sector(&self) -> usize;
+
+    fn sectors_per_cluster(&self) -> usize;
+
+    fn root_dir(&self) -> RootDir;
+
+    fn cluster_to_sector(&self, cluster: &Cluster) -> usize;
+
+    /// 数据区的簇数，合法的簇号为 `2..cluster_count + 2`
+    fn cluster_count(&self) -> usize;
+
+    /// 读取簇的 FAT 条目
+    fn fat_entry(&self, cluster: u32) -> FsResult<u32>;
+
+    /// 写入簇的 FAT 条目，所有 FAT 副本保持一致
+    fn set_fat_entry(&self, cluster: u32, value: u32) -> FsResult;
+
+    /// 查找一个空闲簇，不修改 FAT
+    fn find_free_cluster(&self) -> FsResult<Option<u32>>;
+
+    /// 目录的第一个簇，FAT16 的根目录不在簇中
+    fn first_cluster(&self, dir: &Cluster) -> Option<u32> {
+        match (*dir, self.root_dir()) {
+            (Cluster::ROOT_DIR, RootDir::Region(_)) => None,
+            (Cluster::ROOT_DIR, RootDir::Chain(cluster)) => Some(cluster),
+            (Cluster(cluster), _) => Some(cluster),
+        }
+    }
+
+    /// 每簇的字节数
+    fn cluster_size(&self) -> usize {
+        self.sectors_per_cluster() * self.bytes_per_sector()
+    }
+
+    /// 读取 FAT 表获取下一个簇
+    fn get_next_cluster(&self, cluster: u32) -> FsResult<Option<u32>> {
+        match self.fat_entry(cluster)? {
+            0 => Ok(None), // 空簇
+            next if next >= Self::END_OF_CHAIN - 7 => Ok(None), // 坏簇或簇链结束
+            next => Ok(Some(next)), // 下一个簇
+        }
+    }
+
+    /// 分配一个空闲簇并清零，`prev` 为簇链当前的最后一个簇
+    ///
+    /// 调用者需持有 `self.lock()`
+    fn alloc_cluster(&self, prev: Option<u32>) -> FsResult<u32> {
+        let cluster = self.find_free_cluster()?.ok_or(FsError::WriteZero)?;
+
+        // 清零新簇，避免读到之前的数据
+        let zero = Block::default();
+        let start = self.cluster_to_sector(&Cluster(cluster));
+        for i in 0..self.sectors_per_cluster() {
+            self.device().write_block(start + i, &zero)?;
+        }
+
+        self.set_fat_entry(cluster, Self::END_OF_CHAIN)?;
+        if let Some(prev) = prev {
+            self.set_fat_entry(prev, cluster)?;
+        }
+        Ok(cluster)
+    }
+
+    /// 释放从 `start` 开始的整条簇链
+    ///
+    /// 调用者需持有 `self.lock()`
+    fn free_chain(&self, start: u32) -> FsResult {
+        let mut cluster = Some(start);
+        while let Some(current) = cluster.filter(|c| *c >= 2) {
+            cluster = self.get_next_cluster(current)?;
+            self.set_fat_entry(current, 0)?;
+        }
+        Ok(())
+    }
+
+    /// 簇链中的第 `index` 个簇，`grow` 时为缺少的部分分配新簇
+    fn nth_cluster(&self, start: u32, index: usize, grow: bool) -> FsResult<u32> {
+        let mut cluster = start;
+        for _ in 0..index {
+            cluster = match self.get_next_cluster(cluster)? {
+                Some(next) => next,
+                None if grow => self.alloc_cluster(Some(cluster))?,
+                None => return Err(FsError::EndOfFile),
+            };
+        }
+        Ok(cluster)
+    }
+
+    /// 将 `data` 写入簇内 `offset` 处，不能越过簇的末尾
+    fn write_cluster(&self, cluster: u32, offset: usize, data: &[u8]) -> FsResult {
+        let bytes_per_sector = self.bytes_per_sector();
+        let first_sector = self.cluster_to_sector(&Cluster(cluster));
+        let mut block = Block::default();
+        let mut written = 0;
+
+        while written < data.len() {
+            let pos = offset + written;
+            let sector = first_sector + pos / bytes_per_sector;
+            let start = pos % bytes_per_sector;
+            let len = (bytes_per_sector - start).min(data.len() - written);
+
+            // 只写扇区的一部分时，需要先读出原有数据
+            if len < bytes_per_sector {
+                self.device().read_block(sector, &mut block)?;
+            }
+            block.as_mut()[start..start + len].copy_from_slice(&data[written..written + len]);
+            self.device().write_block(sector, &block)?;
+            written += len;
+        }
+
+        Ok(())
+    }
+
+    /// 读取目录条目，长文件名已合并到对应的条目中
+    fn read_dir_entries(&self, cluster: &Cluster) -> FsResult<Vec<DirEntry>> {
+        let mut entries = Vec::new();
+        self.scan_dir(cluster, |entry, _| {
+            if entry.is_valid() {
+                entries.push(entry);
+            }
+            None::<()>
+        })?;
+        Ok(entries)
+    }
+
+    /// 依次访问目录中的条目，`f` 返回 `Some` 时停止遍历并返回该值
+    ///
+    /// 长文件名条目不会单独访问，而是合并到其后的短文件名条目中
+    fn scan_dir<R>(
+        &self,
+        dir_cluster: &Cluster,
+        mut f: impl FnMut(DirEntry, EntryPos) -> Option<R>,
+    ) -> FsResult<Option<R>> {
+        let mut long_name = lfn::LongName::default();
+        let mut long_name_pos = Vec::new();
+        let mut block = Block::default();
+
+        for sector in self.dir_sectors(dir_cluster)? {
+            self.device().read_block(sector, &mut block)?;
+            for offset in (0..block.len()).step_by(DirEntry::LEN) {
+                let data = &block[offset..offset + DirEntry::LEN];
+                match data[0] {
+                    0x00 => return Ok(None), // 目录结束
+                    0xE5 => {
+                        // 已删除的条目
+                        long_name.reset();
+                        long_name_pos.clear();
+                        continue;
+                    }
+                    _ => {}
+                }
+
+                // 长文件名条目
+                if data[11] & 0x3F == Attributes::LFN.bits() {
+                    if long_name.push(data) {
+                        long_name_pos.push((sector, offset));
+                    } else {
+                        long_name_pos.clear();
+                    }
+                    continue;
+                }
+
+                // 跳过无法解析的条目
+                let Ok(mut entry) = DirEntry::parse(data) else {
+                    long_name.reset();
+                    long_name_pos.clear();
+                    continue;
+                };
+                entry.long_name = long_name.finish(&entry.filename);
+                let mut pos = EntryPos::new(sector, offset);
+                if entry.long_name.is_some() {
+                    pos.long_name = core::mem::take(&mut long_name_pos);
+                }
+                long_name_pos.clear();
+
+                if let Some(result) = f(entry, pos) {
+                    return Ok(Some(result));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// 目录占用的所有扇区
+    fn dir_sectors(&self, dir: &Cluster) -> FsResult<Vec<usize>> {
+        let first = match (*dir, self.root_dir()) {
+            (Cluster::ROOT_DIR, RootDir::Region(sectors)) => return Ok(sectors.collect()),
+            (Cluster::ROOT_DIR, RootDir::Chain(cluster)) => cluster,
+            (Cluster(cluster), _) => cluster,
+        };
+
+        let sectors_per_cluster = self.sectors_per_cluster();
+        let mut sectors = Vec::new();
+        let mut cluster = Some(first);
+        while let Some(current) = cluster {
+            let start = self.cluster_to_sector(&Cluster(current));
+            sectors.extend(start..start + sectors_per_cluster);
+            cluster = self.get_next_cluster(current)?;
+        }
+        Ok(sectors)
+    }
+
+    /// 在目录中查找指定名称的条目及其位置，长文件名和短文件名都不区分大小写
+    fn find_entry_pos(
+        &self,
+        dir_cluster: &Cluster,
+        name: &str,
+    ) -> FsResult<Option<(DirEntry, EntryPos)>> {
+        if name.is_empty() {
+            return Ok(None); // 空名称不匹配任何条目
+        }
+        let short_name = ShortFileName::parse(name).ok();
+
+        self.scan_dir(dir_cluster, |entry, pos| {
+            let matched = short_name.as_ref() == Some(&entry.filename)
+                || entry
+                    .long_name
+                    .as_deref()
+                    .is_some_and(|long_name| lfn::eq_ignore_case(long_name, name));
+            matched.then_some((entry, pos))
+        })
+    }
+
+    /// 新条目的短文件名和长文件名，`name` 本身是合法的 8.3 名称时不需要长文件名
+    fn entry_name(
+        &self,
+        dir_cluster: &Cluster,
+        name: &str,
+    ) -> FsResult<(ShortFileName, Option<String>)> {
+        lfn::check_name(name)?;
+        if let Ok(short_name) = ShortFileName::parse(name) {
+            if short_name.to_string() == name {
+                return Ok((short_name, None));
+            }
+        }
+
+        let entries = self.read_dir_entries(dir_cluster)?;
+        let short_name = lfn::short_alias(name, |alias| {
+            entries.iter().any(|entry| entry.filename == *alias)
+        });
+        Ok((short_name, Some(name.into())))
+    }
+
+    /// 在目录中查找指定名称的条目
+    fn find_entry(&self, dir_cluster: &Cluster, name: &str) -> FsResult<Option<DirEntry>> {
+        Ok(self.find_entry_pos(dir_cluster, name)?.map(|(entry, _)| entry))
+    }
+
+    /// 解析路径并查找文件/目录及其条目的位置
+    fn find_path_pos(&self, path: &str) -> FsResult<Option<(DirEntry, EntryPos)>> {
+        let mut parts = path.split('/').filter(|part| !part.is_empty()).peekable();
+        let mut current_cluster = Cluster::ROOT_DIR;
+
+        while let Some(part) = parts.next() {
+            let Some((entry, pos)) = self.find_entry_pos(&current_cluster, part)? else {
+                return Ok(None); // 路径不存在
+            };
+            if parts.peek().is_none() {
+                return Ok(Some((entry, pos)));
+            }
+            // 中间路径必须是目录
+            if !entry.is_directory() {
+                return Err(FsError::NotADirectory);
+            }
+            current_cluster = dir_start(&entry);
+        }
+
+        Ok(None) // 根目录没有对应的 DirEntry
+    }
+
+    /// 解析路径并查找文件/目录
+    fn find_path(&self, path: &str) -> FsResult<Option<DirEntry>> {
+        Ok(self.find_path_pos(path)?.map(|(entry, _)| entry))
+    }
+
+    /// 路径对应目录的起始簇
+    fn dir_cluster(&self, path: &str) -> FsResult<Cluster> {
+        match self.find_path(path)? {
+            None if path.trim_matches('/').is_empty() => Ok(Cluster::ROOT_DIR),
+            None => Err(FsError::FileNotFound),
+            Some(entry) if entry.is_directory() => Ok(dir_start(&entry)),
+            Some(_) => Err(FsError::NotADirectory),
+        }
+    }
+
+    /// 读取 `pos` 处的目录条目，条目已被删除时返回 `None`
+    fn read_entry(&self, pos: &EntryPos) -> FsResult<Option<DirEntry>> {
+        let mut block = Block::default();
+        self.device().read_block(pos.sector, &mut block)?;
+        let entry = DirEntry::parse(&block[pos.offset..pos.offset + DirEntry::LEN])?;
+        Ok(Some(entry).filter(DirEntry::is_valid))
+    }
+
+    /// 写回目录条目，不包括长文件名
+    fn write_entry(&self, pos: &EntryPos, entry: &DirEntry) -> FsResult {
+        self.write_slot(pos.sector, pos.offset, &entry.as_bytes())
+    }
+
+    /// 在目录中添加条目及其长文件名，目录已满时为其分配新簇
+    ///
+    /// 调用者需持有 `self.lock()`
+    fn add_entry(&self, dir_cluster: &Cluster, entry: &DirEntry) -> FsResult<EntryPos> {
+        let long_name = match &entry.long_name {
+            Some(name) => lfn::encode(name, lfn::checksum(&entry.filename)),
+            None => Vec::new(),
+        };
+
+        loop {
+            if let Some(mut slots) = self.find_free_slots(dir_cluster, long_name.len() + 1)? {
+                let (sector, offset) = slots.pop().unwrap();
+                for (data, (sector, offset)) in long_name.iter().zip(slots.iter()) {
+                    self.write_slot(*sector, *offset, data)?;
+                }
+
+                let mut pos = EntryPos::new(sector, offset);
+                pos.long_name = slots;
+                self.write_entry(&pos, entry)?;
+                return Ok(pos);
+            }
+
+            // FAT16 根目录的大小是固定的
+            let Some(mut last) = self.first_cluster(dir_cluster) else {
+                return Err(FsError::WriteZero);
+            };
+            while let Some(next) = self.get_next_cluster(last)? {
+                last = next;
+            }
+            self.alloc_cluster(Some(last))?;
+        }
+    }
+
+    /// 目录中 `count` 个连续的空闲位置
+    fn find_free_slots(
+        &self,
+        dir_cluster: &Cluster,
+        count: usize,
+    ) -> FsResult<Option<Vec<(usize, usize)>>> {
+        let mut slots = Vec::new();
+        let mut block = Block::default();
+
+        for sector in self.dir_sectors(dir_cluster)? {
+            self.device().read_block(sector, &mut block)?;
+            for offset in (0..block.len()).step_by(DirEntry::LEN) {
+                if block[offset] != 0x00 && block[offset] != 0xE5 {
+                    slots.clear();
+                    continue;
+                }
+                slots.push((sector, offset));
+                if slots.len() == count {
+                    return Ok(Some(slots));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// 写入目录中的一个位置
+    fn write_slot(&self, sector: usize, offset: usize, data: &[u8]) -> FsResult {
+        let mut block = Block::default();
+        self.device().read_block(sector, &mut block)?;
+        block.as_mut()[offset..offset + DirEntry::LEN].copy_from_slice(data);
+        self.device().write_block(sector, &block)
+    }
+
+    /// 清空文件并释放它的簇
+    ///
+    /// 调用者需持有 `self.lock()`
+    fn truncate(&self, mut entry: DirEntry, pos: &EntryPos) -> FsResult<DirEntry> {
+        if entry.cluster != Cluster::EMPTY {
+            self.free_chain(entry.cluster.0)?;
+        }
+        entry.cluster = Cluster::EMPTY;
+        entry.size = 0;
+        entry.modified_time = current_time();
+        self.write_entry(pos, &entry)?;
+        Ok(entry)
+    }
+
+    /// 将条目及其长文件名标记为已删除，不释放它的簇
+    fn delete_entry(&self, pos: &EntryPos) -> FsResult {
+        let mut block = Block::default();
+        let slots = pos.long_name.iter().copied().chain([(pos.sector, pos.offset)]);
+        for (sector, offset) in slots {
+            self.device().read_block(sector, &mut block)?;
+            block.as_mut()[offset] = 0xE5;
+            self.device().write_block(sector, &block)?;
+        }
+        Ok(())
+    }
+
+    /// 删除条目并释放它的簇链
+    ///
+    /// 调用者需持有 `self.lock()`
+    fn remove_entry(&self, entry: &DirEntry, pos: &EntryPos) -> FsResult {
+        if entry.cluster != Cluster::EMPTY {
+            self.free_chain(entry.cluster.0)?;
+        }
+        self.delete_entry(pos)
+    }
+
+    /// 目录中除 `.` 和 `..` 外是否没有其他条目
+    fn is_dir_empty(&self, dir_cluster: &Cluster) -> FsResult<bool> {
+        let entries = self.read_dir_entries(dir_cluster)?;
+        Ok(entries.iter().all(|entry| {
+            entry.filename == ShortFileName::DOT || entry.filename == ShortFileName::DOT_DOT
+        }))
+    }
+
+    /// 在 `parent` 中创建目录，新目录包含 `.` 和 `..` 两个条目
+    ///
+    /// 调用者需持有 `self.lock()`
+    fn make_dir(&self, parent: &Cluster, name: &str) -> FsResult<DirEntry> {
+        let (filename, long_name) = self.entry_name(parent, name)?;
+        let cluster = self.alloc_cluster(None)?;
+        let mut entry = DirEntry::new(filename, Attributes::DIRECTORY);
+        entry.cluster = Cluster(cluster);
+
+        let sector = self.cluster_to_sector(&entry.cluster);
+        let mut dot = entry.clone();
+        dot.filename = ShortFileName::DOT;
+        self.write_entry(&EntryPos::new(sector, 0), &dot)?;
+
+        // 父目录为根目录时，`..` 的簇号为 0
+        let mut dot_dot = entry.clone();
+        dot_dot.filename = ShortFileName::DOT_DOT;
+        dot_dot.cluster = parent_cluster(parent);
+        self.write_entry(&EntryPos::new(sector, DirEntry::LEN), &dot_dot)?;
+
+        entry.long_name = long_name;
+
+        if let Err(err) = self.add_entry(parent, &entry) {
+            self.free_chain(cluster)?;
+            return Err(err);
+        }
+        Ok(entry)
+    }
+
+    /// 将目录的 `..` 条目指向新的父目录
+    fn set_parent(&self, dir: &DirEntry, parent: &Cluster) -> FsResult {
+        let pos = EntryPos::new(self.cluster_to_sector(&dir.cluster), DirEntry::LEN);
+        let mut block = Block::default();
+        self.device().read_block(pos.sector, &mut block)?;
+
+        let mut dot_dot = DirEntry::parse(&block[pos.offset..pos.offset + DirEntry::LEN])?;
+        if dot_dot.filename != ShortFileName::DOT_DOT {
+            return Err(FsError::InvalidOperation);
+        }
+        dot_dot.cluster = parent_cluster(parent);
+        self.write_entry(&pos, &dot_dot)
+    }
+}
+
+impl<V: FatVolume> FileSystem for Fat<V> {
+    fn read_dir(&self, path: &str) -> FsResult<Box<dyn Iterator<Item = Metadata> + Send>> {
+        // DONE: read dir and return an iterator for all entries
+        let cluster = self.handle.dir_cluster(path)?;
+
+        let entries = self.handle.read_dir_entries(&cluster)?;
+        let metadata_list: Vec<Metadata> = entries
+            .into_iter()
+            .map(|entry| {
+                let entry_type = if entry.is_directory() {
+                    FileType::Directory
+                } else {
+                    FileType::File
+                };
+
+                Metadata::new(
+                    entry.filename(),
+                    entry_type,
+                    entry.size as usize,
+                    Some(entry.created_time),
+                    Some(entry.modified_time),
+                    Some(entry.accessed_time),
+                )
+            })
+            .collect();
+
+        Ok(Box::new(metadata_list.into_iter()))
+    }
+
+    fn open_file(&self, path: &str) -> FsResult<FileHandle> {
+        // DONE: open file and return a file handle
+        match self.handle.find_path_pos(path)? {
+            Some((entry, pos)) if entry.is_file() => self.file_handle(entry, pos, 0),
+            Some(_) => Err(FsError::NotAFile),
+            None => Err(FsError::FileNotFound),
+        }
+    }
+
+    fn metadata(&self, path: &str) -> FsResult<Metadata> {
+        // DONE: read metadata of the file / dir
+        if path == "/" || path.is_empty() {
+            // 根目录的元数据
+            return Ok(Metadata::new(
+                "/".to_string(),
+                FileType::Directory,
+                0,
+                None, // 根目录通常没有创建时间
+                None,
+                None,
+            ));
+        }
+
+        if let Some(entry) = self.handle.find_path(path)? {
+            let entry_type = if entry.is_directory() {
+                FileType::Directory
+            } else {
+                FileType::File
+            };
+
+            Ok(Metadata::new(
+                entry.filename(),
+                entry_type,
+                entry.size as usize,
+                Some(entry.created_time),
+                Some(entry.modified_time),
+                Some(entry.accessed_time),
+            ))
+        } else {
+            Err(FsError::FileNotFound)
+        }
+    }
+
+    fn exists(&self, path: &str) -> FsResult<bool> {
+        // DONE: check if the file / dir exists
+        if path == "/" || path.is_empty() {
+            return Ok(true);
+        }
+
+        Ok(self.handle.find_path(path)?.is_some())
+    }
+
+    /// Create the file, or truncate it if it exists
+    fn create_file(&self, path: &str) -> FsResult<FileHandle> {
+        let path = normalize(path);
+        let (parent, name) = split_parent(&path).ok_or(FsError::NotAFile)?;
+
+        let _guard = self.handle.lock().lock();
+        let dir = self.handle.dir_cluster(parent)?;
+        let (entry, pos) = match self.handle.find_entry_pos(&dir, name)? {
+            Some((entry, _)) if !entry.is_file() => return Err(FsError::NotAFile),
+            Some((entry, pos)) => (self.handle.truncate(entry, &pos)?, pos),
+            None => {
+                let (filename, long_name) = self.handle.entry_name(&dir, name)?;
+                let mut entry = DirEntry::new(filename, Attributes::ARCHIVE);
+                entry.long_name = long_name;
+                let pos = self.handle.add_entry(&dir, &entry)?;
+                (entry, pos)
+            }
+        };
+
+        self.file_handle(entry, pos, 0)
+    }
+
+    fn create_dir(&self, path: &str) -> FsResult {
+        let path = normalize(path);
+        let (parent, name) = split_parent(&path).ok_or(FsError::AlreadyExists)?;
+
+        let _guard = self.handle.lock().lock();
+        let dir = self.handle.dir_cluster(parent)?;
+        if self.handle.find_entry_pos(&dir, name)?.is_some() {
+            return Err(FsError::AlreadyExists);
+        }
+        self.handle.make_dir(&dir, name)?;
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &str) -> FsResult {
+        let _guard = self.handle.lock().lock();
+        match self.handle.find_path_pos(path)? {
+            Some((entry, pos)) if entry.is_file() => self.handle.remove_entry(&entry, &pos),
+            Some(_) => Err(FsError::NotAFile),
+            None => Err(FsError::FileNotFound),
+        }
+    }
+
+    fn remove_dir(&self, path: &str) -> FsResult {
+        let _guard = self.handle.lock().lock();
+        match self.handle.find_path_pos(path)? {
+            Some((entry, pos)) if entry.is_directory() => {
+                if !self.handle.is_dir_empty(&entry.cluster)? {
+                    return Err(FsError::NotEmpty);
+                }
+                self.handle.remove_entry(&entry, &pos)
+            }
+            Some(_) => Err(FsError::NotADirectory),
+            None if normalize(path) == "/" => Err(FsError::Busy),
+            None => Err(FsError::FileNotFound),
+        }
+    }
+
+    fn copy_file(&self, src: &str, dst: &str) -> FsResult {
+        let mut src = self.open_file(src)?;
+        if self.exists(dst)? {
+            return Err(FsError::AlreadyExists);
+        }
+        let mut dst = self.create_file(dst)?;
+
+        let mut buf = [0u8; BLOCK_SIZE];
+        loop {
+            match src.read(&mut buf)? {
+                0 => return Ok(()),
+                len => dst.write_all(&buf[..len])?,
+            }
+        }
+    }
+
+    fn move_file(&self, src: &str, dst: &str) -> FsResult {
+        self.rename(src, dst, false)
+    }
+
+    fn move_dir(&self, src: &str, dst: &str) -> FsResult {
+        if is_under(&normalize(dst), &normalize(src)) {
+            return Err(FsError::InvalidOperation);
+        }
+        self.rename(src, dst, true)
+    }
+
+    fn append_file(&self, path: &str) -> FsResult<FileHandle> {
+        match self.handle.find_path_pos(path)? {
+            Some((entry, pos)) if entry.is_file() => {
+                let len = entry.size as usize;
+                self.file_handle(entry, pos, len)
+            }
+            Some(_) => Err(FsError::NotAFile),
+            None => Err(FsError::FileNotFound),
+        }
+    }
+}
+
+impl<V: FatVolume> Fat<V> {
+    /// Move the entry at `src` to `dst`, which must not exist
+    fn rename(&self, src: &str, dst: &str, is_dir: bool) -> FsResult {
+        let dst = normalize(dst);
+        let (dst_parent, dst_name) = split_parent(&dst).ok_or(FsError::AlreadyExists)?;
+
+        let _guard = self.handle.lock().lock();
+        let (mut entry, pos) = self
+            .handle
+            .find_path_pos(src)?
+            .ok_or(FsError::FileNotFound)?;
+        match (is_dir, entry.is_directory()) {
+            (true, false) => return Err(FsError::NotADirectory),
+            (false, true) => return Err(FsError::NotAFile),
+            _ => {}
+        }
+
+        let parent = self.handle.dir_cluster(dst_parent)?;
+        if self.handle.find_entry_pos(&parent, dst_name)?.is_some() {
+            return Err(FsError::AlreadyExists);
+        }
+
+        (entry.filename, entry.long_name) = self.handle.entry_name(&parent, dst_name)?;
+        self.handle.add_entry(&parent, &entry)?;
+        self.handle.delete_entry(&pos)?;
+
+        if is_dir {
+            self.handle.set_parent(&entry, &parent)?;
+        }
+        Ok(())
+    }
+
+    /// Open the file of `entry` at `offset`
+    fn file_handle(&self, entry: DirEntry, pos: EntryPos, offset: usize) -> FsResult<FileHandle> {
+        let meta = entry.as_meta();
+        let mut file = File::new(self.handle.clone(), entry, pos);
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(FileHandle::new(meta, Box::new(file)))
+    }
+}
+
+/// The cluster stored in `..`, 0 for the root directory
+fn parent_cluster(parent: &Cluster) -> Cluster {
+    if *parent == Cluster::ROOT_DIR {
+        Cluster::EMPTY
+    } else {
+        *parent
+    }
+}
+
+/// The first cluster of a directory, `..` of a top-level directory is 0
+fn dir_start(entry: &DirEntry) -> Cluster {
+    if entry.cluster == Cluster::EMPTY {
+        Cluster::ROOT_DIR
+    } else {
+        entry.cluster
+    }
+}
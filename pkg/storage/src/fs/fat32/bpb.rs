@@ -0,0 +1,180 @@
+//! Fat32 BIOS Parameter Block
+//!
+//! The first 36 bytes are the same as in FAT16, followed by the
+//! FAT32 extended boot record.
+//!
+//! reference:
+//! - <https://en.wikipedia.org/wiki/BIOS_parameter_block>
+//! - <https://wiki.osdev.org/FAT#FAT_32>
+
+use crate::*;
+
+/// Represents a Boot Parameter Block.
+///
+/// This is the first sector of a FAT 32 formatted partition,
+/// and it describes various properties of the FAT 32 filesystem.
+pub struct Fat32Bpb {
+    data: [u8; 512],
+}
+
+impl Fat32Bpb {
+    /// Attempt to parse a Boot Parameter Block from a 512 byte sector,
+    /// fails if it is not a FAT32 BPB.
+    pub fn new(data: &[u8]) -> FsResult<Fat32Bpb> {
+        let data = data.try_into().map_err(|_| FsError::InvalidOperation)?;
+        let bpb = Fat32Bpb { data };
+
+        // FAT32 has no fixed root directory and keeps the FAT size in 32 bits
+        if bpb.trail() != 0xAA55
+            || bpb.root_entries_count() != 0
+            || bpb.sectors_per_fat_16() != 0
+            || bpb.sectors_per_fat() == 0
+        {
+            return Err(FsError::InvalidOperation);
+        }
+
+        Ok(bpb)
+    }
+
+    pub fn total_sectors(&self) -> u32 {
+        if self.total_sectors_16() == 0 {
+            self.total_sectors_32()
+        } else {
+            self.total_sectors_16() as u32
+        }
+    }
+
+    define_field!([u8; 8], 0x03, oem_name);
+    define_field!(u16, 0x0B, bytes_per_sector);
+    define_field!(u8, 0x0D, sectors_per_cluster);
+    define_field!(u16, 0x0E, reserved_sector_count);
+    define_field!(u8, 0x10, fat_count);
+    // 根目录条目数，FAT32 中为 0
+    define_field!(u16, 0x11, root_entries_count);
+    define_field!(u16, 0x13, total_sectors_16);
+    define_field!(u8, 0x15, media_descriptor);
+    // 16 位的每 FAT 扇区数，FAT32 中为 0
+    define_field!(u16, 0x16, sectors_per_fat_16);
+    define_field!(u16, 0x18, sectors_per_track);
+    define_field!(u16, 0x1A, track_count);
+    define_field!(u32, 0x1C, hidden_sectors);
+    define_field!(u32, 0x20, total_sectors_32);
+    // 扩展引导记录 (偏移 0x24 起)
+    define_field!(u32, 0x24, sectors_per_fat);
+    define_field!(u16, 0x28, ext_flags);
+    define_field!(u16, 0x2A, fs_version);
+    // 根目录的起始簇
+    define_field!(u32, 0x2C, root_cluster);
+    // FSInfo 结构所在的扇区
+    define_field!(u16, 0x30, fs_info_sector);
+    define_field!(u16, 0x32, backup_boot_sector);
+    define_field!(u8, 0x40, drive_number);
+    define_field!(u8, 0x41, reserved_flags);
+    define_field!(u8, 0x42, boot_signature);
+    define_field!(u32, 0x43, volume_id);
+    define_field!([u8; 11], 0x47, volume_label);
+    define_field!([u8; 8], 0x52, system_identifier);
+    define_field!(u16, 0x1FE, trail);
+}
+
+impl core::fmt::Debug for Fat32Bpb {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Fat32 BPB")
+            .field("OEM Name", &self.oem_name_str())
+            .field("Bytes per Sector", &self.bytes_per_sector())
+            .field("Sectors per Cluster", &self.sectors_per_cluster())
+            .field("Reserved Sector Count", &self.reserved_sector_count())
+            .field("FAT Count", &self.fat_count())
+            .field("Total Sectors", &self.total_sectors())
+            .field("Media Descriptor", &self.media_descriptor())
+            .field("Sectors per FAT", &self.sectors_per_fat())
+            .field("Sectors per Track", &self.sectors_per_track())
+            .field("Track Count", &self.track_count())
+            .field("Hidden Sectors", &self.hidden_sectors())
+            .field("Ext Flags", &self.ext_flags())
+            .field("FS Version", &self.fs_version())
+            .field("Root Cluster", &self.root_cluster())
+            .field("FSInfo Sector", &self.fs_info_sector())
+            .field("Backup Boot Sector", &self.backup_boot_sector())
+            .field("Drive Number", &self.drive_number())
+            .field("Boot Signature", &self.boot_signature())
+            .field("Volume ID", &self.volume_id())
+            .field("Volume Label", &self.volume_label_str())
+            .field("System Identifier", &self.system_identifier_str())
+            .field("Trail", &self.trail())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sector(data: &[u8]) -> Vec<u8> {
+        let mut bpb_data = Vec::with_capacity(512);
+        bpb_data.extend_from_slice(data);
+        bpb_data.resize(510, 0u8);
+        bpb_data.extend_from_slice(&[0x55, 0xAA]);
+        bpb_data
+    }
+
+    #[test]
+    fn test_fat32_bpb() {
+        // `mkfs.fat -F 32` on a 512 MiB partition
+        const DATA: [u8; 96] = hex_literal::hex!(
+            "EB 58 90 6D 6B 66 73 2E 66 61 74 00 02 08 20 00
+        02 00 00 00 00 F8 00 00 3F 00 FF 00 00 08 00 00
+        00 F8 0F 00 F8 03 00 00 00 00 00 00 02 00 00 00
+        01 00 06 00 00 00 00 00 00 00 00 00 00 00 00 00
+        80 00 29 C4 3B 7D 1E 4E 4F 20 4E 41 4D 45 20 20
+        20 20 46 41 54 33 32 20 20 20 0E 1F BE 77 7C AC"
+        );
+
+        let bpb = Fat32Bpb::new(&sector(&DATA)).unwrap();
+
+        assert_eq!(bpb.oem_name(), b"mkfs.fat");
+        assert_eq!(bpb.bytes_per_sector(), 512);
+        assert_eq!(bpb.sectors_per_cluster(), 8);
+        assert_eq!(bpb.reserved_sector_count(), 32);
+        assert_eq!(bpb.fat_count(), 2);
+        assert_eq!(bpb.root_entries_count(), 0);
+        assert_eq!(bpb.total_sectors_16(), 0);
+        assert_eq!(bpb.media_descriptor(), 0xf8);
+        assert_eq!(bpb.sectors_per_fat_16(), 0);
+        assert_eq!(bpb.sectors_per_track(), 63);
+        assert_eq!(bpb.track_count(), 255);
+        assert_eq!(bpb.hidden_sectors(), 2048);
+        assert_eq!(bpb.total_sectors_32(), 0xff800);
+        assert_eq!(bpb.sectors_per_fat(), 0x3f8);
+        assert_eq!(bpb.ext_flags(), 0);
+        assert_eq!(bpb.fs_version(), 0);
+        assert_eq!(bpb.root_cluster(), 2);
+        assert_eq!(bpb.fs_info_sector(), 1);
+        assert_eq!(bpb.backup_boot_sector(), 6);
+        assert_eq!(bpb.drive_number(), 0x80);
+        assert_eq!(bpb.reserved_flags(), 0);
+        assert_eq!(bpb.boot_signature(), 0x29);
+        assert_eq!(bpb.volume_id(), 0x1e7d3bc4);
+        assert_eq!(bpb.volume_label(), b"NO NAME    ");
+        assert_eq!(bpb.system_identifier(), b"FAT32   ");
+        assert_eq!(bpb.system_identifier_str(), "FAT32   ");
+
+        assert_eq!(bpb.total_sectors(), 0xff800);
+
+        println!("{:#?}", bpb);
+    }
+
+    #[test]
+    fn test_fat16_is_not_fat32() {
+        // Taken from QEMU VVFAT, see the FAT16 BPB tests
+        const DATA: [u8; 64] = hex_literal::hex!(
+            "EB 3E 90 4D 53 57 49 4E 34 2E 31 00 02 10 01 00
+        02 00 02 00 00 F8 FC 00 3F 00 10 00 3F 00 00 00
+        C1 BF 0F 00 80 00 29 FD 1A BE FA 51 45 4D 55 20
+        56 56 46 41 54 20 46 41 54 31 36 20 20 20 00 00"
+        );
+
+        assert!(Fat32Bpb::new(&sector(&DATA)).is_err());
+        assert!(Fat32Bpb::new(&[0u8; 512]).is_err());
+    }
+}
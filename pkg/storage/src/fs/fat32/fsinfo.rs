@@ -0,0 +1,114 @@
+//! FAT32 FSInfo Structure
+//!
+//! A sector, usually right after the boot sector, caching the number of
+//! free clusters and where to start looking for one. Both are only hints
+//! and may be `0xFFFFFFFF` when unknown.
+//!
+//! reference: <https://wiki.osdev.org/FAT#FSInfo_Structure_.28FAT32_only.29>
+
+use crate::*;
+
+const LEAD_SIGNATURE: u32 = 0x4161_5252;
+const STRUCT_SIGNATURE: u32 = 0x6141_7272;
+const TRAIL_SIGNATURE: u32 = 0xAA55_0000;
+
+/// The value of `free_count` and `next_free` when unknown
+pub const UNKNOWN: u32 = 0xFFFF_FFFF;
+
+#[derive(Clone)]
+pub struct FsInfo {
+    data: [u8; 512],
+}
+
+impl FsInfo {
+    /// Parse the FSInfo sector, fails if any of its signatures is wrong
+    pub fn new(data: &[u8]) -> FsResult<FsInfo> {
+        let data = data.try_into().map_err(|_| FsError::InvalidOperation)?;
+        let info = FsInfo { data };
+
+        if info.lead_signature() != LEAD_SIGNATURE
+            || info.struct_signature() != STRUCT_SIGNATURE
+            || info.trail_signature() != TRAIL_SIGNATURE
+        {
+            return Err(FsError::InvalidOperation);
+        }
+
+        Ok(info)
+    }
+
+    /// An FSInfo sector with unknown free count and next free cluster
+    pub fn empty() -> FsInfo {
+        let mut info = FsInfo { data: [0; 512] };
+        info.set(0x000, LEAD_SIGNATURE);
+        info.set(0x1E4, STRUCT_SIGNATURE);
+        info.set(0x1FC, TRAIL_SIGNATURE);
+        info.set_free_count(UNKNOWN);
+        info.set_next_free(UNKNOWN);
+        info
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 512] {
+        &self.data
+    }
+
+    define_field!(u32, 0x000, lead_signature);
+    define_field!(u32, 0x1E4, struct_signature);
+    // 空闲簇数
+    define_field!(u32, 0x1E8, free_count);
+    // 从该簇开始查找空闲簇
+    define_field!(u32, 0x1EC, next_free);
+    define_field!(u32, 0x1FC, trail_signature);
+
+    pub fn set_free_count(&mut self, count: u32) {
+        self.set(0x1E8, count);
+    }
+
+    pub fn set_next_free(&mut self, cluster: u32) {
+        self.set(0x1EC, cluster);
+    }
+
+    fn set(&mut self, offset: usize, value: u32) {
+        self.data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+impl core::fmt::Debug for FsInfo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FsInfo")
+            .field("Free Count", &self.free_count())
+            .field("Next Free", &self.next_free())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fs_info() {
+        let mut data = [0u8; 512];
+        data[..4].copy_from_slice(b"RRaA");
+        data[0x1E4..0x1E8].copy_from_slice(b"rrAa");
+        data[0x1E8..0x1EC].copy_from_slice(&hex_literal::hex!("2C FF 01 00"));
+        data[0x1EC..0x1F0].copy_from_slice(&hex_literal::hex!("05 00 00 00"));
+        data[0x1FE..].copy_from_slice(&[0x55, 0xAA]);
+
+        let mut info = FsInfo::new(&data).unwrap();
+        assert_eq!(info.free_count(), 0x1ff2c);
+        assert_eq!(info.next_free(), 5);
+
+        info.set_free_count(0x1ff2b);
+        info.set_next_free(6);
+        let info = FsInfo::new(info.as_bytes()).unwrap();
+        assert_eq!(info.free_count(), 0x1ff2b);
+        assert_eq!(info.next_free(), 6);
+
+        let empty = FsInfo::new(FsInfo::empty().as_bytes()).unwrap();
+        assert_eq!(empty.free_count(), UNKNOWN);
+        assert_eq!(empty.next_free(), UNKNOWN);
+
+        data[0x1E4] = 0;
+        assert!(FsInfo::new(&data).is_err());
+    }
+}
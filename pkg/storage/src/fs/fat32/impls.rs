@@ -0,0 +1,168 @@
+use super::*;
+
+/// FAT32 条目只有低 28 位是簇号，高 4 位保留
+const ENTRY_MASK: u32 = 0x0FFF_FFFF;
+
+impl Fat32Impl {
+    pub fn new(inner: impl BlockDevice<Block512>) -> Self {
+        Self::try_new(inner).expect("Failed to load Fat32 volume")
+    }
+
+    /// Load the volume, fails if the first block is not a FAT32 BPB
+    pub fn try_new(inner: impl BlockDevice<Block512>) -> FsResult<Self> {
+        let mut block = Block::default();
+
+        inner.read_block(0, &mut block)?;
+        let bpb = Fat32Bpb::new(block.as_ref())?;
+
+        trace!("Loading Fat32 Volume: {:#?}", bpb);
+
+        // FirstDataSector = BPB_ResvdSecCnt + (BPB_NumFATs * BPB_FATSz32)，没有根目录区域
+        let fat_start = bpb.reserved_sector_count() as usize;
+        let first_data_sector =
+            fat_start + bpb.fat_count() as usize * bpb.sectors_per_fat() as usize;
+
+        // FSInfo 中只是提示信息，无效时视为未知
+        let fs_info = match bpb.fs_info_sector() {
+            0 | 0xFFFF => FsInfo::empty(),
+            sector => {
+                inner.read_block(sector as usize, &mut block)?;
+                FsInfo::new(block.as_ref()).unwrap_or_else(|_| FsInfo::empty())
+            }
+        };
+
+        Ok(Self {
+            bpb,
+            inner: Box::new(inner),
+            fat_start,
+            first_data_sector,
+            fs_info: spin::Mutex::new(fs_info),
+            lock: spin::Mutex::new(()),
+        })
+    }
+
+    /// FAT 条目所在的扇区（相对于 FAT 起始）和扇区内的偏移
+    fn fat_entry_pos(&self, cluster: u32) -> (usize, usize) {
+        // FAT32 中每个 FAT 条目占 4 字节
+        let fat_offset = cluster as usize * 4;
+        let bytes_per_sector = self.bpb.bytes_per_sector() as usize;
+        (fat_offset / bytes_per_sector, fat_offset % bytes_per_sector)
+    }
+
+    /// 簇被分配或释放后更新 FSInfo 并写回磁盘
+    fn update_fs_info(&self, cluster: u32, allocated: bool) -> FsResult {
+        let mut info = self.fs_info.lock();
+
+        let free = info.free_count();
+        if free != fsinfo::UNKNOWN {
+            info.set_free_count(if allocated { free.saturating_sub(1) } else { free + 1 });
+        }
+        if allocated {
+            info.set_next_free(cluster + 1);
+        }
+
+        match self.bpb.fs_info_sector() {
+            0 | 0xFFFF => Ok(()),
+            sector => {
+                let block = Block::new(info.as_bytes());
+                self.inner.write_block(sector as usize, &block)
+            }
+        }
+    }
+}
+
+impl FatVolume for Fat32Impl {
+    const END_OF_CHAIN: u32 = 0x0FFF_FFFF;
+
+    fn device(&self) -> &dyn BlockDevice<Block512> {
+        self.inner.as_ref()
+    }
+
+    fn lock(&self) -> &spin::Mutex<()> {
+        &self.lock
+    }
+
+    fn bytes_per_sector(&self) -> usize {
+        self.bpb.bytes_per_sector() as usize
+    }
+
+    fn sectors_per_cluster(&self) -> usize {
+        self.bpb.sectors_per_cluster() as usize
+    }
+
+    fn root_dir(&self) -> RootDir {
+        RootDir::Chain(self.bpb.root_cluster())
+    }
+
+    fn cluster_to_sector(&self, cluster: &Cluster) -> usize {
+        let cluster = match *cluster {
+            Cluster::ROOT_DIR => self.bpb.root_cluster(),
+            Cluster(c) => c.max(2),
+        };
+        (cluster - 2) as usize * self.bpb.sectors_per_cluster() as usize + self.first_data_sector
+    }
+
+    fn cluster_count(&self) -> usize {
+        let data_sectors =
+            (self.bpb.total_sectors() as usize).saturating_sub(self.first_data_sector);
+        let fat_entries =
+            self.bpb.sectors_per_fat() as usize * self.bpb.bytes_per_sector() as usize / 4;
+        (data_sectors / self.bpb.sectors_per_cluster() as usize).min(fat_entries.saturating_sub(2))
+    }
+
+    fn fat_entry(&self, cluster: u32) -> FsResult<u32> {
+        let (sector, offset) = self.fat_entry_pos(cluster);
+        let mut block = Block::default();
+        self.inner.read_block(self.fat_start + sector, &mut block)?;
+        let entry = u32::from_le_bytes(block[offset..offset + 4].try_into().unwrap());
+        Ok(entry & ENTRY_MASK)
+    }
+
+    fn set_fat_entry(&self, cluster: u32, value: u32) -> FsResult {
+        let (sector, offset) = self.fat_entry_pos(cluster);
+        let value = value & ENTRY_MASK;
+        let mut block = Block::default();
+        let mut old = 0;
+
+        for i in 0..self.bpb.fat_count() as usize {
+            let sector = self.fat_start + i * self.bpb.sectors_per_fat() as usize + sector;
+            self.inner.read_block(sector, &mut block)?;
+            let entry = u32::from_le_bytes(block[offset..offset + 4].try_into().unwrap());
+            if i == 0 {
+                old = entry & ENTRY_MASK;
+            }
+            let entry = (entry & !ENTRY_MASK) | value;
+            block.as_mut()[offset..offset + 4].copy_from_slice(&entry.to_le_bytes());
+            self.inner.write_block(sector, &block)?;
+        }
+
+        if (old == 0) != (value == 0) {
+            self.update_fs_info(cluster, old == 0)?;
+        }
+        Ok(())
+    }
+
+    /// 从 FSInfo 记录的位置开始查找，到末尾后从头继续
+    fn find_free_cluster(&self) -> FsResult<Option<u32>> {
+        let end = self.cluster_count() as u32 + 2;
+        let start = match self.fs_info.lock().next_free() {
+            next @ 2.. if next < end => next,
+            _ => 2,
+        };
+
+        let mut block = Block::default();
+        let mut loaded = None;
+        for cluster in (start..end).chain(2..start) {
+            let (sector, offset) = self.fat_entry_pos(cluster);
+            if loaded != Some(sector) {
+                self.inner.read_block(self.fat_start + sector, &mut block)?;
+                loaded = Some(sector);
+            }
+            let entry = u32::from_le_bytes(block[offset..offset + 4].try_into().unwrap());
+            if entry & ENTRY_MASK == 0 {
+                return Ok(Some(cluster));
+            }
+        }
+        Ok(None)
+    }
+}
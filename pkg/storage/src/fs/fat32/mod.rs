@@ -0,0 +1,191 @@
+pub mod bpb;
+pub mod fsinfo;
+pub mod impls;
+
+use crate::fat16::direntry::*;
+use crate::fat16::volume::*;
+use crate::*;
+
+use bpb::Fat32Bpb;
+use fsinfo::FsInfo;
+
+/// Identifies a Fat32 filesystem on the disk.
+pub type Fat32 = Fat<Fat32Impl>;
+
+impl Fat32 {
+    pub fn new(inner: impl BlockDevice<Block512>) -> Self {
+        Self {
+            handle: Arc::new(Fat32Impl::new(inner)),
+        }
+    }
+
+    pub fn try_new(inner: impl BlockDevice<Block512>) -> FsResult<Self> {
+        Ok(Self {
+            handle: Arc::new(Fat32Impl::try_new(inner)?),
+        })
+    }
+}
+
+/// The Fat32 filesystem.
+///
+/// Unlike FAT16 there is no fixed root directory region, the root
+/// directory is a cluster chain starting at `bpb.root_cluster()`.
+///
+/// [ BPB ] [ FSInfo ] [ Reserved ] [ FATs ] [ Data ]
+pub struct Fat32Impl {
+    pub(crate) inner: Box<dyn BlockDevice<Block512>>,
+    pub bpb: Fat32Bpb,
+    pub fat_start: usize,
+    pub first_data_sector: usize,
+    /// Free cluster hints, written back whenever a cluster is allocated or freed
+    pub(crate) fs_info: spin::Mutex<FsInfo>,
+    /// Held while the FAT or a directory is modified
+    pub(crate) lock: spin::Mutex<()>,
+}
+
+impl core::fmt::Debug for Fat32Impl {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Fat32Impl")
+            .field("bpb", &self.bpb)
+            .field("fs_info", &*self.fs_info.lock())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fat16::tests::{read_to_end, RamDisk};
+
+    /// 4096 sectors, one sector per cluster, two FATs of 32 sectors
+    fn disk() -> RamDisk {
+        let mut disk = vec![Block512::default(); 4096];
+
+        let bpb = disk[0].as_mut();
+        bpb[0x0B..0x0D].copy_from_slice(&512u16.to_le_bytes());
+        bpb[0x0D] = 1;
+        bpb[0x0E..0x10].copy_from_slice(&32u16.to_le_bytes());
+        bpb[0x10] = 2;
+        bpb[0x20..0x24].copy_from_slice(&4096u32.to_le_bytes());
+        bpb[0x24..0x28].copy_from_slice(&32u32.to_le_bytes());
+        bpb[0x2C..0x30].copy_from_slice(&2u32.to_le_bytes());
+        bpb[0x30..0x32].copy_from_slice(&1u16.to_le_bytes());
+        bpb[0x1FE..0x200].copy_from_slice(&0xAA55u16.to_le_bytes());
+
+        let mut info = FsInfo::empty();
+        info.set_free_count(3999);
+        info.set_next_free(3);
+        disk[1].as_mut().copy_from_slice(info.as_bytes());
+
+        // cluster 2 is the root directory
+        for fat in [32, 64] {
+            disk[fat].as_mut()[..12].copy_from_slice(&hex_literal::hex!(
+                "F8 FF FF 0F FF FF FF 0F FF FF FF 0F"
+            ));
+        }
+
+        RamDisk(spin::Mutex::new(disk))
+    }
+
+    fn format() -> Fat32 {
+        Fat32::new(disk())
+    }
+
+    fn free_count(fs: &Fat32) -> u32 {
+        let mut block = Block512::default();
+        fs.handle.inner.read_block(1, &mut block).unwrap();
+        let info = FsInfo::new(block.as_ref()).unwrap();
+        assert_eq!(info.free_count(), fs.handle.fs_info.lock().free_count());
+        info.free_count()
+    }
+
+    #[test]
+    fn open_by_bpb() {
+        let fs = open_fat(disk()).unwrap();
+        assert!(format!("{:?}", fs).starts_with("Fat32Impl"));
+    }
+
+    #[test]
+    fn root_dir_chain() {
+        let fs = format();
+        assert_eq!(fs.handle.cluster_count(), 4000);
+
+        // each name takes a long name entry and a short one, 16 entries per cluster
+        let names: Vec<String> = (0..20).map(|i| format!("file{}.txt", i)).collect();
+        for name in &names {
+            fs.create_file(&format!("/{}", name)).unwrap();
+        }
+        fs.create_file("/file7.txt").unwrap().write_all(b"seven").unwrap();
+
+        let listed: Vec<String> = fs.read_dir("/").unwrap().map(|m| m.name).collect();
+        assert_eq!(listed, names);
+        assert_eq!(read_to_end(&fs, "/FILE7.TXT"), b"seven");
+
+        // the root directory grew to three clusters
+        let handle = &fs.handle;
+        assert_eq!(handle.get_next_cluster(2).unwrap(), Some(3));
+        assert_eq!(handle.get_next_cluster(3).unwrap(), Some(4));
+        assert_eq!(handle.get_next_cluster(4).unwrap(), None);
+        assert_eq!(free_count(&fs), 3999 - 3);
+
+        fs.remove_file("/file7.txt").unwrap();
+        assert_eq!(free_count(&fs), 3999 - 2);
+        assert!(!fs.exists("/file7.txt").unwrap());
+    }
+
+    #[test]
+    fn fat_entries_are_28_bits() {
+        let fs = format();
+        let handle = &fs.handle;
+
+        // the high 4 bits are reserved and kept as they are
+        let mut block = Block512::default();
+        for fat in [32, 64] {
+            handle.inner.read_block(fat, &mut block).unwrap();
+            block.as_mut()[40..44].copy_from_slice(&0xF000_0000u32.to_le_bytes());
+            handle.inner.write_block(fat, &block).unwrap();
+        }
+        assert_eq!(handle.fat_entry(10).unwrap(), 0);
+
+        handle.set_fat_entry(10, 0x0FFF_FFFF).unwrap();
+        assert_eq!(handle.fat_entry(10).unwrap(), 0x0FFF_FFFF);
+        assert_eq!(handle.get_next_cluster(10).unwrap(), None);
+        for fat in [32, 64] {
+            handle.inner.read_block(fat, &mut block).unwrap();
+            assert_eq!(block[40..44], 0xFFFF_FFFFu32.to_le_bytes());
+        }
+        assert_eq!(free_count(&fs), 3998);
+
+        handle.set_fat_entry(10, 0).unwrap();
+        assert_eq!(free_count(&fs), 3999);
+    }
+
+    #[test]
+    fn directories() {
+        let fs = format();
+        fs.create_dir("/a").unwrap();
+        fs.create_dir("/a/Sub Directory").unwrap();
+        fs.create_file("/a/sub directory/data.bin")
+            .unwrap()
+            .write_all(&[7; 1000])
+            .unwrap();
+        assert_eq!(free_count(&fs), 3999 - 4);
+
+        fs.move_dir("/a/Sub Directory", "/b").unwrap();
+        assert_eq!(read_to_end(&fs, "/b/data.bin"), [7; 1000]);
+
+        // `..` of a directory in the root is 0, not the root cluster
+        let b = fs.handle.find_path("/b").unwrap().unwrap();
+        let entries = fs.handle.read_dir_entries(&b.cluster).unwrap();
+        assert_eq!(entries[1].filename, ShortFileName::DOT_DOT);
+        assert_eq!(entries[1].cluster, Cluster::EMPTY);
+
+        assert_eq!(fs.remove_dir("/b"), Err(FsError::NotEmpty));
+        fs.remove_file("/b/data.bin").unwrap();
+        fs.remove_dir("/b").unwrap();
+        fs.remove_dir("/a").unwrap();
+        assert_eq!(fs.remove_dir("/"), Err(FsError::Busy));
+        assert_eq!(free_count(&fs), 3999);
+        assert_eq!(fs.read_dir("/").unwrap().count(), 0);
+    }
+}
@@ -1 +1,19 @@
 pub mod fat16;
+pub mod fat32;
+pub mod tmpfs;
+
+use crate::*;
+use fat16::Fat16;
+use fat32::{bpb::Fat32Bpb, Fat32};
+
+/// Open the FAT filesystem on `inner`, FAT32 or FAT16 depending on its BPB
+pub fn open_fat(inner: impl BlockDevice<Block512>) -> FsResult<Box<dyn FileSystem>> {
+    let mut block = Block512::default();
+    inner.read_block(0, &mut block)?;
+
+    if Fat32Bpb::new(block.as_ref()).is_ok() {
+        Ok(Box::new(Fat32::try_new(inner)?))
+    } else {
+        Ok(Box::new(Fat16::try_new(inner)?))
+    }
+}
@@ -0,0 +1,267 @@
+//! A filesystem kept in memory
+//!
+//! Open files share the data with the tree, writes are seen by every
+//! handle of the same file.
+
+use crate::*;
+use alloc::collections::BTreeMap;
+use spin::RwLock;
+
+type FileData = Arc<RwLock<Vec<u8>>>;
+
+#[derive(Debug)]
+enum Node {
+    File(FileData),
+    Dir(BTreeMap<String, Node>),
+}
+
+impl Node {
+    fn meta(&self, name: &str) -> Metadata {
+        match self {
+            Node::File(data) => {
+                Metadata::new(name.into(), FileType::File, data.read().len(), None, None, None)
+            }
+            Node::Dir(_) => Metadata::new(name.into(), FileType::Directory, 0, None, None, None),
+        }
+    }
+
+    fn find(&self, path: &str) -> FsResult<&Node> {
+        let mut node = self;
+        for part in path.split(PATH_SEPARATOR).filter(|p| !p.is_empty()) {
+            node = match node {
+                Node::Dir(children) => children.get(part).ok_or(FsError::FileNotFound)?,
+                Node::File(_) => return Err(FsError::NotADirectory),
+            };
+        }
+        Ok(node)
+    }
+
+    fn find_dir_mut(&mut self, path: &str) -> FsResult<&mut BTreeMap<String, Node>> {
+        let mut node = self;
+        for part in path.split(PATH_SEPARATOR).filter(|p| !p.is_empty()) {
+            node = match node {
+                Node::Dir(children) => children.get_mut(part).ok_or(FsError::FileNotFound)?,
+                Node::File(_) => return Err(FsError::NotADirectory),
+            };
+        }
+        match node {
+            Node::Dir(children) => Ok(children),
+            Node::File(_) => Err(FsError::NotADirectory),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Tmpfs {
+    root: RwLock<Node>,
+}
+
+impl Default for Tmpfs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tmpfs {
+    pub fn new() -> Self {
+        Self {
+            root: RwLock::new(Node::Dir(BTreeMap::new())),
+        }
+    }
+
+    fn split(path: &str) -> FsResult<(String, String)> {
+        let path = normalize(path);
+        let (parent, name) = split_parent(&path).ok_or(FsError::InvalidOperation)?;
+        Ok((parent.into(), name.into()))
+    }
+
+    fn open_data(&self, path: &str) -> FsResult<(Metadata, FileData)> {
+        let root = self.root.read();
+        let node = root.find(path)?;
+        let path = normalize(path);
+        let name = split_parent(&path).map_or("/", |(_, name)| name);
+        match node {
+            Node::File(data) => Ok((node.meta(name), data.clone())),
+            Node::Dir(_) => Err(FsError::NotAFile),
+        }
+    }
+}
+
+impl FileSystem for Tmpfs {
+    fn read_dir(&self, path: &str) -> FsResult<Box<dyn Iterator<Item = Metadata> + Send>> {
+        let root = self.root.read();
+        match root.find(path)? {
+            Node::Dir(children) => {
+                let entries: Vec<Metadata> =
+                    children.iter().map(|(name, node)| node.meta(name)).collect();
+                Ok(Box::new(entries.into_iter()))
+            }
+            Node::File(_) => Err(FsError::NotADirectory),
+        }
+    }
+
+    fn open_file(&self, path: &str) -> FsResult<FileHandle> {
+        let (meta, data) = self.open_data(path)?;
+        Ok(FileHandle::new(meta, Box::new(TmpFile { data, offset: 0 })))
+    }
+
+    fn metadata(&self, path: &str) -> FsResult<Metadata> {
+        let path = normalize(path);
+        let name = split_parent(&path).map_or("/", |(_, name)| name);
+        Ok(self.root.read().find(&path)?.meta(name))
+    }
+
+    fn exists(&self, path: &str) -> FsResult<bool> {
+        match self.root.read().find(path) {
+            Ok(_) => Ok(true),
+            Err(FsError::FileNotFound) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Create the file, or truncate it if it exists
+    fn create_file(&self, path: &str) -> FsResult<FileHandle> {
+        let (parent, name) = Self::split(path)?;
+        let mut root = self.root.write();
+        let dir = root.find_dir_mut(&parent)?;
+
+        let data = match dir.get(&name) {
+            Some(Node::File(data)) => {
+                data.write().clear();
+                data.clone()
+            }
+            Some(Node::Dir(_)) => return Err(FsError::NotAFile),
+            None => {
+                let data = FileData::default();
+                dir.insert(name.clone(), Node::File(data.clone()));
+                data
+            }
+        };
+
+        let meta = Metadata::new(name, FileType::File, 0, None, None, None);
+        Ok(FileHandle::new(meta, Box::new(TmpFile { data, offset: 0 })))
+    }
+
+    fn append_file(&self, path: &str) -> FsResult<FileHandle> {
+        let (meta, data) = self.open_data(path)?;
+        let offset = meta.len;
+        Ok(FileHandle::new(meta, Box::new(TmpFile { data, offset })))
+    }
+
+    fn create_dir(&self, path: &str) -> FsResult {
+        let (parent, name) = Self::split(path)?;
+        let mut root = self.root.write();
+        let dir = root.find_dir_mut(&parent)?;
+
+        if dir.contains_key(&name) {
+            return Err(FsError::AlreadyExists);
+        }
+        dir.insert(name, Node::Dir(BTreeMap::new()));
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &str) -> FsResult {
+        let (parent, name) = Self::split(path)?;
+        let mut root = self.root.write();
+        let dir = root.find_dir_mut(&parent)?;
+
+        match dir.get(&name) {
+            Some(Node::File(_)) => {
+                dir.remove(&name);
+                Ok(())
+            }
+            Some(Node::Dir(_)) => Err(FsError::NotAFile),
+            None => Err(FsError::FileNotFound),
+        }
+    }
+
+    fn remove_dir(&self, path: &str) -> FsResult {
+        let (parent, name) = Self::split(path)?;
+        let mut root = self.root.write();
+        let dir = root.find_dir_mut(&parent)?;
+
+        match dir.get(&name) {
+            Some(Node::Dir(children)) if !children.is_empty() => Err(FsError::NotEmpty),
+            Some(Node::Dir(_)) => {
+                dir.remove(&name);
+                Ok(())
+            }
+            Some(Node::File(_)) => Err(FsError::NotADirectory),
+            None => Err(FsError::FileNotFound),
+        }
+    }
+
+    fn move_file(&self, src: &str, dst: &str) -> FsResult {
+        let (src_parent, src_name) = Self::split(src)?;
+        let (dst_parent, dst_name) = Self::split(dst)?;
+        let mut root = self.root.write();
+
+        if root.find(dst).is_ok() {
+            return Err(FsError::AlreadyExists);
+        }
+        root.find_dir_mut(&dst_parent)?;
+
+        let node = root
+            .find_dir_mut(&src_parent)?
+            .remove(&src_name)
+            .ok_or(FsError::FileNotFound)?;
+        root.find_dir_mut(&dst_parent)?.insert(dst_name, node);
+        Ok(())
+    }
+
+    fn move_dir(&self, src: &str, dst: &str) -> FsResult {
+        if is_under(&normalize(dst), &normalize(src)) {
+            return Err(FsError::InvalidOperation);
+        }
+        self.move_file(src, dst)
+    }
+}
+
+/// An open file of `Tmpfs`
+struct TmpFile {
+    data: FileData,
+    offset: usize,
+}
+
+impl Read for TmpFile {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
+        let data = self.data.read();
+        let rest = &data[self.offset.min(data.len())..];
+        let len = rest.len().min(buf.len());
+        buf[..len].copy_from_slice(&rest[..len]);
+        self.offset += len;
+        Ok(len)
+    }
+}
+
+impl Write for TmpFile {
+    fn write(&mut self, buf: &[u8]) -> FsResult<usize> {
+        let mut data = self.data.write();
+        let end = self.offset + buf.len();
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[self.offset..end].copy_from_slice(buf);
+        self.offset = end;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> FsResult {
+        Ok(())
+    }
+}
+
+impl Seek for TmpFile {
+    fn seek(&mut self, pos: SeekFrom) -> FsResult<usize> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => offset as isize,
+            SeekFrom::End(offset) => self.data.read().len() as isize + offset,
+            SeekFrom::Current(offset) => self.offset as isize + offset,
+        };
+        if offset < 0 {
+            return Err(FsError::InvalidOffset);
+        }
+        self.offset = offset as usize;
+        Ok(self.offset)
+    }
+}
@@ -65,7 +65,7 @@ where
     B: BlockTrait,
 {
     fn block_count(&self) -> FsResult<usize> {
-        self.inner.block_count()
+        Ok(self.size)
     }
 
     fn read_block(&self, offset: usize, block: &mut B) -> FsResult {
@@ -0,0 +1,161 @@
+use num_enum::FromPrimitive;
+
+/// Largest error number that may be encoded in a syscall return value.
+///
+/// Return values in `[-MAX_ERRNO, -1]` (as `isize`) are errors, everything
+/// else is a successful result. This is the same convention Linux uses.
+pub const MAX_ERRNO: usize = 4095;
+
+pub type SyscallResult<T = usize> = Result<T, SyscallError>;
+
+/// Error codes shared by the kernel and the user library.
+///
+/// The values follow the classic errno numbering, so they are stable
+/// across kernel and user space.
+#[repr(usize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
+pub enum SyscallError {
+    /// Operation not permitted (EPERM)
+    PermissionDenied = 1,
+    /// No such file or directory (ENOENT)
+    NotFound = 2,
+    /// No such process (ESRCH)
+    NoSuchProcess = 3,
+    /// Interrupted system call (EINTR)
+    Interrupted = 4,
+    /// I/O error (EIO)
+    IoError = 5,
+    /// Argument list too long (E2BIG)
+    ArgListTooLong = 7,
+    /// Exec format error (ENOEXEC)
+    ExecFormat = 8,
+    /// Bad file descriptor (EBADF)
+    BadFd = 9,
+    /// No child processes (ECHILD)
+    NoChild = 10,
+    /// Resource temporarily unavailable (EAGAIN)
+    WouldBlock = 11,
+    /// Out of memory (ENOMEM)
+    NoMemory = 12,
+    /// Bad address (EFAULT)
+    BadAddress = 14,
+    /// Device or resource busy (EBUSY)
+    Busy = 16,
+    /// File exists (EEXIST)
+    AlreadyExists = 17,
+    /// Not a directory (ENOTDIR)
+    NotADirectory = 20,
+    /// Is a directory (EISDIR)
+    IsADirectory = 21,
+    /// Invalid argument (EINVAL)
+    InvalidArgument = 22,
+    /// Too many open files (EMFILE)
+    TooManyFiles = 24,
+    /// No space left on device (ENOSPC)
+    NoSpace = 28,
+    /// Read-only file system (EROFS)
+    ReadOnly = 30,
+    /// File name too long (ENAMETOOLONG)
+    NameTooLong = 36,
+    /// Function not implemented (ENOSYS)
+    NotSupported = 38,
+    /// Directory not empty (ENOTEMPTY)
+    NotEmpty = 39,
+
+    #[num_enum(default)]
+    Unknown = MAX_ERRNO,
+}
+
+impl SyscallError {
+    /// The errno value of this error
+    #[inline]
+    pub fn errno(self) -> usize {
+        self as usize
+    }
+
+    /// Encode the error as a syscall return value (`-errno`)
+    #[inline]
+    pub fn encode(self) -> usize {
+        (self as usize).wrapping_neg()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PermissionDenied => "Operation not permitted",
+            Self::NotFound => "No such file or directory",
+            Self::NoSuchProcess => "No such process",
+            Self::Interrupted => "Interrupted system call",
+            Self::IoError => "I/O error",
+            Self::ArgListTooLong => "Argument list too long",
+            Self::ExecFormat => "Exec format error",
+            Self::BadFd => "Bad file descriptor",
+            Self::NoChild => "No child processes",
+            Self::WouldBlock => "Resource temporarily unavailable",
+            Self::NoMemory => "Out of memory",
+            Self::BadAddress => "Bad address",
+            Self::Busy => "Device or resource busy",
+            Self::AlreadyExists => "File exists",
+            Self::NotADirectory => "Not a directory",
+            Self::IsADirectory => "Is a directory",
+            Self::InvalidArgument => "Invalid argument",
+            Self::TooManyFiles => "Too many open files",
+            Self::NoSpace => "No space left on device",
+            Self::ReadOnly => "Read-only file system",
+            Self::NameTooLong => "File name too long",
+            Self::NotSupported => "Function not implemented",
+            Self::NotEmpty => "Directory not empty",
+            Self::Unknown => "Unknown error",
+        }
+    }
+}
+
+impl core::fmt::Display for SyscallError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Encode a syscall result into the value placed in `rax`
+#[inline]
+pub fn encode_result(result: SyscallResult) -> usize {
+    match result {
+        Ok(value) => value,
+        Err(err) => err.encode(),
+    }
+}
+
+/// Decode the value returned in `rax` into a syscall result
+#[inline]
+pub fn decode_result(ret: usize) -> SyscallResult {
+    if ret >= MAX_ERRNO.wrapping_neg() {
+        Err(SyscallError::from(ret.wrapping_neg()))
+    } else {
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        assert_eq!(decode_result(0), Ok(0));
+        assert_eq!(decode_result(0x2000_0000_0000), Ok(0x2000_0000_0000));
+        assert_eq!(decode_result(usize::MAX - MAX_ERRNO), Ok(usize::MAX - MAX_ERRNO));
+
+        for err in [
+            SyscallError::NotFound,
+            SyscallError::BadAddress,
+            SyscallError::BadFd,
+            SyscallError::NoMemory,
+            SyscallError::Unknown,
+        ] {
+            assert_eq!(decode_result(encode_result(Err(err))), Err(err));
+        }
+
+        assert_eq!(SyscallError::BadFd.encode() as isize, -9);
+        assert_eq!(decode_result(-1isize as usize), Err(SyscallError::PermissionDenied));
+        assert_eq!(decode_result(-100isize as usize), Err(SyscallError::Unknown));
+    }
+}
@@ -0,0 +1,49 @@
+//! Arguments of `open`, `mount` and `umount`
+
+use num_enum::TryFromPrimitive;
+
+/// `flags` of `open`: create the file if it does not exist
+pub const O_CREATE: usize = 1;
+/// `flags` of `open`: truncate the file to zero length
+pub const O_TRUNC: usize = 2;
+/// `flags` of `open`: start writing at the end of the file
+pub const O_APPEND: usize = 4;
+
+/// `flags` of `mount`: reject every write to the filesystem
+pub const MOUNT_READ_ONLY: usize = 1;
+
+/// Filesystems that can be mounted
+#[repr(usize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromPrimitive)]
+pub enum FsType {
+    /// FAT on a block device under `/dev`
+    Fat = 0,
+    /// Files kept in memory, lost on unmount
+    Tmpfs = 1,
+    /// Kernel state, see `/proc`
+    Procfs = 2,
+    /// Device nodes, see `/dev`
+    Devfs = 3,
+}
+
+impl FsType {
+    /// Parse the name used by `mount`, e.g. `fat` or `tmpfs`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fat" | "vfat" | "fat16" | "fat32" => Some(Self::Fat),
+            "tmpfs" => Some(Self::Tmpfs),
+            "proc" | "procfs" => Some(Self::Procfs),
+            "dev" | "devfs" => Some(Self::Devfs),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fat => "fat",
+            Self::Tmpfs => "tmpfs",
+            Self::Procfs => "proc",
+            Self::Devfs => "devfs",
+        }
+    }
+}
@@ -1,8 +1,16 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 use num_enum::FromPrimitive;
 
+pub mod error;
+pub mod fs;
 pub mod macros;
+pub mod proc;
+pub mod sched;
+pub mod signal;
+pub mod wait;
+
+pub use error::*;
 
 #[repr(usize)]
 #[derive(Clone, Debug, FromPrimitive)]
@@ -13,18 +21,38 @@ pub enum Syscall {
     Open = 14,
     Close = 15,
     ListDir = 16,
+    Yield = 24,
+    Nice = 34,
+    Sleep = 35,
     GetPid = 39,
     
     VFork = 40,
+    Fork = 57,
+    Exec = 58,
     Spawn = 59,
     Exit = 60,
     WaitPid = 61,
     Kill = 62,
     Sem = 66,
     Brk = 67,
+    Rename = 82,
+    Mkdir = 83,
+    Rmdir = 84,
+    Unlink = 87,
+
+    Trace = 101,
+    ThreadCreate = 102,
+    ThreadJoin = 103,
+    SigAction = 104,
+    SigProcMask = 105,
+    SigReturn = 106,
+    ProcInfo = 107,
+    SetPriority = 141,
+    Sync = 162,
+    Mount = 165,
+    Umount = 166,
+    Time = 201,
 
-    ListApp = 65529,
-    Stat = 65530,
     Allocate = 65533,
     Deallocate = 65534,
 
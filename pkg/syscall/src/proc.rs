@@ -0,0 +1,108 @@
+//! Process records returned by `Syscall::ProcInfo`
+//!
+//! The caller passes a buffer of `ProcInfo`, the kernel fills as many
+//! records as fit and returns the number of processes, so the caller
+//! can retry with a larger buffer.
+
+use core::fmt;
+
+/// Longest process name kept in a record, in bytes
+pub const PROC_NAME_LEN: usize = 32;
+
+/// `ProcInfo::cpu` of a process that is not running
+pub const NO_CPU: u8 = u8::MAX;
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProcStatus {
+    Running,
+    #[default]
+    Ready,
+    Blocked,
+    /// exited, waiting to be reaped by its parent
+    Dead,
+}
+
+impl fmt::Display for ProcStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Running => "R",
+            Self::Ready => "S",
+            Self::Blocked => "B",
+            Self::Dead => "Z",
+        };
+        f.pad(s)
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProcInfo {
+    pub pid: u16,
+    /// 0 if the process has no parent
+    pub ppid: u16,
+    pub status: ProcStatus,
+    pub nice: i8,
+    /// queue level in the `mlfq` scheduler, 0 is the highest
+    pub level: u8,
+    /// CPU running the process, or `NO_CPU`
+    pub cpu: u8,
+    name_len: u8,
+    name: [u8; PROC_NAME_LEN],
+    /// timer ticks spent in user mode
+    pub user_ticks: u64,
+    /// timer ticks spent in the kernel, e.g. in syscalls
+    pub kernel_ticks: u64,
+    /// memory used by the program, in bytes
+    pub code: u64,
+    pub stack: u64,
+    pub heap: u64,
+    /// time since boot the process was created at, in nanoseconds
+    pub start_ns: u64,
+}
+
+impl ProcInfo {
+    pub fn name(&self) -> &str {
+        let len = (self.name_len as usize).min(PROC_NAME_LEN);
+        core::str::from_utf8(&self.name[..len]).unwrap_or("?")
+    }
+
+    /// Set the name, truncated to `PROC_NAME_LEN` bytes on a char boundary
+    pub fn set_name(&mut self, name: &str) {
+        let mut len = name.len().min(PROC_NAME_LEN);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.name[..len].copy_from_slice(&name.as_bytes()[..len]);
+        self.name_len = len as u8;
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.user_ticks + self.kernel_ticks
+    }
+
+    /// Total memory used, in bytes
+    pub fn memory(&self) -> u64 {
+        self.code + self.stack + self.heap
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.cpu != NO_CPU
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_truncated_on_char_boundary() {
+        let mut info = ProcInfo::default();
+        info.set_name("sh");
+        assert_eq!(info.name(), "sh");
+
+        let long = "进程".repeat(8);
+        info.set_name(&long);
+        assert_eq!(info.name(), &long[..30]);
+    }
+}
@@ -0,0 +1,29 @@
+//! Process priorities, the same nice values as on Linux
+//!
+//! A lower nice value means a higher priority. Syscalls return
+//! `nice - NICE_MIN`, so the value can not be mistaken for an error.
+
+/// Highest priority
+pub const NICE_MIN: isize = -20;
+/// Lowest priority
+pub const NICE_MAX: isize = 19;
+/// Priority of new processes
+pub const NICE_DEFAULT: isize = 0;
+
+/// Clamp `nice` into `[NICE_MIN, NICE_MAX]`
+#[inline]
+pub fn clamp_nice(nice: isize) -> isize {
+    nice.clamp(NICE_MIN, NICE_MAX)
+}
+
+/// Encode a nice value as a syscall return value
+#[inline]
+pub fn encode_nice(nice: isize) -> usize {
+    (nice - NICE_MIN) as usize
+}
+
+/// Decode a nice value returned by a syscall
+#[inline]
+pub fn decode_nice(ret: usize) -> isize {
+    ret as isize + NICE_MIN
+}
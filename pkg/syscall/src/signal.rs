@@ -0,0 +1,154 @@
+//! Signal numbers shared by the kernel and the user library
+//!
+//! The numbers follow Linux on x86_64.
+
+use num_enum::TryFromPrimitive;
+
+/// Number of signal slots, valid signals are in `[1, NSIG)`
+pub const NSIG: usize = 32;
+
+/// Handler value: take the default action of the signal
+pub const SIG_DFL: usize = 0;
+/// Handler value: ignore the signal
+pub const SIG_IGN: usize = 1;
+
+/// `sigprocmask`: add signals to the blocked set
+pub const SIG_BLOCK: usize = 0;
+/// `sigprocmask`: remove signals from the blocked set
+pub const SIG_UNBLOCK: usize = 1;
+/// `sigprocmask`: replace the blocked set
+pub const SIG_SETMASK: usize = 2;
+
+#[repr(usize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, TryFromPrimitive)]
+pub enum Signal {
+    /// Interrupt from keyboard (Ctrl + C)
+    Int = 2,
+    /// Kill, can not be caught, blocked or ignored
+    Kill = 9,
+    /// User-defined signal 1
+    Usr1 = 10,
+    /// Invalid memory reference
+    Segv = 11,
+    /// User-defined signal 2
+    Usr2 = 12,
+    /// Termination request
+    Term = 15,
+    /// Child stopped or terminated
+    Chld = 17,
+}
+
+/// What happens to a process receiving a signal with `SIG_DFL`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DefaultAction {
+    Terminate,
+    Ignore,
+}
+
+impl Signal {
+    #[inline]
+    pub fn number(self) -> usize {
+        self as usize
+    }
+
+    /// Whether a handler may be installed for, or a mask may block, this signal
+    #[inline]
+    pub fn can_catch(self) -> bool {
+        self != Signal::Kill
+    }
+
+    #[inline]
+    pub fn default_action(self) -> DefaultAction {
+        match self {
+            Signal::Chld => DefaultAction::Ignore,
+            _ => DefaultAction::Terminate,
+        }
+    }
+}
+
+impl core::fmt::Display for Signal {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "SIG{}", match self {
+            Signal::Int => "INT",
+            Signal::Kill => "KILL",
+            Signal::Usr1 => "USR1",
+            Signal::Segv => "SEGV",
+            Signal::Usr2 => "USR2",
+            Signal::Term => "TERM",
+            Signal::Chld => "CHLD",
+        })
+    }
+}
+
+/// A set of signals, bit `n` stands for signal `n`
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SigSet(pub u64);
+
+impl SigSet {
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    #[inline]
+    pub fn contains(self, sig: Signal) -> bool {
+        self.0 & (1 << sig.number()) != 0
+    }
+
+    #[inline]
+    pub fn insert(&mut self, sig: Signal) {
+        self.0 |= 1 << sig.number();
+    }
+
+    #[inline]
+    pub fn remove(&mut self, sig: Signal) {
+        self.0 &= !(1 << sig.number());
+    }
+
+    #[inline]
+    pub fn union(self, other: SigSet) -> SigSet {
+        Self(self.0 | other.0)
+    }
+
+    #[inline]
+    pub fn difference(self, other: SigSet) -> SigSet {
+        Self(self.0 & !other.0)
+    }
+
+    /// The lowest known signal in this set
+    pub fn first(self) -> Option<Signal> {
+        (1..NSIG)
+            .filter(|&n| self.0 & (1 << n) != 0)
+            .find_map(|n| Signal::try_from(n).ok())
+    }
+}
+
+impl From<Signal> for SigSet {
+    fn from(sig: Signal) -> Self {
+        Self(1 << sig.number())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sigset_ops() {
+        let mut set = SigSet::empty();
+        assert_eq!(set.first(), None);
+
+        set.insert(Signal::Term);
+        set.insert(Signal::Int);
+        assert!(set.contains(Signal::Term));
+        assert!(!set.contains(Signal::Kill));
+        assert_eq!(set.first(), Some(Signal::Int));
+
+        set.remove(Signal::Int);
+        assert_eq!(set.first(), Some(Signal::Term));
+        assert_eq!(set.difference(Signal::Term.into()), SigSet::empty());
+
+        // unknown signals are kept but never reported
+        assert_eq!(SigSet(1 << 31).first(), None);
+    }
+}
@@ -0,0 +1,105 @@
+//! Arguments and results of `waitpid`
+//!
+//! The reaped pid and its status are packed into one return value:
+//! bits `[0, 16)` hold the pid, bits `[16, 24)` the signal that terminated
+//! the process (0 for a normal exit) and bits `[24, 56)` the exit code.
+
+use core::fmt;
+
+use crate::signal::Signal;
+
+/// `pid` argument: wait for any child of the caller
+pub const WAIT_ANY: isize = -1;
+
+/// `options` flag: return at once if no child has exited
+pub const WNOHANG: usize = 1;
+
+/// How a process terminated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The process called `exit` or returned from `main`
+    Exited(isize),
+    /// The process was terminated by the kernel, e.g. killed or faulted
+    Signaled(Signal),
+}
+
+impl ExitStatus {
+    /// Exit code of a normal exit
+    pub fn code(self) -> Option<isize> {
+        match self {
+            Self::Exited(code) => Some(code),
+            Self::Signaled(_) => None,
+        }
+    }
+
+    /// Signal that terminated the process
+    pub fn signal(self) -> Option<Signal> {
+        match self {
+            Self::Exited(_) => None,
+            Self::Signaled(sig) => Some(sig),
+        }
+    }
+
+    pub fn success(self) -> bool {
+        self == Self::Exited(0)
+    }
+
+    /// Exit code as reported by a shell, `128 + signal` if terminated
+    pub fn shell_code(self) -> isize {
+        match self {
+            Self::Exited(code) => code,
+            Self::Signaled(sig) => 128 + sig.number() as isize,
+        }
+    }
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exited(code) => write!(f, "exited with code {}", code),
+            Self::Signaled(sig) => write!(f, "terminated by {}", sig),
+        }
+    }
+}
+
+/// Pack the result of `waitpid`, exit codes are truncated to `i32`
+pub fn encode_wait(pid: u16, status: ExitStatus) -> usize {
+    let status = match status {
+        ExitStatus::Exited(code) => (code as i32 as u32 as usize) << 8,
+        ExitStatus::Signaled(sig) => sig.number(),
+    };
+    (status << 16) | pid as usize
+}
+
+/// Unpack the result of `waitpid`
+pub fn decode_wait(ret: usize) -> (u16, ExitStatus) {
+    let pid = ret as u16;
+    let status = match Signal::try_from((ret >> 16) & 0xff) {
+        Ok(sig) => ExitStatus::Signaled(sig),
+        Err(_) => ExitStatus::Exited((ret >> 24) as u32 as i32 as isize),
+    };
+    (pid, status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_result;
+
+    #[test]
+    fn wait_status_roundtrip() {
+        for status in [
+            ExitStatus::Exited(0),
+            ExitStatus::Exited(64),
+            ExitStatus::Exited(-1),
+            ExitStatus::Signaled(Signal::Segv),
+        ] {
+            let ret = encode_wait(42, status);
+            // never mistaken for an error
+            assert_eq!(decode_result(ret), Ok(ret));
+            assert_eq!(decode_wait(ret), (42, status));
+        }
+
+        assert_eq!(ExitStatus::Signaled(Signal::Int).shell_code(), 130);
+    }
+}